@@ -0,0 +1,4 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset name="tilesheet" tilewidth="32" tileheight="32" tilecount="84" columns="14">
+ <image source="../tilesheet.png" width="448" height="192"/>
+</tileset>
@@ -0,0 +1,8 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset name="external" tilewidth="32" tileheight="32" tilecount="1" columns="1">
+ <tile id="0">
+  <objectgroup draworder="index">
+   <object id="1" template="collision_box.tx" x="2" y="2"/>
+  </objectgroup>
+ </tile>
+</tileset>
@@ -0,0 +1,22 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.2" tiledversion="1.2.4" name="animated" tilewidth="32" tileheight="32" tilecount="4" columns="4">
+ <image source="tilesheet.png" width="128" height="32"/>
+ <tile id="0">
+  <animation>
+   <frame tileid="0" duration="100"/>
+   <frame tileid="1" duration="100"/>
+   <frame tileid="2" duration="250"/>
+  </animation>
+ </tile>
+ <tile id="1">
+  <properties>
+   <property name="still" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="3">
+  <animation>
+   <frame tileid="3" duration="100"/>
+   <frame tileid="4" duration="100"/>
+  </animation>
+ </tile>
+</tileset>
@@ -0,0 +1,10 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.2" tiledversion="1.2.4" name="props" tilewidth="64" tileheight="96" tilecount="2" columns="0">
+ <grid orientation="orthogonal" width="1" height="1"/>
+ <tile id="0">
+  <image width="32" height="32" source="rock.png"/>
+ </tile>
+ <tile id="1">
+  <image width="64" height="96" source="tree.png" trans="ff00ff"/>
+ </tile>
+</tileset>
@@ -0,0 +1,14 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.2" tiledversion="1.2.4" name="platforms" tilewidth="32" tileheight="32" tilecount="84" columns="14">
+ <image source="tilesheet.png" width="448" height="192"/>
+ <tile id="4">
+  <objectgroup draworder="index">
+   <object id="1" x="0" y="8">
+    <properties>
+     <property name="oneway" type="bool" value="true"/>
+    </properties>
+    <polygon points="0,0 32,0 32,4 0,4"/>
+   </object>
+  </objectgroup>
+ </tile>
+</tileset>
@@ -0,0 +1,4 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.10" tiledversion="1.10.2" name="spaced" tilewidth="16" tileheight="16" spacing="2" margin="1" tilecount="8" columns="4">
+ <image source="spaced.png" width="72" height="36"/>
+</tileset>
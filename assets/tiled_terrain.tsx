@@ -0,0 +1,11 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.2" tiledversion="1.2.4" name="terrain" tilewidth="32" tileheight="32" tilecount="84" columns="14">
+ <image source="tilesheet.png" width="448" height="192"/>
+ <terraintypes>
+  <terrain name="Grass" tile="15"/>
+  <terrain name="Water" tile="-1"/>
+ </terraintypes>
+ <tile id="0" terrain="0,0,0,1"/>
+ <tile id="1" terrain=",1,,1"/>
+ <tile id="2"/>
+</tileset>
@@ -0,0 +1,19 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.5" tiledversion="1.5.0" name="terrain" tilewidth="32" tileheight="32" tilecount="84" columns="14">
+ <image source="tilesheet.png" width="448" height="192"/>
+ <wangsets>
+  <wangset name="Ground" type="corner" tile="-1">
+   <properties>
+    <property name="layer" value="floor"/>
+   </properties>
+   <wangcolor name="Grass" color="#00ff00" tile="2" probability="0.5">
+    <properties>
+     <property name="walkable" type="bool" value="true"/>
+    </properties>
+   </wangcolor>
+   <wangcolor name="Water" color="#0000ff" tile="-1" probability="1"/>
+   <wangtile tileid="0" wangid="0,1,0,1,0,1,0,1"/>
+   <wangtile tileid="1" wangid="0,2,0,1,0,2,0,2"/>
+  </wangset>
+ </wangsets>
+</tileset>
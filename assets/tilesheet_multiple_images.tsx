@@ -0,0 +1,5 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset name="tilesheet" tilewidth="32" tileheight="32" tilecount="84">
+ <image source="tilesheet.png" width="448" height="192"/>
+ <image source="tilesheet_alt.png" width="448" height="192"/>
+</tileset>
@@ -0,0 +1,17 @@
+#[macro_use]
+extern crate criterion;
+extern crate tiled;
+
+use criterion::Criterion;
+use std::fs;
+use tiled::Map;
+
+fn parse_large_zlib_map(c: &mut Criterion) {
+    let tmx = fs::read("assets/tiled_large_zlib.tmx").unwrap();
+    c.bench_function("parse 256x256 zlib map", |b| {
+        b.iter(|| Map::parse(&tmx[..]).unwrap())
+    });
+}
+
+criterion_group!(benches, parse_large_zlib_map);
+criterion_main!(benches);
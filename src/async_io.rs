@@ -0,0 +1,86 @@
+use crate::{
+    error::Error,
+    map::{external_sources, open_loaded, resolve_relative, Map, ParseOptions},
+};
+use std::{collections::HashMap, future::Future, path::Path};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+impl Map {
+    /// Like `parse`, for a map read from an asynchronous stream. The whole
+    /// stream is read into memory first, then parsed.
+    pub async fn parse_async<R: AsyncRead + Unpin>(reader: R) -> Result<Map, Error> {
+        let data = read_to_end(reader).await?;
+        Map::parse(&data[..])
+    }
+
+    /// Like `parse_with_loader`, with a loader that returns the contents of an
+    /// external tileset or object template asynchronously. All of them are
+    /// loaded before the map is parsed.
+    pub async fn parse_with_loader_async<R, F, Fut>(reader: R, loader: F) -> Result<Map, Error>
+    where
+        R: AsyncRead + Unpin,
+        F: FnMut(String) -> Fut,
+        Fut: Future<Output = Result<Vec<u8>, Error>>,
+    {
+        let data = read_to_end(reader).await?;
+        let files = load_external_files(&data, loader).await?;
+        Map::parse_with_loader(&data[..], |source| open_loaded(&files, source))
+    }
+
+    /// Like `parse_file`, reading the map, its external tilesets and its
+    /// object templates with `tokio::fs`.
+    pub async fn parse_file_async(path: &Path) -> Result<Map, Error> {
+        let data = tokio::fs::read(path)
+            .await
+            .map_err(|_| Error::Other(format!("Map file not found: {:?}", path)))?;
+        let files = load_external_files(&data, |source| {
+            let path = resolve_relative(path, &source);
+            async move {
+                tokio::fs::read(&path)
+                    .await
+                    .map_err(|_| Error::Other(format!("External file not found: {:?}", path)))
+            }
+        })
+        .await?;
+        Map::parse_impl(
+            &data[..],
+            Some(path),
+            &mut |source: &str| open_loaded(&files, source),
+            None,
+            &mut ParseOptions::default(),
+        )
+    }
+}
+
+async fn read_to_end<R: AsyncRead + Unpin>(mut reader: R) -> Result<Vec<u8>, Error> {
+    let mut data = Vec::new();
+    reader
+        .read_to_end(&mut data)
+        .await
+        .map_err(|e| Error::Other(format!("Failed to read the map: {}", e)))?;
+    Ok(data)
+}
+
+/// Loads the contents of every external tileset and object template of the
+/// map in `data`, keyed by their `source` or `template` attribute, along with
+/// the templates used by those tilesets.
+async fn load_external_files<F, Fut>(
+    data: &[u8],
+    mut loader: F,
+) -> Result<HashMap<String, Vec<u8>>, Error>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<Vec<u8>, Error>>,
+{
+    let mut files = HashMap::new();
+    let mut sources = external_sources(data, None)?;
+    while let Some(source) = sources.pop() {
+        if files.contains_key(&source) {
+            continue;
+        }
+        let contents = loader(source.clone()).await?;
+        sources.extend(external_sources(&contents, Some(&source))?);
+        files.insert(source, contents);
+    }
+    Ok(files)
+}
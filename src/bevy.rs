@@ -0,0 +1,72 @@
+//! Integration with Bevy assets
+use crate::{
+    error::Error,
+    map::{external_sources, open_loaded, resolve_relative, Map},
+};
+use bevy_app::{App, Plugin};
+use bevy_asset::{io::Reader, Asset, AssetApp, AssetLoader, LoadContext};
+use bevy_reflect::TypePath;
+use std::collections::HashMap;
+
+/// A map loaded by the asset server.
+#[derive(Asset, TypePath, Debug)]
+pub struct TiledMap {
+    pub map: Map,
+}
+
+/// Loads `.tmx` files as `TiledMap`s. External tilesets and object templates,
+/// including those used by the external tilesets, are read through the asset
+/// server, relative to the map.
+#[derive(Clone, Debug, Default, TypePath)]
+pub struct TmxLoader;
+
+impl AssetLoader for TmxLoader {
+    type Asset = TiledMap;
+    type Settings = ();
+    type Error = Error;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<TiledMap, Error> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(|e| Error::Other(format!("Failed to read the map: {}", e)))?;
+        let mut files = HashMap::new();
+        let mut sources = external_sources(&bytes, None)?;
+        while let Some(source) = sources.pop() {
+            if files.contains_key(&source) {
+                continue;
+            }
+            let path = resolve_relative(load_context.asset_path().path(), &source);
+            let contents = load_context
+                .read_asset_bytes(path.clone())
+                .await
+                .map_err(|_| Error::Other(format!("External file not found: {:?}", path)))?;
+            sources.extend(external_sources(&contents, Some(&source))?);
+            files.insert(source, contents);
+        }
+        let map = Map::parse_with_loader(&bytes[..], |source| open_loaded(&files, source))?;
+        Ok(TiledMap { map })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tmx"]
+    }
+}
+
+/// Registers `TiledMap` and its loader with the app, so that maps can be
+/// loaded with `asset_server.load("level.tmx")`.
+#[derive(Clone, Debug, Default)]
+pub struct TiledPlugin;
+
+impl Plugin for TiledPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<TiledMap>()
+            .init_asset_loader::<TmxLoader>();
+    }
+}
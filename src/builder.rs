@@ -0,0 +1,94 @@
+use crate::{
+    error::Error,
+    layer::Layer,
+    map::{Map, Orientation, SUPPORTED_TILED_VERSION},
+    tileset::Tileset,
+};
+
+/// Builds a `Map` from code, for example to generate levels that are then
+/// saved with `Map::write`.
+///
+/// Tilesets and layers are kept in the order they are added, and their
+/// `tileset_index` and `layer_index` are set to match.
+#[derive(Debug, Clone)]
+pub struct MapBuilder {
+    map: Map,
+}
+
+impl MapBuilder {
+    /// Starts an orthogonal map with no tilesets or layers, saved as the
+    /// newest version of the format this crate supports.
+    pub fn new() -> MapBuilder {
+        MapBuilder {
+            map: Map {
+                version: SUPPORTED_TILED_VERSION.to_string(),
+                ..Map::default()
+            },
+        }
+    }
+
+    pub fn orientation(mut self, orientation: Orientation) -> MapBuilder {
+        self.map.orientation = orientation;
+        self
+    }
+
+    /// The size of the map, in tiles.
+    pub fn dimensions(mut self, width: u32, height: u32) -> MapBuilder {
+        self.map.width = width;
+        self.map.height = height;
+        self
+    }
+
+    /// The size of a tile of the map, in pixels.
+    pub fn tile_size(mut self, tile_width: u32, tile_height: u32) -> MapBuilder {
+        self.map.tile_width = tile_width;
+        self.map.tile_height = tile_height;
+        self
+    }
+
+    pub fn add_tileset(mut self, mut tileset: Tileset) -> MapBuilder {
+        tileset.tileset_index = self.map.tilesets.len();
+        self.map.tilesets.push(tileset);
+        self
+    }
+
+    pub fn add_layer(mut self, mut layer: Layer) -> MapBuilder {
+        layer.layer_index = self.map.layers.len() as u32;
+        self.map.layers.push(layer);
+        self
+    }
+
+    /// Returns the map, after checking that it has a size, that its tiles
+    /// have a size and that no two tilesets share a `first_gid`.
+    pub fn build(self) -> Result<Map, Error> {
+        let map = self.map;
+        if map.width == 0 || map.height == 0 {
+            return Err(Error::MalformedAttributes(
+                "maps must have a non-zero width and height".to_string(),
+            ));
+        }
+        if map.tile_width == 0 || map.tile_height == 0 {
+            return Err(Error::MalformedAttributes(
+                "maps must have a non-zero tile width and height".to_string(),
+            ));
+        }
+        for (i, tileset) in map.tilesets.iter().enumerate() {
+            if map.tilesets[..i]
+                .iter()
+                .any(|other| other.first_gid == tileset.first_gid)
+            {
+                return Err(Error::MalformedAttributes(format!(
+                    "more than one tileset has the first gid {}",
+                    tileset.first_gid
+                )));
+            }
+        }
+        Ok(map)
+    }
+}
+
+impl Default for MapBuilder {
+    fn default() -> Self {
+        MapBuilder::new()
+    }
+}
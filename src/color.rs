@@ -1,29 +1,61 @@
 use crate::error::ParseTileError;
 use std::str::FromStr;
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+/// Colors are ordered by their red, green, blue and then alpha components.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
 pub struct Color {
     pub red: u8,
     pub green: u8,
     pub blue: u8,
+    /// 255 (fully opaque) unless the color was written as `#AARRGGBB`.
+    pub alpha: u8,
+}
+
+impl Color {
+    /// Linearly interpolates every component, alpha included, from `self` at
+    /// `t = 0.0` to `other` at `t = 1.0`. `t` is clamped to that range.
+    pub fn blend(&self, other: &Color, t: f32) -> Color {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let mix = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * t).round() as u8;
+        Color {
+            red: mix(self.red, other.red),
+            green: mix(self.green, other.green),
+            blue: mix(self.blue, other.blue),
+            alpha: mix(self.alpha, other.alpha),
+        }
+    }
+
+    /// Packs the color as `0xAARRGGBB`, the same order Tiled writes it in.
+    pub fn to_u32(&self) -> u32 {
+        u32::from_be_bytes([self.alpha, self.red, self.green, self.blue])
+    }
 }
 
 impl FromStr for Color {
     type Err = ParseTileError;
 
+    /// Parses either the `#RRGGBB` or the `#AARRGGBB` form. The leading `#` is
+    /// optional.
     fn from_str(s: &str) -> Result<Color, ParseTileError> {
         let s = if s.starts_with("#") { &s[1..] } else { s };
-        if s.len() != 6 {
+        // The digits are sliced by byte, which only lines up with ASCII.
+        if !s.is_ascii() {
             return Err(ParseTileError::ColorError);
         }
+        let (a, s) = match s.len() {
+            6 => (Ok(255), s),
+            8 => (u8::from_str_radix(&s[0..2], 16), &s[2..]),
+            _ => return Err(ParseTileError::ColorError),
+        };
         let r = u8::from_str_radix(&s[0..2], 16);
         let g = u8::from_str_radix(&s[2..4], 16);
         let b = u8::from_str_radix(&s[4..6], 16);
-        if r.is_ok() && g.is_ok() && b.is_ok() {
+        if r.is_ok() && g.is_ok() && b.is_ok() && a.is_ok() {
             return Ok(Color {
                 red: r.unwrap(),
                 green: g.unwrap(),
                 blue: b.unwrap(),
+                alpha: a.unwrap(),
             });
         }
         Err(ParseTileError::ColorError)
@@ -1,5 +1,7 @@
 use std::fmt;
+use xml::common::TextPosition;
 use xml::reader::Error as XmlError;
+use xml::writer::Error as XmlWriterError;
 
 /// Errors which occured when parsing the file
 #[derive(Debug)]
@@ -10,45 +12,98 @@ pub enum Error {
     /// An error occured when decompressing using the
     /// [flate2](https://github.com/alexcrichton/flate2-rs) crate.
     DecompressingError(std::io::Error),
+    /// An error occured when reading or writing, other than while
+    /// decompressing the layer data.
+    Io(std::io::Error),
     Base64DecodingError(base64::DecodeError),
     XmlDecodingError(XmlError),
+    /// An error occured when writing a map with `Map::write`.
+    XmlEncodingError(XmlWriterError),
+    /// An error occured when reading a map with `Map::parse_json`. The
+    /// variant exists without the `json` feature too, so that matching on
+    /// `Error` doesn't depend on the enabled features.
+    JsonDecodingError(Box<dyn std::error::Error + Send + Sync>),
+    /// An error occured when writing a map with `Map::write_json`.
+    JsonEncodingError(Box<dyn std::error::Error + Send + Sync>),
     PrematureEnd(String),
+    /// The file uses something Tiled can write but this crate can't read, such
+    /// as object templates in a JSON map.
+    Unsupported(String),
     Other(String),
 }
 
+impl Error {
+    /// Prefixes the message of errors that don't carry a position of their own
+    /// with the `row:column` the parser was at.
+    pub(crate) fn at_position(self, position: TextPosition) -> Error {
+        match self {
+            Error::MalformedAttributes(s) => {
+                Error::MalformedAttributes(format!("{}: {}", position, s))
+            }
+            Error::PrematureEnd(s) => Error::PrematureEnd(format!("{}: {}", position, s)),
+            Error::Unsupported(s) => Error::Unsupported(format!("{}: {}", position, s)),
+            Error::Other(s) => Error::Other(format!("{}: {}", position, s)),
+            e => e,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<base64::DecodeError> for Error {
+    fn from(e: base64::DecodeError) -> Error {
+        Error::Base64DecodingError(e)
+    }
+}
+
+impl From<XmlError> for Error {
+    fn from(e: XmlError) -> Error {
+        Error::XmlDecodingError(e)
+    }
+}
+
+impl From<XmlWriterError> for Error {
+    fn from(e: XmlWriterError) -> Error {
+        Error::XmlEncodingError(e)
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         match *self {
             Error::MalformedAttributes(ref s) => write!(fmt, "{}", s),
             Error::DecompressingError(ref e) => write!(fmt, "{}", e),
+            Error::Io(ref e) => write!(fmt, "{}", e),
             Error::Base64DecodingError(ref e) => write!(fmt, "{}", e),
             Error::XmlDecodingError(ref e) => write!(fmt, "{}", e),
+            Error::XmlEncodingError(ref e) => write!(fmt, "{}", e),
+            Error::JsonDecodingError(ref e) => write!(fmt, "{}", e),
+            Error::JsonEncodingError(ref e) => write!(fmt, "{}", e),
             Error::PrematureEnd(ref e) => write!(fmt, "{}", e),
+            Error::Unsupported(ref s) => write!(fmt, "{}", s),
             Error::Other(ref s) => write!(fmt, "{}", s),
         }
     }
 }
 
-// This is a skeleton implementation, which should probably be extended in the future.
 impl std::error::Error for Error {
-    fn description(&self) -> &str {
-        match *self {
-            Error::MalformedAttributes(ref s) => s.as_ref(),
-            Error::DecompressingError(ref e) => e.description(),
-            Error::Base64DecodingError(ref e) => e.description(),
-            Error::XmlDecodingError(ref e) => e.description(),
-            Error::PrematureEnd(ref s) => s.as_ref(),
-            Error::Other(ref s) => s.as_ref(),
-        }
-    }
-    fn cause(&self) -> Option<&std::error::Error> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match *self {
-            Error::MalformedAttributes(_) => None,
-            Error::DecompressingError(ref e) => Some(e as &std::error::Error),
-            Error::Base64DecodingError(ref e) => Some(e as &std::error::Error),
-            Error::XmlDecodingError(ref e) => Some(e as &std::error::Error),
-            Error::PrematureEnd(_) => None,
-            Error::Other(_) => None,
+            Error::DecompressingError(ref e) => Some(e),
+            Error::Io(ref e) => Some(e),
+            Error::Base64DecodingError(ref e) => Some(e),
+            Error::XmlDecodingError(ref e) => Some(e),
+            Error::XmlEncodingError(ref e) => Some(e),
+            Error::JsonDecodingError(ref e) => Some(&**e),
+            Error::JsonEncodingError(ref e) => Some(&**e),
+            Error::MalformedAttributes(_)
+            | Error::PrematureEnd(_)
+            | Error::Unsupported(_)
+            | Error::Other(_) => None,
         }
     }
 }
@@ -57,4 +112,8 @@ impl std::error::Error for Error {
 pub enum ParseTileError {
     ColorError,
     OrientationError,
+    AlignmentError,
+    RenderOrderError,
+    StaggerAxisError,
+    DrawOrderError,
 }
@@ -0,0 +1,439 @@
+use crate::{
+    color::Color,
+    image::Image,
+    layer::{Group, ImageLayer, Layer, LayerKind},
+    map::Map,
+    object::{Object, ObjectGroup, ObjectShape},
+    property::{Properties, PropertyValue},
+    tile::Tile,
+    tileset::Tileset,
+    wangset::WangSet,
+    writer::{draw_order, halign, render_order, stagger_axis, valign},
+};
+use std::collections::HashMap;
+
+impl Map {
+    /// A hash of the contents of this map, for detecting changes without
+    /// comparing whole maps. Two maps with the same contents have the same
+    /// fingerprint, whatever the platform, the version of Rust or the order in
+    /// which their properties were inserted. Fields that the parser derives
+    /// rather than reads from the file, such as `layer_index` and
+    /// `tileset_index`, are excluded, but the layers are hashed in the order
+    /// that `layer_index` gives them, so that changing the order in which
+    /// they are drawn changes the fingerprint.
+    pub fn fingerprint(&self) -> u64 {
+        let mut fingerprint = Fingerprint::new();
+        fingerprint.map(self);
+        fingerprint.0
+    }
+}
+
+/// A 64-bit FNV-1a hash that every field is fed to in an encoding of its own,
+/// rather than through `Hash`, whose output std doesn't promise to keep.
+/// Strings and lists are prefixed with their length, and maps keyed by name
+/// are fed in key order.
+struct Fingerprint(u64);
+
+impl Fingerprint {
+    fn new() -> Fingerprint {
+        Fingerprint(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.bytes(&[value as u8]);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn i32(&mut self, value: i32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.u32(value.to_bits());
+    }
+
+    fn len(&mut self, len: usize) {
+        self.bytes(&(len as u64).to_le_bytes());
+    }
+
+    fn str(&mut self, value: &str) {
+        self.len(value.len());
+        self.bytes(value.as_bytes());
+    }
+
+    fn opt<T>(&mut self, value: Option<T>, f: impl FnOnce(&mut Fingerprint, T)) {
+        self.bool(value.is_some());
+        if let Some(value) = value {
+            f(self, value);
+        }
+    }
+
+    fn opt_str(&mut self, value: &Option<String>) {
+        self.opt(value.as_deref(), Fingerprint::str);
+    }
+
+    fn color(&mut self, color: Color) {
+        self.bytes(&[color.red, color.green, color.blue, color.alpha]);
+    }
+
+    fn points(&mut self, points: &[(f32, f32)]) {
+        self.len(points.len());
+        for &(x, y) in points.iter() {
+            self.f32(x);
+            self.f32(y);
+        }
+    }
+
+    fn properties(&mut self, properties: &Properties) {
+        let properties = sorted(properties);
+        self.len(properties.len());
+        for (name, value) in properties {
+            self.str(name);
+            match *value {
+                PropertyValue::BoolValue(v) => {
+                    self.bytes(&[0]);
+                    self.bool(v);
+                }
+                PropertyValue::FloatValue(v) => {
+                    self.bytes(&[1]);
+                    self.f32(v);
+                }
+                PropertyValue::IntValue(v) => {
+                    self.bytes(&[2]);
+                    self.i32(v);
+                }
+                PropertyValue::ColorValue(v) => {
+                    self.bytes(&[3]);
+                    self.u32(v);
+                }
+                PropertyValue::StringValue(ref v) => {
+                    self.bytes(&[4]);
+                    self.str(v);
+                }
+                PropertyValue::FileValue(ref v) => {
+                    self.bytes(&[5]);
+                    self.str(&v.to_string_lossy());
+                }
+                PropertyValue::ObjectValue(v) => {
+                    self.bytes(&[6]);
+                    self.u32(v);
+                }
+            }
+        }
+    }
+
+    fn extra(&mut self, extra: &HashMap<String, String>) {
+        let extra = sorted(extra);
+        self.len(extra.len());
+        for (name, value) in extra {
+            self.str(name);
+            self.str(value);
+        }
+    }
+
+    fn image(&mut self, image: &Image) {
+        self.str(&image.source);
+        self.u32(image.width);
+        self.u32(image.height);
+        self.opt(image.transparent_color, Fingerprint::color);
+    }
+
+    fn images(&mut self, images: &[Image]) {
+        self.len(images.len());
+        for image in images.iter() {
+            self.image(image);
+        }
+    }
+
+    fn map(&mut self, map: &Map) {
+        self.str(&map.version);
+        self.opt_str(&map.class);
+        self.str(&map.orientation.to_string());
+        self.str(render_order(map.render_order));
+        self.u32(map.width);
+        self.u32(map.height);
+        self.u32(map.tile_width);
+        self.u32(map.tile_height);
+        self.opt(map.stagger_axis, |f, axis| f.str(stagger_axis(axis)));
+        self.opt(map.hex_side_length, Fingerprint::u32);
+        self.bool(map.infinite);
+        self.opt(map.background_color, Fingerprint::color);
+        self.opt(map.next_layer_id, Fingerprint::u32);
+        self.opt(map.next_object_id, Fingerprint::u32);
+        self.opt(map.chunk_size, |f, (width, height)| {
+            f.u32(width);
+            f.u32(height);
+        });
+        self.properties(&map.properties);
+        self.extra(&map.extra);
+        self.len(map.tilesets.len());
+        for tileset in map.tilesets.iter() {
+            self.tileset(tileset);
+        }
+        self.layers(LayerKind::in_order(
+            &map.layers,
+            &map.image_layers,
+            &map.object_groups,
+            &map.groups,
+        ));
+    }
+
+    fn tileset(&mut self, tileset: &Tileset) {
+        self.u32(tileset.first_gid);
+        self.str(&tileset.name);
+        self.opt_str(&tileset.class);
+        self.u32(tileset.tile_width);
+        self.u32(tileset.tile_height);
+        self.u32(tileset.spacing);
+        self.u32(tileset.margin);
+        self.opt(tileset.tile_count, Fingerprint::u32);
+        self.opt(tileset.columns, Fingerprint::u32);
+        self.images(&tileset.images);
+        self.len(tileset.tiles.len());
+        for tile in tileset.tiles.iter() {
+            self.tile(tile);
+        }
+        self.len(tileset.wang_sets.len());
+        for wang_set in tileset.wang_sets.iter() {
+            self.wang_set(wang_set);
+        }
+        self.len(tileset.terrain_types.len());
+        for terrain in tileset.terrain_types.iter() {
+            self.str(&terrain.name);
+            self.i32(terrain.tile);
+        }
+        self.opt(tileset.source.as_deref(), |f, source| {
+            f.str(&source.to_string_lossy())
+        });
+        self.extra(&tileset.extra);
+    }
+
+    fn tile(&mut self, tile: &Tile) {
+        self.u32(tile.id);
+        self.bool(tile.flip_h);
+        self.bool(tile.flip_v);
+        self.bool(tile.flip_d);
+        self.images(&tile.images);
+        self.properties(&tile.properties);
+        self.opt(tile.objectgroup.as_ref(), Fingerprint::object_group);
+        self.opt(tile.animation.as_deref(), |f, frames| {
+            f.len(frames.len());
+            for frame in frames.iter() {
+                f.u32(frame.tile_id);
+                f.u32(frame.duration);
+            }
+        });
+        self.opt_str(&tile.tile_type);
+        self.f32(tile.probability);
+        self.opt(tile.terrain, |f, terrain| {
+            for &corner in terrain.iter() {
+                f.opt(corner, Fingerprint::u32);
+            }
+        });
+    }
+
+    fn wang_set(&mut self, wang_set: &WangSet) {
+        self.str(&wang_set.name);
+        self.opt_str(&wang_set.class);
+        self.opt_str(&wang_set.wang_type);
+        self.opt(wang_set.tile, Fingerprint::u32);
+        self.len(wang_set.colors.len());
+        for color in wang_set.colors.iter() {
+            self.str(&color.name);
+            self.opt_str(&color.class);
+            self.color(color.color);
+            self.opt(color.tile, Fingerprint::u32);
+            self.f32(color.probability);
+            self.properties(&color.properties);
+        }
+        self.len(wang_set.tiles.len());
+        for wang_tile in wang_set.tiles.iter() {
+            self.u32(wang_tile.tile_id);
+            self.bytes(&wang_tile.wang_id);
+        }
+        self.properties(&wang_set.properties);
+    }
+
+    /// Layers of every kind, in draw order, each preceded by its kind.
+    fn layers(&mut self, layers: Vec<LayerKind>) {
+        self.len(layers.len());
+        for layer in layers {
+            match layer {
+                LayerKind::Tile(layer) => {
+                    self.bytes(&[0]);
+                    self.layer(layer);
+                }
+                LayerKind::Image(layer) => {
+                    self.bytes(&[1]);
+                    self.image_layer(layer);
+                }
+                LayerKind::Objects(layer) => {
+                    self.bytes(&[2]);
+                    self.object_group(layer);
+                }
+                LayerKind::Group(layer) => {
+                    self.bytes(&[3]);
+                    self.group(layer);
+                }
+            }
+        }
+    }
+
+    fn layer(&mut self, layer: &Layer) {
+        self.opt(layer.id, Fingerprint::u32);
+        self.str(&layer.name);
+        self.opt_str(&layer.class);
+        self.f32(layer.opacity);
+        self.bool(layer.visible);
+        self.bool(layer.locked);
+        self.opt(layer.tint_color, Fingerprint::color);
+        self.f32(layer.offset_x);
+        self.f32(layer.offset_y);
+        self.len(layer.tiles.len());
+        for row in layer.tiles.iter() {
+            self.len(row.len());
+            for &gid in row.iter() {
+                self.u32(gid);
+            }
+        }
+        self.len(layer.chunks.len());
+        for chunk in layer.chunks.iter() {
+            self.i32(chunk.x);
+            self.i32(chunk.y);
+            self.u32(chunk.width);
+            self.u32(chunk.height);
+            self.len(chunk.tiles.len());
+            for row in chunk.tiles.iter() {
+                self.len(row.len());
+                for &gid in row.iter() {
+                    self.u32(gid);
+                }
+            }
+        }
+        self.properties(&layer.properties);
+        self.extra(&layer.extra);
+    }
+
+    fn image_layer(&mut self, image_layer: &ImageLayer) {
+        self.opt(image_layer.id, Fingerprint::u32);
+        self.str(&image_layer.name);
+        self.opt_str(&image_layer.class);
+        self.f32(image_layer.opacity);
+        self.bool(image_layer.visible);
+        self.bool(image_layer.locked);
+        self.opt(image_layer.tint_color, Fingerprint::color);
+        self.f32(image_layer.offset_x);
+        self.f32(image_layer.offset_y);
+        self.opt(image_layer.image.as_ref(), Fingerprint::image);
+        self.properties(&image_layer.properties);
+        self.extra(&image_layer.extra);
+    }
+
+    fn object_group(&mut self, object_group: &ObjectGroup) {
+        self.opt(object_group.id, Fingerprint::u32);
+        self.str(&object_group.name);
+        self.opt_str(&object_group.class);
+        self.f32(object_group.opacity);
+        self.bool(object_group.visible);
+        self.bool(object_group.locked);
+        self.opt(object_group.color, Fingerprint::color);
+        self.str(draw_order(object_group.draw_order));
+        self.extra(&object_group.extra);
+        self.len(object_group.objects.len());
+        for object in object_group.objects.iter() {
+            self.object(object);
+        }
+    }
+
+    fn object(&mut self, object: &Object) {
+        self.u32(object.id);
+        self.u32(object.gid);
+        self.str(&object.name);
+        self.str(&object.obj_type);
+        self.f32(object.x);
+        self.f32(object.y);
+        self.f32(object.rotation);
+        self.bool(object.visible);
+        match object.shape {
+            ObjectShape::Rect { width, height } => {
+                self.bytes(&[0]);
+                self.f32(width);
+                self.f32(height);
+            }
+            ObjectShape::Ellipse { width, height } => {
+                self.bytes(&[1]);
+                self.f32(width);
+                self.f32(height);
+            }
+            ObjectShape::Polyline { ref points } => {
+                self.bytes(&[2]);
+                self.points(points);
+            }
+            ObjectShape::Polygon { ref points } => {
+                self.bytes(&[3]);
+                self.points(points);
+            }
+            ObjectShape::Point => self.bytes(&[4]),
+            ObjectShape::Text {
+                width,
+                height,
+                ref text,
+            } => {
+                self.bytes(&[5]);
+                self.f32(width);
+                self.f32(height);
+                self.str(&text.font_family);
+                self.u32(text.pixel_size);
+                self.bool(text.wrap);
+                self.color(text.color);
+                self.bool(text.bold);
+                self.bool(text.italic);
+                self.bool(text.underline);
+                self.bool(text.strikeout);
+                self.bool(text.kerning);
+                self.str(halign(text.halign));
+                self.str(valign(text.valign));
+                self.str(&text.contents);
+            }
+        }
+        self.properties(&object.properties);
+        self.extra(&object.extra);
+    }
+
+    fn group(&mut self, group: &Group) {
+        self.opt(group.id, Fingerprint::u32);
+        self.str(&group.name);
+        self.opt_str(&group.class);
+        self.f32(group.opacity);
+        self.bool(group.visible);
+        self.bool(group.locked);
+        self.opt(group.tint_color, Fingerprint::color);
+        self.f32(group.offset_x);
+        self.f32(group.offset_y);
+        self.properties(&group.properties);
+        self.extra(&group.extra);
+        self.layers(LayerKind::in_order(
+            &group.layers,
+            &group.image_layers,
+            &group.object_groups,
+            &group.groups,
+        ));
+    }
+}
+
+/// The entries of `map` in key order.
+fn sorted<V>(map: &HashMap<String, V>) -> Vec<(&String, &V)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    entries
+}
@@ -2,12 +2,12 @@ use crate::{color::Color, error::Error, get_attrs, parse_tag};
 use std::io::Read;
 use xml::{attribute::OwnedAttribute, EventReader};
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct Image {
     /// The filepath of the image
     pub source: String,
-    pub width: i32,
-    pub height: i32,
+    pub width: u32,
+    pub height: u32,
     pub transparent_color: Option<Color>,
 }
 
@@ -37,4 +37,27 @@ impl Image {
             transparent_color: c,
         })
     }
+
+    /// The width and height of the image, in pixels.
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// The width of the image divided by its height, or `None` if either is
+    /// zero.
+    pub fn aspect_ratio(&self) -> Option<f32> {
+        if self.width == 0 || self.height == 0 {
+            return None;
+        }
+        Some(self.width as f32 / self.height as f32)
+    }
+
+    /// The number of pixels in the image, or `None` if either dimension is
+    /// zero.
+    pub fn pixel_count(&self) -> Option<u64> {
+        if self.width == 0 || self.height == 0 {
+            return None;
+        }
+        Some(self.width as u64 * self.height as u64)
+    }
 }
@@ -0,0 +1,1105 @@
+use crate::{
+    color::Color,
+    error::Error,
+    image::Image,
+    layer::{Group, ImageLayer, Layer, LayerKind},
+    map::{
+        decode_tiles, no_external_tilesets, open_relative_to, source_path, split_rows, Chunk, Map,
+        RenderOrder,
+    },
+    object::{
+        DrawOrder, HorizontalAlignment, Object, ObjectGroup, ObjectShape, Text, VerticalAlignment,
+    },
+    property::{Properties, PropertyValue},
+    tile::{Frame, Tile},
+    tileset::{Terrain, Tileset, TilesetLoader},
+    wangset::{WangColor, WangSet, WangTile},
+    writer::{color, draw_order, halign, layer_bounds, render_order, stagger_axis, valign},
+};
+use serde_json::{json, Map as JsonMap, Value};
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+impl Map {
+    /// Parses a map in the Tiled JSON (`.tmj`) format. The map can't have
+    /// external tilesets; use `parse_json_file` for those. Objects that use a
+    /// template are an `Error::Unsupported`, and unknown keys are not kept in
+    /// `extra`.
+    pub fn parse_json<R: Read>(reader: R) -> Result<Map, Error> {
+        Self::parse_json_impl(reader, None, &mut no_external_tilesets)
+    }
+
+    /// Parses the Tiled JSON map at `path`, as `parse_json` does. External
+    /// tilesets, which may be `.tsx` or JSON files, are loaded relative to it.
+    pub fn parse_json_file(path: &Path) -> Result<Map, Error> {
+        let file = File::open(path)
+            .map_err(|_| Error::Other(format!("Map file not found: {:?}", path)))?;
+        Self::parse_json_impl(file, Some(path), &mut open_relative_to(path))
+    }
+
+    fn parse_json_impl<R: Read>(
+        reader: R,
+        map_path: Option<&Path>,
+        loader: &mut TilesetLoader,
+    ) -> Result<Map, Error> {
+        let value: Value =
+            serde_json::from_reader(reader).map_err(|e| Error::JsonDecodingError(Box::new(e)))?;
+        parse_map(&value, map_path, loader)
+    }
+
+    /// Writes this map as a Tiled JSON (`.tmj`) file. Tile layer data is
+    /// written as a flat array of GIDs. External tilesets are written as
+    /// references with the `source` they were read with; all other tilesets
+    /// are embedded in the map.
+    pub fn write_json<W: Write>(&self, writer: W) -> Result<(), Error> {
+        serde_json::to_writer_pretty(writer, &map(self))
+            .map_err(|e| Error::JsonEncodingError(Box::new(e)))
+    }
+}
+
+fn map(map: &Map) -> Value {
+    let mut value = JsonMap::new();
+    value.insert("type".to_string(), json!("map"));
+    value.insert("version".to_string(), json!(map.version));
+    insert_opt(&mut value, "class", map.class.clone());
+    value.insert(
+        "orientation".to_string(),
+        json!(map.orientation.to_string()),
+    );
+    value.insert(
+        "renderorder".to_string(),
+        json!(render_order(map.render_order)),
+    );
+    value.insert("width".to_string(), json!(map.width));
+    value.insert("height".to_string(), json!(map.height));
+    value.insert("tilewidth".to_string(), json!(map.tile_width));
+    value.insert("tileheight".to_string(), json!(map.tile_height));
+    insert_opt(&mut value, "hexsidelength", map.hex_side_length);
+    insert_opt(
+        &mut value,
+        "staggeraxis",
+        map.stagger_axis.map(stagger_axis),
+    );
+    value.insert("infinite".to_string(), json!(map.infinite));
+    insert_opt(
+        &mut value,
+        "backgroundcolor",
+        map.background_color.map(color),
+    );
+    insert_opt(&mut value, "nextlayerid", map.next_layer_id);
+    insert_opt(&mut value, "nextobjectid", map.next_object_id);
+    if let Some((width, height)) = map.chunk_size {
+        value.insert(
+            "editorsettings".to_string(),
+            json!({ "chunksize": { "width": width, "height": height } }),
+        );
+    }
+    insert_properties(&mut value, &map.properties);
+    value.insert(
+        "tilesets".to_string(),
+        Value::Array(map.tilesets.iter().map(tileset).collect()),
+    );
+    value.insert(
+        "layers".to_string(),
+        layers(
+            &map.layers,
+            &map.image_layers,
+            &map.object_groups,
+            &map.groups,
+        ),
+    );
+    Value::Object(value)
+}
+
+fn insert_opt<T: Into<Value>>(value: &mut JsonMap<String, Value>, name: &str, v: Option<T>) {
+    if let Some(v) = v {
+        value.insert(name.to_string(), v.into());
+    }
+}
+
+/// Tiled writes properties as an array of objects sorted by name, and leaves
+/// the key out entirely when there are none.
+fn insert_properties(value: &mut JsonMap<String, Value>, properties: &Properties) {
+    if properties.is_empty() {
+        return;
+    }
+    let mut properties: Vec<_> = properties.iter().collect();
+    properties.sort_by(|a, b| a.0.cmp(b.0));
+    let properties = properties
+        .into_iter()
+        .map(|(name, value)| {
+            let (property_type, value) = match *value {
+                PropertyValue::BoolValue(v) => ("bool", json!(v)),
+                PropertyValue::FloatValue(v) => ("float", json!(v)),
+                PropertyValue::IntValue(v) => ("int", json!(v)),
+                PropertyValue::ColorValue(v) => ("color", json!(format!("#{:08x}", v))),
+                PropertyValue::StringValue(ref v) => ("string", json!(v)),
+                PropertyValue::FileValue(ref v) => ("file", json!(v.to_string_lossy())),
+                PropertyValue::ObjectValue(v) => ("object", json!(v)),
+            };
+            json!({ "name": name, "type": property_type, "value": value })
+        })
+        .collect();
+    value.insert("properties".to_string(), Value::Array(properties));
+}
+
+fn insert_image(value: &mut JsonMap<String, Value>, image: &Image) {
+    value.insert("image".to_string(), json!(image.source));
+    value.insert("imagewidth".to_string(), json!(image.width));
+    value.insert("imageheight".to_string(), json!(image.height));
+    insert_opt(
+        value,
+        "transparentcolor",
+        image.transparent_color.map(color),
+    );
+}
+
+fn tileset(tileset: &Tileset) -> Value {
+    if let Some(source) = tileset.written_source() {
+        return json!({
+            "firstgid": tileset.first_gid,
+            "source": source,
+        });
+    }
+    let mut value = JsonMap::new();
+    value.insert("firstgid".to_string(), json!(tileset.first_gid));
+    value.insert("name".to_string(), json!(tileset.name));
+    insert_opt(&mut value, "class", tileset.class.clone());
+    value.insert("tilewidth".to_string(), json!(tileset.tile_width));
+    value.insert("tileheight".to_string(), json!(tileset.tile_height));
+    value.insert("spacing".to_string(), json!(tileset.spacing));
+    value.insert("margin".to_string(), json!(tileset.margin));
+    insert_opt(&mut value, "tilecount", tileset.tile_count);
+    insert_opt(&mut value, "columns", tileset.columns);
+    // A JSON tileset has room for a single image.
+    if let Some(image) = tileset.images.first() {
+        insert_image(&mut value, image);
+    }
+    if !tileset.terrain_types.is_empty() {
+        let terrains = tileset
+            .terrain_types
+            .iter()
+            .map(|terrain| json!({ "name": terrain.name, "tile": terrain.tile }))
+            .collect();
+        value.insert("terrains".to_string(), Value::Array(terrains));
+    }
+    if !tileset.tiles.is_empty() {
+        value.insert(
+            "tiles".to_string(),
+            Value::Array(tileset.tiles.iter().map(tile).collect()),
+        );
+    }
+    if !tileset.wang_sets.is_empty() {
+        value.insert(
+            "wangsets".to_string(),
+            Value::Array(tileset.wang_sets.iter().map(wang_set).collect()),
+        );
+    }
+    Value::Object(value)
+}
+
+fn wang_set(wang_set: &WangSet) -> Value {
+    let mut value = JsonMap::new();
+    value.insert("name".to_string(), json!(wang_set.name));
+    insert_opt(&mut value, "class", wang_set.class.clone());
+    insert_opt(&mut value, "type", wang_set.wang_type.clone());
+    value.insert("tile".to_string(), json!(tile_id(wang_set.tile)));
+    insert_properties(&mut value, &wang_set.properties);
+    let colors = wang_set
+        .colors
+        .iter()
+        .map(|wang_color| {
+            let mut value = JsonMap::new();
+            value.insert("name".to_string(), json!(wang_color.name));
+            insert_opt(&mut value, "class", wang_color.class.clone());
+            value.insert("color".to_string(), json!(color(wang_color.color)));
+            value.insert("tile".to_string(), json!(tile_id(wang_color.tile)));
+            value.insert("probability".to_string(), json!(wang_color.probability));
+            insert_properties(&mut value, &wang_color.properties);
+            Value::Object(value)
+        })
+        .collect();
+    value.insert("colors".to_string(), Value::Array(colors));
+    let tiles = wang_set
+        .tiles
+        .iter()
+        .map(|wang_tile| json!({ "tileid": wang_tile.tile_id, "wangid": wang_tile.wang_id }))
+        .collect();
+    value.insert("wangtiles".to_string(), Value::Array(tiles));
+    Value::Object(value)
+}
+
+/// Tiled writes -1 when no tile represents a wang set or color.
+fn tile_id(tile: Option<u32>) -> i64 {
+    tile.map_or(-1, i64::from)
+}
+
+fn tile(tile: &Tile) -> Value {
+    let mut value = JsonMap::new();
+    value.insert("id".to_string(), json!(tile.id));
+    insert_opt(&mut value, "type", tile.tile_type.clone());
+    if let Some(terrain) = tile.terrain {
+        // Corners without a terrain are -1 in JSON.
+        let terrain: Vec<i64> = terrain
+            .iter()
+            .map(|corner| corner.map_or(-1, i64::from))
+            .collect();
+        value.insert("terrain".to_string(), json!(terrain));
+    }
+    if tile.probability != 1.0 {
+        value.insert("probability".to_string(), json!(tile.probability));
+    }
+    insert_properties(&mut value, &tile.properties);
+    if let Some(image) = tile.images.first() {
+        insert_image(&mut value, image);
+    }
+    if let Some(ref object_group) = tile.objectgroup {
+        value.insert("objectgroup".to_string(), self::object_group(object_group));
+    }
+    if let Some(ref animation) = tile.animation {
+        let frames = animation
+            .iter()
+            .map(|frame| json!({ "tileid": frame.tile_id, "duration": frame.duration }))
+            .collect();
+        value.insert("animation".to_string(), Value::Array(frames));
+    }
+    Value::Object(value)
+}
+
+fn layers(
+    layers: &[Layer],
+    image_layers: &[ImageLayer],
+    object_groups: &[ObjectGroup],
+    groups: &[Group],
+) -> Value {
+    LayerKind::in_order(layers, image_layers, object_groups, groups)
+        .into_iter()
+        .map(|layer| match layer {
+            LayerKind::Tile(layer) => self::layer(layer),
+            LayerKind::Image(layer) => image_layer(layer),
+            LayerKind::Objects(layer) => object_group(layer),
+            LayerKind::Group(layer) => group(layer),
+        })
+        .collect()
+}
+
+/// The keys shared by all kinds of layer.
+#[allow(clippy::too_many_arguments)]
+fn layer_common(
+    layer_type: &str,
+    id: Option<u32>,
+    name: &str,
+    class: &Option<String>,
+    opacity: f32,
+    visible: bool,
+    locked: bool,
+    (offset_x, offset_y): (f32, f32),
+    properties: &Properties,
+) -> JsonMap<String, Value> {
+    let mut value = JsonMap::new();
+    value.insert("type".to_string(), json!(layer_type));
+    insert_opt(&mut value, "id", id);
+    value.insert("name".to_string(), json!(name));
+    insert_opt(&mut value, "class", class.clone());
+    value.insert("opacity".to_string(), json!(opacity));
+    value.insert("visible".to_string(), json!(visible));
+    if locked {
+        value.insert("locked".to_string(), json!(locked));
+    }
+    value.insert("x".to_string(), json!(0));
+    value.insert("y".to_string(), json!(0));
+    if offset_x != 0.0 {
+        value.insert("offsetx".to_string(), json!(offset_x));
+    }
+    if offset_y != 0.0 {
+        value.insert("offsety".to_string(), json!(offset_y));
+    }
+    insert_properties(&mut value, properties);
+    value
+}
+
+fn layer(layer: &Layer) -> Value {
+    let mut value = layer_common(
+        "tilelayer",
+        layer.id,
+        &layer.name,
+        &layer.class,
+        layer.opacity,
+        layer.visible,
+        layer.locked,
+        (layer.offset_x, layer.offset_y),
+        &layer.properties,
+    );
+    insert_opt(&mut value, "tintcolor", layer.tint_color.map(color));
+    let (x, y, width, height) = layer_bounds(layer);
+    value.insert("width".to_string(), json!(width));
+    value.insert("height".to_string(), json!(height));
+    if layer.is_chunked() {
+        value.insert("startx".to_string(), json!(x));
+        value.insert("starty".to_string(), json!(y));
+        let chunks: Vec<Value> = layer
+            .chunks
+            .iter()
+            .map(|chunk| {
+                let data: Vec<u32> = chunk.tiles.iter().flatten().cloned().collect();
+                json!({
+                    "x": chunk.x,
+                    "y": chunk.y,
+                    "width": chunk.width,
+                    "height": chunk.height,
+                    "data": data,
+                })
+            })
+            .collect();
+        value.insert("chunks".to_string(), Value::Array(chunks));
+    } else {
+        value.insert("data".to_string(), json!(layer.tiles_flat()));
+    }
+    Value::Object(value)
+}
+
+fn image_layer(layer: &ImageLayer) -> Value {
+    let mut value = layer_common(
+        "imagelayer",
+        layer.id,
+        &layer.name,
+        &layer.class,
+        layer.opacity,
+        layer.visible,
+        layer.locked,
+        (layer.offset_x, layer.offset_y),
+        &layer.properties,
+    );
+    insert_opt(&mut value, "tintcolor", layer.tint_color.map(color));
+    // Tiled writes an empty string for an image layer without an image.
+    let source = layer.image.as_ref().map_or("", |image| &image.source);
+    value.insert("image".to_string(), json!(source));
+    if let Some(ref image) = layer.image {
+        value.insert("imagewidth".to_string(), json!(image.width));
+        value.insert("imageheight".to_string(), json!(image.height));
+        insert_opt(
+            &mut value,
+            "transparentcolor",
+            image.transparent_color.map(color),
+        );
+    }
+    Value::Object(value)
+}
+
+fn object_group(object_group: &ObjectGroup) -> Value {
+    let mut value = layer_common(
+        "objectgroup",
+        object_group.id,
+        &object_group.name,
+        &object_group.class,
+        object_group.opacity,
+        object_group.visible,
+        object_group.locked,
+        extra_offset(&object_group.extra),
+        &Properties::new(),
+    );
+    insert_opt(&mut value, "color", object_group.color.map(color));
+    value.insert(
+        "draworder".to_string(),
+        json!(draw_order(object_group.draw_order)),
+    );
+    value.insert(
+        "objects".to_string(),
+        Value::Array(object_group.objects.iter().map(object).collect()),
+    );
+    Value::Object(value)
+}
+
+/// The offset of an object group, which has no fields for it and keeps the
+/// `offsetx` and `offsety` attributes of a TMX file in `extra`.
+fn extra_offset(extra: &HashMap<String, String>) -> (f32, f32) {
+    let offset = |key| extra.get(key).and_then(|v| v.parse().ok()).unwrap_or(0.0);
+    (offset("offsetx"), offset("offsety"))
+}
+
+fn object(object: &Object) -> Value {
+    let mut value = JsonMap::new();
+    value.insert("id".to_string(), json!(object.id));
+    if object.gid != 0 {
+        value.insert("gid".to_string(), json!(object.gid));
+    }
+    value.insert("name".to_string(), json!(object.name));
+    value.insert("type".to_string(), json!(object.obj_type));
+    value.insert("x".to_string(), json!(object.x));
+    value.insert("y".to_string(), json!(object.y));
+    let (width, height) = match object.shape {
+        ObjectShape::Rect { width, height }
+        | ObjectShape::Ellipse { width, height }
+        | ObjectShape::Text { width, height, .. } => (width, height),
+        _ => (0.0, 0.0),
+    };
+    value.insert("width".to_string(), json!(width));
+    value.insert("height".to_string(), json!(height));
+    value.insert("rotation".to_string(), json!(object.rotation));
+    value.insert("visible".to_string(), json!(object.visible));
+    insert_properties(&mut value, &object.properties);
+    match object.shape {
+        ObjectShape::Rect { .. } => {}
+        ObjectShape::Ellipse { .. } => {
+            value.insert("ellipse".to_string(), json!(true));
+        }
+        ObjectShape::Point => {
+            value.insert("point".to_string(), json!(true));
+        }
+        ObjectShape::Polyline { ref points } => {
+            value.insert("polyline".to_string(), points_value(points));
+        }
+        ObjectShape::Polygon { ref points } => {
+            value.insert("polygon".to_string(), points_value(points));
+        }
+        ObjectShape::Text { ref text, .. } => {
+            value.insert(
+                "text".to_string(),
+                json!({
+                    "text": text.contents,
+                    "fontfamily": text.font_family,
+                    "pixelsize": text.pixel_size,
+                    "wrap": text.wrap,
+                    "color": color(text.color),
+                    "bold": text.bold,
+                    "italic": text.italic,
+                    "underline": text.underline,
+                    "strikeout": text.strikeout,
+                    "kerning": text.kerning,
+                    "halign": halign(text.halign),
+                    "valign": valign(text.valign),
+                }),
+            );
+        }
+    }
+    Value::Object(value)
+}
+
+fn points_value(points: &[(f32, f32)]) -> Value {
+    points
+        .iter()
+        .map(|&(x, y)| json!({ "x": x, "y": y }))
+        .collect()
+}
+
+fn group(group: &Group) -> Value {
+    let mut value = layer_common(
+        "group",
+        group.id,
+        &group.name,
+        &group.class,
+        group.opacity,
+        group.visible,
+        group.locked,
+        (group.offset_x, group.offset_y),
+        &group.properties,
+    );
+    insert_opt(&mut value, "tintcolor", group.tint_color.map(color));
+    value.insert(
+        "layers".to_string(),
+        layers(
+            &group.layers,
+            &group.image_layers,
+            &group.object_groups,
+            &group.groups,
+        ),
+    );
+    Value::Object(value)
+}
+
+/// The value of `key` in the JSON object `value`, converted with `convert`.
+/// `None` if the key is missing or null, and an error if it can't be
+/// converted.
+fn optional<'a, T>(
+    value: &'a Value,
+    key: &str,
+    convert: impl Fn(&'a Value) -> Option<T>,
+) -> Result<Option<T>, Error> {
+    match value.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) => convert(v).map(Some).ok_or_else(|| {
+            Error::MalformedAttributes(format!("\"{}\" has the wrong type: {}", key, v))
+        }),
+    }
+}
+
+fn required<'a, T>(
+    value: &'a Value,
+    key: &str,
+    convert: impl Fn(&'a Value) -> Option<T>,
+) -> Result<T, Error> {
+    optional(value, key, convert)?
+        .ok_or_else(|| Error::MalformedAttributes(format!("\"{}\" is missing", key)))
+}
+
+fn as_u32(value: &Value) -> Option<u32> {
+    value.as_u64().and_then(|v| u32::try_from(v).ok())
+}
+
+fn as_i32(value: &Value) -> Option<i32> {
+    value.as_i64().and_then(|v| i32::try_from(v).ok())
+}
+
+fn as_f32(value: &Value) -> Option<f32> {
+    value.as_f64().map(|v| v as f32)
+}
+
+fn as_string(value: &Value) -> Option<String> {
+    value.as_str().map(String::from)
+}
+
+/// Strings such as colors and enum names, parsed with `FromStr`.
+fn parsed<T: FromStr>(value: &Value) -> Option<T> {
+    value.as_str()?.parse().ok()
+}
+
+/// The ids of tiles representing a wang set or color, where -1 means none.
+fn as_tile_id(value: &Value) -> Option<Option<u32>> {
+    value.as_i64().map(|v| u32::try_from(v).ok())
+}
+
+fn as_array(value: &Value) -> Option<&Vec<Value>> {
+    value.as_array()
+}
+
+/// The elements of the array under `key`, or nothing if there is none.
+fn array<'a>(value: &'a Value, key: &str) -> Result<&'a [Value], Error> {
+    Ok(optional(value, key, as_array)?.map_or(&[], |v| v.as_slice()))
+}
+
+fn gids(value: &Value) -> Option<Vec<u32>> {
+    value.as_array()?.iter().map(as_u32).collect()
+}
+
+fn parse_map(
+    value: &Value,
+    map_path: Option<&Path>,
+    loader: &mut TilesetLoader,
+) -> Result<Map, Error> {
+    if value.get("type").and_then(Value::as_str) != Some("map") {
+        return Err(Error::MalformedAttributes(
+            "the JSON document is not a map".to_string(),
+        ));
+    }
+    // Files saved before Tiled 1.6 have the version as a number.
+    let version = required(value, "version", |v| match *v {
+        Value::String(ref v) => Some(v.clone()),
+        Value::Number(ref v) => Some(v.to_string()),
+        _ => None,
+    })?;
+    let chunk_size = match value.get("editorsettings").and_then(|v| v.get("chunksize")) {
+        Some(chunk_size) => Some((
+            required(chunk_size, "width", as_u32)?,
+            required(chunk_size, "height", as_u32)?,
+        )),
+        None => None,
+    };
+    let mut map = Map {
+        version,
+        class: optional(value, "class", as_string)?,
+        orientation: required(value, "orientation", parsed)?,
+        render_order: optional(value, "renderorder", parsed)?.unwrap_or(RenderOrder::RightDown),
+        width: required(value, "width", as_u32)?,
+        height: required(value, "height", as_u32)?,
+        tile_width: required(value, "tilewidth", as_u32)?,
+        tile_height: required(value, "tileheight", as_u32)?,
+        stagger_axis: optional(value, "staggeraxis", parsed)?,
+        hex_side_length: optional(value, "hexsidelength", as_u32)?,
+        infinite: optional(value, "infinite", Value::as_bool)?.unwrap_or(false),
+        properties: parse_properties(value)?,
+        background_color: optional(value, "backgroundcolor", parsed)?,
+        next_layer_id: optional(value, "nextlayerid", as_u32)?,
+        next_object_id: optional(value, "nextobjectid", as_u32)?,
+        chunk_size,
+        ..Map::default()
+    };
+    for tileset in array(value, "tilesets")? {
+        let tileset = parse_tileset_reference(tileset, map.tilesets.len(), map_path, loader)?;
+        map.tilesets.push(tileset);
+    }
+    let mut layers = Layers::default();
+    let mut layer_index = 0;
+    parse_layers(value, map.width, &mut layer_index, &mut layers)?;
+    map.layers = layers.layers;
+    map.image_layers = layers.image_layers;
+    map.object_groups = layers.object_groups;
+    map.groups = layers.groups;
+    Ok(map)
+}
+
+/// The properties array under the `properties` key. Values are checked
+/// against their type in the same way as the `value` attribute of a TMX
+/// property.
+fn parse_properties(value: &Value) -> Result<Properties, Error> {
+    let mut properties = Properties::new();
+    for property in array(value, "properties")? {
+        let name = required(property, "name", as_string)?;
+        let property_type =
+            optional(property, "type", as_string)?.unwrap_or_else(|| "string".to_string());
+        let value = match property.get("value") {
+            Some(Value::String(v)) => v.clone(),
+            Some(v) => v.to_string(),
+            None => {
+                return Err(Error::MalformedAttributes(
+                    "property must have a name and a value".to_string(),
+                ))
+            }
+        };
+        properties.insert(name, PropertyValue::new(property_type, value)?);
+    }
+    Ok(properties)
+}
+
+/// The image of a tileset or tile, which JSON stores in the keys of its
+/// owner.
+fn parse_image(value: &Value) -> Result<Option<Image>, Error> {
+    let source = match optional(value, "image", as_string)? {
+        Some(source) => source,
+        None => return Ok(None),
+    };
+    Ok(Some(Image {
+        source,
+        width: required(value, "imagewidth", as_u32)?,
+        height: required(value, "imageheight", as_u32)?,
+        transparent_color: optional(value, "transparentcolor", parsed)?,
+    }))
+}
+
+/// Reads a tileset of the map, loading it with `loader` if it is external.
+/// External tilesets may be either `.tsx` or JSON files.
+fn parse_tileset_reference(
+    value: &Value,
+    tileset_index: usize,
+    map_path: Option<&Path>,
+    loader: &mut TilesetLoader,
+) -> Result<Tileset, Error> {
+    let first_gid = required(value, "firstgid", as_u32)?;
+    let source = match optional(value, "source", as_string)? {
+        Some(source) => source,
+        None => return parse_tileset(value, first_gid, tileset_index),
+    };
+    let file = loader(&source)?;
+    let mut tileset = if source.ends_with(".tsj") || source.ends_with(".json") {
+        let value: Value =
+            serde_json::from_reader(file).map_err(|e| Error::JsonDecodingError(Box::new(e)))?;
+        parse_tileset(&value, first_gid, tileset_index)?
+    } else {
+        Tileset::parse_source(file, first_gid, tileset_index, &source, loader, false)?
+    };
+    tileset.source = Some(source_path(map_path, &source));
+    tileset.source_attribute = Some(source);
+    Ok(tileset)
+}
+
+fn parse_tileset(value: &Value, first_gid: u32, tileset_index: usize) -> Result<Tileset, Error> {
+    let mut terrain_types = Vec::new();
+    for terrain in array(value, "terrains")? {
+        terrain_types.push(Terrain {
+            name: required(terrain, "name", as_string)?,
+            tile: required(terrain, "tile", as_i32)?,
+        });
+    }
+    Ok(Tileset {
+        first_gid,
+        tileset_index,
+        name: required(value, "name", as_string)?,
+        class: optional(value, "class", as_string)?,
+        tile_width: required(value, "tilewidth", as_u32)?,
+        tile_height: required(value, "tileheight", as_u32)?,
+        spacing: optional(value, "spacing", as_u32)?.unwrap_or(0),
+        margin: optional(value, "margin", as_u32)?.unwrap_or(0),
+        tile_count: optional(value, "tilecount", as_u32)?,
+        columns: optional(value, "columns", as_u32)?,
+        images: parse_image(value)?.into_iter().collect(),
+        tiles: array(value, "tiles")?
+            .iter()
+            .map(parse_tile)
+            .collect::<Result<_, _>>()?,
+        wang_sets: array(value, "wangsets")?
+            .iter()
+            .map(parse_wang_set)
+            .collect::<Result<_, _>>()?,
+        terrain_types,
+        source: None,
+        source_attribute: None,
+        extra: HashMap::new(),
+    })
+}
+
+fn parse_tile(value: &Value) -> Result<Tile, Error> {
+    let terrain = match optional(value, "terrain", as_array)? {
+        Some(corners) => {
+            let mut terrain = [None; 4];
+            if corners.len() != terrain.len() {
+                return Err(Error::MalformedAttributes(
+                    "tile terrain must have 4 corners".to_string(),
+                ));
+            }
+            for (corner, v) in terrain.iter_mut().zip(corners) {
+                *corner = as_tile_id(v).ok_or_else(|| {
+                    Error::MalformedAttributes("tile terrain must be numbers".to_string())
+                })?;
+            }
+            Some(terrain)
+        }
+        None => None,
+    };
+    let animation = match optional(value, "animation", as_array)? {
+        Some(frames) => Some(
+            frames
+                .iter()
+                .map(|frame| {
+                    Ok(Frame {
+                        tile_id: required(frame, "tileid", as_u32)?,
+                        duration: required(frame, "duration", as_u32)?,
+                    })
+                })
+                .collect::<Result<_, Error>>()?,
+        ),
+        None => None,
+    };
+    let objectgroup = match value.get("objectgroup") {
+        Some(group) => Some(parse_object_group(group, None)?),
+        None => None,
+    };
+    // Tiled 1.9 renamed the tile type to class.
+    let tile_type = match optional(value, "class", as_string)? {
+        Some(class) => Some(class),
+        None => optional(value, "type", as_string)?,
+    };
+    Ok(Tile {
+        id: required(value, "id", as_u32)?,
+        flip_h: false,
+        flip_v: false,
+        flip_d: false,
+        images: parse_image(value)?.into_iter().collect(),
+        properties: parse_properties(value)?,
+        objectgroup,
+        animation,
+        tile_type,
+        probability: optional(value, "probability", as_f32)?.unwrap_or(1.0),
+        terrain,
+    })
+}
+
+fn parse_wang_set(value: &Value) -> Result<WangSet, Error> {
+    let mut colors = Vec::new();
+    for color in array(value, "colors")? {
+        colors.push(WangColor {
+            name: required(color, "name", as_string)?,
+            class: optional(color, "class", as_string)?,
+            color: required(color, "color", parsed)?,
+            tile: optional(color, "tile", as_tile_id)?.flatten(),
+            probability: optional(color, "probability", as_f32)?.unwrap_or(1.0),
+            properties: parse_properties(color)?,
+        });
+    }
+    let mut tiles = Vec::new();
+    for tile in array(value, "wangtiles")? {
+        let wang_id = required(tile, "wangid", |v| {
+            let colors: Vec<u8> = v
+                .as_array()?
+                .iter()
+                .map(|v| v.as_u64().and_then(|v| u8::try_from(v).ok()))
+                .collect::<Option<_>>()?;
+            colors.try_into().ok()
+        })?;
+        tiles.push(WangTile {
+            tile_id: required(tile, "tileid", as_u32)?,
+            wang_id,
+        });
+    }
+    Ok(WangSet {
+        name: required(value, "name", as_string)?,
+        class: optional(value, "class", as_string)?,
+        wang_type: optional(value, "type", as_string)?,
+        tile: optional(value, "tile", as_tile_id)?.flatten(),
+        colors,
+        tiles,
+        properties: parse_properties(value)?,
+    })
+}
+
+/// The layers of a map or group, sorted by kind.
+#[derive(Default)]
+struct Layers {
+    layers: Vec<Layer>,
+    image_layers: Vec<ImageLayer>,
+    object_groups: Vec<ObjectGroup>,
+    groups: Vec<Group>,
+}
+
+/// Reads the `layers` array of a map or group. `layer_index` counts the
+/// layers in document order, as when parsing a TMX map.
+fn parse_layers(
+    value: &Value,
+    width: u32,
+    layer_index: &mut u32,
+    layers: &mut Layers,
+) -> Result<(), Error> {
+    for layer in array(value, "layers")? {
+        match required(layer, "type", Value::as_str)? {
+            "tilelayer" => {
+                layers.layers.push(parse_layer(layer, width, *layer_index)?);
+                *layer_index += 1;
+            }
+            "imagelayer" => {
+                layers
+                    .image_layers
+                    .push(parse_image_layer(layer, *layer_index)?);
+                *layer_index += 1;
+            }
+            "objectgroup" => {
+                layers
+                    .object_groups
+                    .push(parse_object_group(layer, Some(*layer_index))?);
+                *layer_index += 1;
+            }
+            "group" => {
+                let group = parse_group(layer, width, layer_index)?;
+                layers.groups.push(group);
+            }
+            t => {
+                return Err(Error::MalformedAttributes(format!(
+                    "unknown layer type {:?}",
+                    t
+                )))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The data of a tile layer or of one of its chunks, as an array of gids or
+/// as base64 if the `layer` says so.
+fn parse_tiles(
+    value: &Value,
+    layer: &Value,
+    width: u32,
+    height: u32,
+) -> Result<Vec<Vec<u32>>, Error> {
+    let tiles = match optional(layer, "encoding", as_string)?.as_deref() {
+        None | Some("csv") => split_rows(&required(value, "data", gids)?, width)?,
+        Some(encoding) => {
+            let compression = optional(layer, "compression", as_string)?;
+            decode_tiles(
+                &required(value, "data", as_string)?,
+                Some(encoding),
+                compression.as_deref().filter(|c| !c.is_empty()),
+                width,
+                Some(height),
+            )?
+        }
+    };
+    if tiles.len() != height as usize {
+        return Err(Error::MalformedAttributes(format!(
+            "layer data has {} rows, but the layer is {} tiles high",
+            tiles.len(),
+            height
+        )));
+    }
+    Ok(tiles)
+}
+
+fn parse_layer(value: &Value, map_width: u32, layer_index: u32) -> Result<Layer, Error> {
+    let mut tiles = Vec::new();
+    let mut chunks = Vec::new();
+    match optional(value, "chunks", as_array)? {
+        Some(values) => {
+            for chunk in values {
+                let width = required(chunk, "width", as_u32)?;
+                let height = required(chunk, "height", as_u32)?;
+                chunks.push(Chunk {
+                    x: required(chunk, "x", as_i32)?,
+                    y: required(chunk, "y", as_i32)?,
+                    width,
+                    height,
+                    tiles: parse_tiles(chunk, value, width, height)?,
+                });
+            }
+        }
+        None => {
+            let width = optional(value, "width", as_u32)?.unwrap_or(map_width);
+            tiles = parse_tiles(value, value, width, required(value, "height", as_u32)?)?;
+        }
+    }
+    Ok(Layer {
+        id: optional(value, "id", as_u32)?,
+        name: required(value, "name", as_string)?,
+        class: optional(value, "class", as_string)?,
+        opacity: optional(value, "opacity", as_f32)?.unwrap_or(1.0),
+        visible: optional(value, "visible", Value::as_bool)?.unwrap_or(true),
+        locked: optional(value, "locked", Value::as_bool)?.unwrap_or(false),
+        tint_color: optional(value, "tintcolor", parsed)?,
+        offset_x: optional(value, "offsetx", as_f32)?.unwrap_or(0.0),
+        offset_y: optional(value, "offsety", as_f32)?.unwrap_or(0.0),
+        tiles,
+        chunks,
+        properties: parse_properties(value)?,
+        layer_index,
+        extra: HashMap::new(),
+    })
+}
+
+fn parse_image_layer(value: &Value, layer_index: u32) -> Result<ImageLayer, Error> {
+    // Tiled writes an empty string for an image layer without an image, and
+    // only writes the size of the image since 1.8.
+    let image = match optional(value, "image", as_string)? {
+        Some(source) if !source.is_empty() => Some(Image {
+            source,
+            width: optional(value, "imagewidth", as_u32)?.unwrap_or(0),
+            height: optional(value, "imageheight", as_u32)?.unwrap_or(0),
+            transparent_color: optional(value, "transparentcolor", parsed)?,
+        }),
+        _ => None,
+    };
+    Ok(ImageLayer {
+        id: optional(value, "id", as_u32)?,
+        name: required(value, "name", as_string)?,
+        class: optional(value, "class", as_string)?,
+        opacity: optional(value, "opacity", as_f32)?.unwrap_or(1.0),
+        visible: optional(value, "visible", Value::as_bool)?.unwrap_or(true),
+        locked: optional(value, "locked", Value::as_bool)?.unwrap_or(false),
+        tint_color: optional(value, "tintcolor", parsed)?,
+        offset_x: optional(value, "offsetx", as_f32)?.unwrap_or(0.0),
+        offset_y: optional(value, "offsety", as_f32)?.unwrap_or(0.0),
+        image,
+        properties: parse_properties(value)?,
+        layer_index,
+        extra: HashMap::new(),
+    })
+}
+
+fn parse_object_group(value: &Value, layer_index: Option<u32>) -> Result<ObjectGroup, Error> {
+    // Kept in `extra` as a TMX file would, see `extra_offset`.
+    let mut extra = HashMap::new();
+    for &key in ["offsetx", "offsety"].iter() {
+        if let Some(offset) = optional(value, key, as_f32)? {
+            extra.insert(key.to_string(), offset.to_string());
+        }
+    }
+    Ok(ObjectGroup {
+        id: optional(value, "id", as_u32)?,
+        name: optional(value, "name", as_string)?.unwrap_or_default(),
+        class: optional(value, "class", as_string)?,
+        opacity: optional(value, "opacity", as_f32)?.unwrap_or(1.0),
+        visible: optional(value, "visible", Value::as_bool)?.unwrap_or(true),
+        locked: optional(value, "locked", Value::as_bool)?.unwrap_or(false),
+        objects: array(value, "objects")?
+            .iter()
+            .map(parse_object)
+            .collect::<Result<_, _>>()?,
+        color: optional(value, "color", parsed)?,
+        draw_order: optional(value, "draworder", parsed)?.unwrap_or(DrawOrder::TopDown),
+        layer_index,
+        extra,
+    })
+}
+
+fn parse_object(value: &Value) -> Result<Object, Error> {
+    if value.get("template").is_some() {
+        return Err(Error::Unsupported(
+            "object templates are not supported in JSON maps".to_string(),
+        ));
+    }
+    let width = optional(value, "width", as_f32)?.unwrap_or(0.0);
+    let height = optional(value, "height", as_f32)?.unwrap_or(0.0);
+    let shape = if optional(value, "ellipse", Value::as_bool)? == Some(true) {
+        ObjectShape::Ellipse { width, height }
+    } else if optional(value, "point", Value::as_bool)? == Some(true) {
+        ObjectShape::Point
+    } else if let Some(points) = optional(value, "polyline", as_array)? {
+        ObjectShape::Polyline {
+            points: parse_points(points)?,
+        }
+    } else if let Some(points) = optional(value, "polygon", as_array)? {
+        ObjectShape::Polygon {
+            points: parse_points(points)?,
+        }
+    } else if let Some(text) = value.get("text") {
+        ObjectShape::Text {
+            width,
+            height,
+            text: parse_text(text)?,
+        }
+    } else {
+        ObjectShape::Rect { width, height }
+    };
+    // Tiled 1.9 renamed the object type to class.
+    let obj_type = match optional(value, "class", as_string)? {
+        Some(class) => class,
+        None => optional(value, "type", as_string)?.unwrap_or_default(),
+    };
+    Ok(Object {
+        id: optional(value, "id", as_u32)?.unwrap_or(0),
+        gid: optional(value, "gid", as_u32)?.unwrap_or(0),
+        name: optional(value, "name", as_string)?.unwrap_or_default(),
+        obj_type,
+        x: required(value, "x", as_f32)?,
+        y: required(value, "y", as_f32)?,
+        rotation: optional(value, "rotation", as_f32)?.unwrap_or(0.0),
+        visible: optional(value, "visible", Value::as_bool)?.unwrap_or(true),
+        shape,
+        properties: parse_properties(value)?,
+        extra: HashMap::new(),
+    })
+}
+
+fn parse_points(points: &[Value]) -> Result<Vec<(f32, f32)>, Error> {
+    points
+        .iter()
+        .map(|point| Ok((required(point, "x", as_f32)?, required(point, "y", as_f32)?)))
+        .collect()
+}
+
+fn parse_text(value: &Value) -> Result<Text, Error> {
+    Ok(Text {
+        font_family: optional(value, "fontfamily", as_string)?
+            .unwrap_or_else(|| "sans-serif".to_string()),
+        pixel_size: optional(value, "pixelsize", as_u32)?.unwrap_or(16),
+        wrap: optional(value, "wrap", Value::as_bool)?.unwrap_or(false),
+        color: optional(value, "color", parsed)?.unwrap_or(Color {
+            red: 0,
+            green: 0,
+            blue: 0,
+            alpha: 255,
+        }),
+        bold: optional(value, "bold", Value::as_bool)?.unwrap_or(false),
+        italic: optional(value, "italic", Value::as_bool)?.unwrap_or(false),
+        underline: optional(value, "underline", Value::as_bool)?.unwrap_or(false),
+        strikeout: optional(value, "strikeout", Value::as_bool)?.unwrap_or(false),
+        kerning: optional(value, "kerning", Value::as_bool)?.unwrap_or(true),
+        halign: optional(value, "halign", parsed)?.unwrap_or(HorizontalAlignment::Left),
+        valign: optional(value, "valign", parsed)?.unwrap_or(VerticalAlignment::Top),
+        contents: optional(value, "text", as_string)?.unwrap_or_default(),
+    })
+}
+
+fn parse_group(value: &Value, width: u32, layer_index: &mut u32) -> Result<Group, Error> {
+    let group_index = *layer_index;
+    *layer_index += 1;
+    let mut layers = Layers::default();
+    parse_layers(value, width, layer_index, &mut layers)?;
+    Ok(Group {
+        id: optional(value, "id", as_u32)?,
+        name: optional(value, "name", as_string)?.unwrap_or_default(),
+        class: optional(value, "class", as_string)?,
+        opacity: optional(value, "opacity", as_f32)?.unwrap_or(1.0),
+        visible: optional(value, "visible", Value::as_bool)?.unwrap_or(true),
+        locked: optional(value, "locked", Value::as_bool)?.unwrap_or(false),
+        tint_color: optional(value, "tintcolor", parsed)?,
+        offset_x: optional(value, "offsetx", as_f32)?.unwrap_or(0.0),
+        offset_y: optional(value, "offsety", as_f32)?.unwrap_or(0.0),
+        properties: parse_properties(value)?,
+        layers: layers.layers,
+        image_layers: layers.image_layers,
+        object_groups: layers.object_groups,
+        groups: layers.groups,
+        layer_index: group_index,
+        extra: HashMap::new(),
+    })
+}
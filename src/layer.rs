@@ -1,74 +1,260 @@
 use crate::{
-    error::Error, get_attrs, image::Image, map::parse_data, parse_tag, property::parse_properties,
+    color::Color,
+    error::Error,
+    get_attrs,
+    image::Image,
+    macros::{parse_bool_attr, parse_opacity_attr},
+    map::{default_loader, parse_layer_data, Chunk, LayerData, Map, ParseOptions},
+    object::{ObjectGroup, Templates},
+    parse_tag,
+    property::parse_properties_impl,
     property::Properties,
+    tile::{is_empty_gid, ALL_FLIP_FLAGS, FLIPPED_HORIZONTALLY_FLAG, FLIPPED_VERTICALLY_FLAG},
 };
 use std::{collections::HashMap, io::Read};
 use xml::{attribute::OwnedAttribute, EventReader};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Layer {
+    /// The unique id Tiled gave this layer, if the file has one.
+    pub id: Option<u32>,
     pub name: String,
+    /// The class of this layer, used by Tiled for custom types.
+    pub class: Option<String>,
     pub opacity: f32,
     pub visible: bool,
+    /// Whether the layer is locked in Tiled, which only affects editing.
+    pub locked: bool,
+    pub tint_color: Option<Color>,
+    pub offset_x: f32,
+    pub offset_y: f32,
     /// The tiles are arranged in rows. Each tile is a number which can be used
     ///  to find which tileset it belongs to and can then be rendered.
     pub tiles: Vec<Vec<u32>>,
+    /// The chunks of a layer of an infinite map. Layers stored in chunks have
+    /// no `tiles`.
+    pub chunks: Vec<Chunk>,
     pub properties: Properties,
     pub layer_index: u32,
+    /// The attributes of the layer that this crate doesn't parse; see
+    /// `Map::extra`.
+    pub extra: HashMap<String, String>,
 }
 
 impl Layer {
+    /// Parses a `<layer>` element of a map that is `width` tiles wide. The
+    /// tiles are checked against the height of the layer if it has one;
+    /// `Map::parse` and friends also check them against the height of the map.
     pub fn new<R: Read>(
         parser: &mut EventReader<R>,
         attrs: Vec<OwnedAttribute>,
         width: u32,
         layer_index: u32,
     ) -> Result<Layer, Error> {
-        let ((o, v), n) = get_attrs!(
+        Layer::parse(parser, attrs, width, layer_index, false)
+    }
+
+    pub(crate) fn parse<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+        width: u32,
+        layer_index: u32,
+        strict_properties: bool,
+    ) -> Result<Layer, Error> {
+        let mut extra = HashMap::new();
+        // The width of a layer is always that of the map, which the tiles are
+        // checked against. It is only read so that it doesn't end up in
+        // `extra`.
+        let ((id, c, o, v, l, tc, ox, oy, _, h), n) = get_attrs!(
             attrs,
             optionals: [
-                ("opacity", opacity, |v:String| v.parse().ok()),
-                ("visible", visible, |v:String| v.parse().ok().map(|x:i32| x == 1)),
+                ("id", id, |v:String| v.parse().ok()),
+                ("class", class, |v| Some(v)),
+                ("opacity", opacity, parse_opacity_attr),
+                ("visible", visible, parse_bool_attr),
+                ("locked", locked, parse_bool_attr),
+                ("tintcolor", tint_color, |v:String| v.parse().ok()),
+                ("offsetx", offset_x, |v:String| v.parse().ok()),
+                ("offsety", offset_y, |v:String| v.parse().ok()),
+                ("width", layer_width, |v:String| v.parse::<u32>().ok()),
+                ("height", height, |v:String| v.parse().ok()),
             ],
             required: [
                 ("name", name, |v| Some(v)),
             ],
-            Error::MalformedAttributes("layer must have a name".to_string())
+            Error::MalformedAttributes("layer must have a name".to_string()),
+            extra: &mut extra
         );
         let mut tiles = Vec::new();
+        let mut chunks = Vec::new();
         let mut properties = HashMap::new();
         parse_tag!(parser, "layer", {
             "data" => |attrs| {
-                tiles = parse_data(parser, attrs, width)?;
+                match parse_layer_data(parser, attrs, width, h)? {
+                    LayerData::Tiles(t) => {
+                        // Checked before the end of the layer is read, so that
+                        // recovering from the error skips just this layer.
+                        if let Some(height) = h {
+                            check_dimensions(&t, height, "layer")?;
+                        }
+                        tiles = t;
+                    }
+                    LayerData::Chunks(c) => chunks = c,
+                }
                 Ok(())
             },
             "properties" => |_| {
-                properties = parse_properties(parser)?;
+                properties = parse_properties_impl(parser, strict_properties)?;
                 Ok(())
             },
         });
 
         Ok(Layer {
+            id,
             name: n,
+            class: c,
             opacity: o.unwrap_or(1.0),
             visible: v.unwrap_or(true),
+            locked: l.unwrap_or(false),
+            tint_color: tc,
+            offset_x: ox.unwrap_or(0.0),
+            offset_y: oy.unwrap_or(0.0),
             tiles: tiles,
+            chunks,
             properties: properties,
             layer_index,
+            extra,
         })
     }
+
+    /// Whether the tiles of this layer are stored in `chunks` rather than in
+    /// `tiles`. This depends on the data of the layer, not on whether the map
+    /// is infinite.
+    pub fn is_chunked(&self) -> bool {
+        !self.chunks.is_empty()
+    }
+
+    /// The tiles of this layer in a single row-major `Vec`, so that the tile
+    /// at column `x` and row `y` is at index `y * width + x`. This is a copy;
+    /// `tiles` remains the storage of the layer.
+    pub fn tiles_flat(&self) -> Vec<u32> {
+        self.tiles.concat()
+    }
+
+    /// Iterates over the non-empty tiles of this layer as `(x, y, gid)`, where
+    /// `x` is the column and `y` the row of the tile. Empty tiles are
+    /// skipped, as decided by `is_empty_gid`; use `iter_all_tiles` to include
+    /// them.
+    pub fn iter_tiles(&self) -> impl Iterator<Item = (u32, u32, u32)> + '_ {
+        self.iter_all_tiles()
+            .filter(|&(_, _, gid)| !is_empty_gid(gid))
+    }
+
+    /// Iterates over every tile of this layer as `(x, y, gid)`, row by row,
+    /// including empty ones.
+    pub fn iter_all_tiles(&self) -> impl Iterator<Item = (u32, u32, u32)> + '_ {
+        self.tiles.iter().enumerate().flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .map(move |(x, &gid)| (x as u32, y as u32, gid))
+        })
+    }
+
+    /// The number of tiles of this layer that belong to the tileset at
+    /// `tileset_index` in `map.tilesets`, whatever their flip flags.
+    pub fn tile_count_for_tileset(&self, map: &Map, tileset_index: usize) -> usize {
+        let tileset = match map.tilesets.get(tileset_index) {
+            Some(tileset) => tileset,
+            None => return 0,
+        };
+        self.iter_tiles()
+            .filter(|&(_, _, gid)| {
+                map.get_tileset_by_gid(gid & !ALL_FLIP_FLAGS)
+                    .is_some_and(|owner| std::ptr::eq(owner, tileset))
+            })
+            .count()
+    }
+
+    /// Mirrors the whole layer horizontally: each row is reversed and every
+    /// non-empty tile has its horizontal flip flag toggled so it is drawn
+    /// mirrored too. Chunked layers have no `tiles` and are left as they are.
+    pub fn flip_horizontal(&mut self) {
+        for row in self.tiles.iter_mut() {
+            row.reverse();
+            for gid in row.iter_mut() {
+                *gid = flip_gid(*gid, FLIPPED_HORIZONTALLY_FLAG);
+            }
+        }
+    }
+
+    /// Mirrors the whole layer vertically: the order of the rows is reversed
+    /// and every non-empty tile has its vertical flip flag toggled so it is
+    /// drawn mirrored too. Chunked layers have no `tiles` and are left as they
+    /// are.
+    pub fn flip_vertical(&mut self) {
+        self.tiles.reverse();
+        for row in self.tiles.iter_mut() {
+            for gid in row.iter_mut() {
+                *gid = flip_gid(*gid, FLIPPED_VERTICALLY_FLAG);
+            }
+        }
+    }
+}
+
+/// Checks that the tiles parsed for a layer have `height` rows, the height of
+/// `what`. Every row already has as many tiles as the map is wide.
+fn check_dimensions(tiles: &[Vec<u32>], height: u32, what: &str) -> Result<(), Error> {
+    if tiles.len() != height as usize {
+        return Err(Error::MalformedAttributes(format!(
+            "layer data has {} rows, but the {} is {} tiles high",
+            tiles.len(),
+            what,
+            height
+        )));
+    }
+    Ok(())
+}
+
+/// `layer` if its tiles fill the `height` rows of the map it was parsed from.
+/// Chunked layers have no `tiles` and are not checked.
+pub(crate) fn check_map_height(layer: Layer, height: u32) -> Result<Layer, Error> {
+    if !layer.tiles.is_empty() {
+        check_dimensions(&layer.tiles, height, "map")?;
+    }
+    Ok(layer)
+}
+
+/// Toggles `flag` on a non-empty gid. Tiled applies the diagonal flip of a
+/// tile before the horizontal and vertical ones, so mirroring the layer
+/// toggles the flag of its own axis even for tiles flipped diagonally.
+fn flip_gid(gid: u32, flag: u32) -> u32 {
+    if is_empty_gid(gid) {
+        gid
+    } else {
+        gid ^ flag
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct ImageLayer {
+    /// The unique id Tiled gave this layer, if the file has one.
+    pub id: Option<u32>,
     pub name: String,
+    /// The class of this layer, used by Tiled for custom types.
+    pub class: Option<String>,
     pub opacity: f32,
     pub visible: bool,
+    /// Whether the layer is locked in Tiled, which only affects editing.
+    pub locked: bool,
+    pub tint_color: Option<Color>,
     pub offset_x: f32,
     pub offset_y: f32,
     pub image: Option<Image>,
     pub properties: Properties,
     pub layer_index: u32,
+    /// The attributes of the layer that this crate doesn't parse; see
+    /// `Map::extra`.
+    pub extra: HashMap<String, String>,
 }
 
 impl ImageLayer {
@@ -77,18 +263,33 @@ impl ImageLayer {
         attrs: Vec<OwnedAttribute>,
         layer_index: u32,
     ) -> Result<ImageLayer, Error> {
-        let ((o, v, ox, oy), n) = get_attrs!(
+        ImageLayer::parse(parser, attrs, layer_index, false)
+    }
+
+    pub(crate) fn parse<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+        layer_index: u32,
+        strict_properties: bool,
+    ) -> Result<ImageLayer, Error> {
+        let mut extra = HashMap::new();
+        let ((id, c, o, v, l, tc, ox, oy), n) = get_attrs!(
             attrs,
             optionals: [
-                ("opacity", opacity, |v:String| v.parse().ok()),
-                ("visible", visible, |v:String| v.parse().ok().map(|x:i32| x == 1)),
+                ("id", id, |v:String| v.parse().ok()),
+                ("class", class, |v| Some(v)),
+                ("opacity", opacity, parse_opacity_attr),
+                ("visible", visible, parse_bool_attr),
+                ("locked", locked, parse_bool_attr),
+                ("tintcolor", tint_color, |v:String| v.parse().ok()),
                 ("offsetx", offset_x, |v:String| v.parse().ok()),
                 ("offsety", offset_y, |v:String| v.parse().ok()),
             ],
             required: [
                 ("name", name, |v| Some(v)),
             ],
-            Error::MalformedAttributes("layer must have a name".to_string()));
+            Error::MalformedAttributes("layer must have a name".to_string()),
+            extra: &mut extra);
         let mut properties = HashMap::new();
         let mut image: Option<Image> = None;
         parse_tag!(parser, "imagelayer", {
@@ -97,19 +298,230 @@ impl ImageLayer {
                 Ok(())
             },
             "properties" => |_| {
-                properties = parse_properties(parser)?;
+                properties = parse_properties_impl(parser, strict_properties)?;
                 Ok(())
             },
         });
         Ok(ImageLayer {
+            id,
             name: n,
+            class: c,
             opacity: o.unwrap_or(1.0),
             visible: v.unwrap_or(true),
+            locked: l.unwrap_or(false),
+            tint_color: tc,
             offset_x: ox.unwrap_or(0.0),
             offset_y: oy.unwrap_or(0.0),
             image,
             properties,
             layer_index,
+            extra,
+        })
+    }
+}
+
+/// A group layer. Groups hold other layers, which are sorted by kind in the
+/// same way as the layers of a `Map`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Group {
+    /// The unique id Tiled gave this layer, if the file has one.
+    pub id: Option<u32>,
+    pub name: String,
+    /// The class of this layer, used by Tiled for custom types.
+    pub class: Option<String>,
+    pub opacity: f32,
+    pub visible: bool,
+    /// Whether the layer is locked in Tiled, which only affects editing.
+    pub locked: bool,
+    pub tint_color: Option<Color>,
+    pub offset_x: f32,
+    pub offset_y: f32,
+    pub properties: Properties,
+    pub layers: Vec<Layer>,
+    pub image_layers: Vec<ImageLayer>,
+    pub object_groups: Vec<ObjectGroup>,
+    pub groups: Vec<Group>,
+    pub layer_index: u32,
+    /// The attributes of the layer that this crate doesn't parse; see
+    /// `Map::extra`.
+    pub extra: HashMap<String, String>,
+}
+
+impl Group {
+    /// Parses a group and all of its children, in a map that is `width` tiles
+    /// wide. `layer_index` is the index of the group itself; the children are
+    /// numbered after it, and on return `layer_index` is the index of whatever
+    /// layer comes after the group. Errors in the children are handled as
+    /// described by `options`.
+    ///
+    /// As with `ObjectGroup::new`, objects that use a template can't be parsed
+    /// this way.
+    pub fn new<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+        width: u32,
+        layer_index: &mut u32,
+        options: &mut ParseOptions,
+    ) -> Result<Group, Error> {
+        let mut loader = default_loader(None);
+        let mut templates = Templates {
+            map_path: None,
+            loader: &mut *loader,
+            tilesets: None,
+        };
+        Group::parse(
+            parser,
+            attrs,
+            width,
+            None,
+            layer_index,
+            &mut templates,
+            options,
+        )
+    }
+
+    /// As `new`, also checking the tile layers against `height`, the height
+    /// of the map, if given.
+    pub(crate) fn parse<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+        width: u32,
+        height: Option<u32>,
+        layer_index: &mut u32,
+        templates: &mut Templates,
+        options: &mut ParseOptions,
+    ) -> Result<Group, Error> {
+        let mut extra = HashMap::new();
+        let ((id, n, c, o, v, l, tc, ox, oy), ()) = get_attrs!(
+            attrs,
+            optionals: [
+                ("id", id, |v:String| v.parse().ok()),
+                ("name", name, |v| Some(v)),
+                ("class", class, |v| Some(v)),
+                ("opacity", opacity, parse_opacity_attr),
+                ("visible", visible, parse_bool_attr),
+                ("locked", locked, parse_bool_attr),
+                ("tintcolor", tint_color, |v:String| v.parse().ok()),
+                ("offsetx", offset_x, |v:String| v.parse().ok()),
+                ("offsety", offset_y, |v:String| v.parse().ok()),
+            ],
+            required: [],
+            Error::MalformedAttributes("group must have attributes with correct types".to_string()),
+            extra: &mut extra
+        );
+        let group_index = *layer_index;
+        *layer_index += 1;
+        let strict_properties = options.strict_properties;
+        let mut properties = HashMap::new();
+        let mut layers = Vec::new();
+        let mut image_layers = Vec::new();
+        let mut object_groups = Vec::new();
+        let mut groups = Vec::new();
+        parse_tag!(parser, "group", {
+            "properties" => |_| {
+                properties = parse_properties_impl(parser, strict_properties)?;
+                Ok(())
+            },
+            "layer" => |attrs| {
+                let layer = Layer::parse(parser, attrs, width, *layer_index, strict_properties);
+                let layer = match (options.recover(parser, "layer", layer)?, height) {
+                    (Some(layer), Some(height)) => {
+                        options.recover_parsed(parser, check_map_height(layer, height))?
+                    }
+                    (layer, _) => layer,
+                };
+                if let Some(layer) = layer {
+                    layers.push(layer);
+                    *layer_index += 1;
+                }
+                Ok(())
+            },
+            "imagelayer" => |attrs| {
+                let layer = ImageLayer::parse(parser, attrs, *layer_index, strict_properties);
+                if let Some(layer) = options.recover(parser, "imagelayer", layer)? {
+                    image_layers.push(layer);
+                    *layer_index += 1;
+                }
+                Ok(())
+            },
+            "objectgroup" => |attrs| {
+                let group = ObjectGroup::parse(
+                    parser,
+                    attrs,
+                    Some(*layer_index),
+                    templates,
+                    strict_properties,
+                );
+                if let Some(group) = options.recover(parser, "objectgroup", group)? {
+                    object_groups.push(group);
+                    *layer_index += 1;
+                }
+                Ok(())
+            },
+            "group" => |attrs| {
+                let group =
+                    Group::parse(parser, attrs, width, height, layer_index, templates, options);
+                if let Some(group) = options.recover(parser, "group", group)? {
+                    groups.push(group);
+                }
+                Ok(())
+            },
+        });
+        Ok(Group {
+            id,
+            name: n.unwrap_or_default(),
+            class: c,
+            opacity: o.unwrap_or(1.0),
+            visible: v.unwrap_or(true),
+            locked: l.unwrap_or(false),
+            tint_color: tc,
+            offset_x: ox.unwrap_or(0.0),
+            offset_y: oy.unwrap_or(0.0),
+            properties,
+            layers,
+            image_layers,
+            object_groups,
+            groups,
+            layer_index: group_index,
+            extra,
         })
     }
 }
+
+/// A reference to a layer of any kind, as yielded by `Map::layers_in_order`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LayerKind<'a> {
+    Tile(&'a Layer),
+    Image(&'a ImageLayer),
+    Objects(&'a ObjectGroup),
+    Group(&'a Group),
+}
+
+impl<'a> LayerKind<'a> {
+    /// The position of the layer among all the layers of the map, as in
+    /// `Layer::layer_index`.
+    pub fn layer_index(&self) -> u32 {
+        match *self {
+            LayerKind::Tile(layer) => layer.layer_index,
+            LayerKind::Image(layer) => layer.layer_index,
+            LayerKind::Objects(layer) => layer.layer_index.unwrap_or(0),
+            LayerKind::Group(layer) => layer.layer_index,
+        }
+    }
+
+    /// All of the given layers, sorted by their `layer_index`.
+    pub(crate) fn in_order(
+        layers: &'a [Layer],
+        image_layers: &'a [ImageLayer],
+        object_groups: &'a [ObjectGroup],
+        groups: &'a [Group],
+    ) -> Vec<LayerKind<'a>> {
+        let mut all: Vec<LayerKind<'a>> = Vec::new();
+        all.extend(layers.iter().map(LayerKind::Tile));
+        all.extend(image_layers.iter().map(LayerKind::Image));
+        all.extend(object_groups.iter().map(LayerKind::Objects));
+        all.extend(groups.iter().map(LayerKind::Group));
+        all.sort_by_key(|layer| layer.layer_index());
+        all
+    }
+}
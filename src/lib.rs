@@ -1,30 +1,57 @@
 extern crate base64;
 extern crate libflate;
 extern crate xml;
+#[cfg(feature = "json")]
+extern crate serde_json;
+#[cfg(feature = "rand")]
+extern crate rand;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(feature = "tokio")]
+mod async_io;
+mod builder;
 mod color;
 mod error;
+mod fingerprint;
 mod image;
+#[cfg(feature = "json")]
+mod json;
 mod layer;
 #[macro_use]
 pub mod macros;
 mod map;
 pub mod object;
 mod property;
+mod reader;
 mod tile;
 mod tileset;
+mod wangset;
+mod writer;
 #[cfg(feature = "amethyst")]
 mod amethyst;
+#[cfg(feature = "bevy")]
+mod bevy;
 
 pub use self::{
+    builder::MapBuilder,
     color::Color,
     error::Error,
     image::Image,
-    layer::{ImageLayer, Layer},
-    map::{parse_data, Map},
-    property::{Properties, PropertyValue},
-    tile::{Frame, Tile},
-    tileset::Tileset,
+    layer::{Group, ImageLayer, Layer, LayerKind},
+    map::{
+        max_supported_map_version, parse_data, Chunk, LayerSummary, Map, Orientation,
+        ParseOptions, RenderOrder, StaggerAxis, SUPPORTED_TILED_VERSION,
+    },
+    property::{
+        parse_properties, parse_properties_strict, Properties, PropertiesExt, PropertyValue,
+    },
+    reader::{MapReader, ParsedLayer},
+    tile::{is_empty_gid, Frame, Tile},
+    tileset::{Terrain, Tileset},
+    wangset::{WangColor, WangSet, WangTile},
 };
 
 #[cfg(feature = "amethyst")]
-pub use amethyst::TmxFormat;
\ No newline at end of file
+pub use amethyst::TmxFormat;
+#[cfg(feature = "bevy")]
+pub use bevy::{TiledMap, TiledPlugin, TmxLoader};
\ No newline at end of file
@@ -2,12 +2,17 @@
 // will check that the required ones are there. This could have been done with
 // attrs.find but that would be inefficient.
 //
+// Attributes that aren't asked for are dropped, unless an `extra` map is
+// given after the error, in which case they are collected into it with
+// `collect_extra_attr`.
+//
 // This is probably a really terrible way to do this. It does cut down on lines
 // though which is nice.
 #[macro_export]
 macro_rules! get_attrs {
     ($attrs:expr, optionals: [$(($oName:pat, $oVar:ident, $oMethod:expr)),* $(,)*],
-     required: [$(($name:pat, $var:ident, $method:expr)),* $(,)*], $err:expr) => {
+     required: [$(($name:pat, $var:ident, $method:expr)),* $(,)*], $err:expr
+     $(, extra: $extra:expr)?) => {
         {
             $(let mut $oVar = None;)*
             $(let mut $var = None;)*
@@ -15,7 +20,9 @@ macro_rules! get_attrs {
                 match attr.name.local_name.as_ref() {
                     $($oName => $oVar = $oMethod(attr.value.clone()),)*
                     $($name => $var = $method(attr.value.clone()),)*
-                    _ => {}
+                    _ => {
+                        $($crate::macros::collect_extra_attr($extra, attr);)?
+                    }
                 }
             }
             if !(true $(&& $var.is_some())*) {
@@ -26,6 +33,69 @@ macro_rules! get_attrs {
     }
 }
 
+// Adds an attribute that isn't otherwise parsed to `extra`, keyed by its name
+// as written, including any prefix. The namespace of a prefixed attribute is
+// kept as well, under `xmlns:prefix`, so that writing the attributes back out
+// on the same element gives a valid document. It is public only because
+// `get_attrs!` is exported and calls it from other crates.
+#[doc(hidden)]
+pub fn collect_extra_attr(
+    extra: &mut std::collections::HashMap<String, String>,
+    attr: &xml::attribute::OwnedAttribute,
+) {
+    match attr.name.prefix {
+        Some(ref prefix) => {
+            if let Some(ref namespace) = attr.name.namespace {
+                extra.insert(format!("xmlns:{}", prefix), namespace.clone());
+            }
+            extra.insert(
+                format!("{}:{}", prefix, attr.name.local_name),
+                attr.value.clone(),
+            );
+        }
+        None => {
+            extra.insert(attr.name.local_name.clone(), attr.value.clone());
+        }
+    }
+}
+
+// Parses a boolean attribute for use with `get_attrs!`. Tiled writes these as
+// `0`/`1`, but `true`/`false` are accepted as well.
+pub(crate) fn parse_bool_attr(v: String) -> Option<bool> {
+    match v.as_ref() {
+        "1" | "true" => Some(true),
+        "0" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+// Parses an opacity attribute for use with `get_attrs!`. Tiled only writes
+// values from 0 to 1, so anything outside of that is clamped into range. Values
+// that are not numbers at all, including NaN, are left to the default of 1.
+pub(crate) fn parse_opacity_attr(v: String) -> Option<f32> {
+    v.parse::<f32>()
+        .ok()
+        .filter(|opacity| !opacity.is_nan())
+        .map(|opacity| opacity.clamp(0.0, 1.0))
+}
+
+// Parses a float attribute. TMX files always use `.` as the decimal separator,
+// so values written with a `,` by a misconfigured tool get an error saying so
+// instead of being treated as missing.
+pub(crate) fn parse_float_attr(name: &str, v: &str) -> Result<f32, crate::error::Error> {
+    v.parse().map_err(|_| {
+        let message = if v.replace(',', ".").parse::<f32>().is_ok() {
+            format!(
+                "{} \"{}\" uses ',' as a decimal separator instead of '.'",
+                name, v
+            )
+        } else {
+            format!("{} \"{}\" is not a number", name, v)
+        };
+        crate::error::Error::MalformedAttributes(message)
+    })
+}
+
 // Goes through the children of the tag and will call the correct function for
 // that child. Closes the tag
 //
@@ -34,7 +104,7 @@ macro_rules! get_attrs {
 macro_rules! parse_tag {
     ($parser:expr, $close_tag:expr, {$($open_tag:expr => $open_method:expr),* $(,)*}) => {
         loop {
-            match $parser.next().map_err(Error::XmlDecodingError)? {
+            match $parser.next()? {
                 xml::reader::XmlEvent::StartElement {name, attributes, ..} => {
                     if false {}
                     $(else if name.local_name == $open_tag {
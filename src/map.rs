@@ -1,19 +1,23 @@
 use crate::{
     color::Color,
     error::{Error, ParseTileError},
-    layer::{ImageLayer, Layer},
-    object::ObjectGroup,
-    property::{parse_properties, Properties},
-    tileset::Tileset,
+    layer::{check_map_height, Group, ImageLayer, Layer, LayerKind},
+    macros::parse_bool_attr,
+    object::{Object, ObjectGroup, Templates},
+    property::{parse_properties_impl, Properties},
+    tile::{is_empty_gid, Tile, ALL_FLIP_FLAGS},
+    tileset::{Tileset, TilesetLoader},
+    writer::layer_bounds,
 };
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    fmt,
     fs::File,
-    io::{BufReader, Read},
-    path::Path,
+    io::Read,
+    path::{Component, Path, PathBuf},
     str::FromStr,
 };
-use xml::{attribute::OwnedAttribute, reader::XmlEvent, EventReader};
+use xml::{attribute::OwnedAttribute, common::Position, reader::XmlEvent, EventReader};
 
 #[cfg(feature = "amethyst")]
 use specs::storage::{VecStorage};
@@ -25,34 +29,203 @@ use amethyst_assets::{Asset, ProcessingState, Handle};
 #[derive(Debug, PartialEq, Clone)]
 pub struct Map {
     pub version: String,
+    /// The class of this map, used by Tiled for custom types.
+    pub class: Option<String>,
     pub orientation: Orientation,
+    pub render_order: RenderOrder,
     pub width: u32,
     pub height: u32,
     pub tile_width: u32,
     pub tile_height: u32,
+    /// The axis along which every other row or column is shifted, for
+    /// staggered and hexagonal maps. Tiled treats a missing axis as `Y`.
+    pub stagger_axis: Option<StaggerAxis>,
+    /// The length of the flat side of hexagonal tiles, in pixels.
+    pub hex_side_length: Option<u32>,
+    /// Whether the map is infinite. Layers of infinite maps are normally split
+    /// into chunks, but each layer is parsed according to its own data; see
+    /// `Layer::is_chunked`.
+    pub infinite: bool,
+    /// Every tileset of the map, in document order. Tilesets are collected
+    /// wherever they appear in the file, so even when a `<tileset>` comes
+    /// after the layers using it, all of them are here by the time parsing
+    /// returns.
     pub tilesets: Vec<Tileset>,
     pub layers: Vec<Layer>,
     pub image_layers: Vec<ImageLayer>,
     pub object_groups: Vec<ObjectGroup>,
+    pub groups: Vec<Group>,
     pub properties: Properties,
     pub background_color: Option<Color>,
+    pub next_layer_id: Option<u32>,
+    pub next_object_id: Option<u32>,
+    /// The chunk width and height from `<editorsettings>`, which Tiled uses
+    /// when saving infinite maps.
+    pub chunk_size: Option<(u32, u32)>,
+    /// The attributes of the `<map>` element that this crate doesn't parse,
+    /// such as the `tiledversion` or attributes added by other tools, keyed
+    /// by name. They are written back out by `write`, so that maps can be
+    /// edited without losing them. Attributes in a namespace are keyed as
+    /// `prefix:name`, with the namespace itself under `xmlns:prefix`.
+    ///
+    /// Tilesets, layers and objects keep their unparsed attributes in the
+    /// same way.
+    pub extra: HashMap<String, String>,
 }
 
 impl Default for Map {
     fn default() -> Self {
         Self {
             version: String::new(),
+            class: None,
             orientation: Orientation::Orthogonal,
+            render_order: RenderOrder::RightDown,
             width: 0,
             height: 0,
             tile_width: 0,
             tile_height: 0,
+            stagger_axis: None,
+            hex_side_length: None,
+            infinite: false,
             tilesets: vec![],
             layers: vec![],
             image_layers: vec![],
             object_groups: vec![],
+            groups: vec![],
             properties: Properties::with_capacity(0),
             background_color: None,
+            next_layer_id: None,
+            next_object_id: None,
+            chunk_size: None,
+            extra: HashMap::new(),
+        }
+    }
+}
+
+/// How many layers of each kind a map has, including the layers nested in
+/// groups. See `Map::layer_summary`.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Default)]
+pub struct LayerSummary {
+    pub tile_layers: usize,
+    pub image_layers: usize,
+    pub object_groups: usize,
+    pub groups: usize,
+}
+
+/// The newest version of the TMX format this crate knows how to read. Maps
+/// with a newer `version` are still parsed, but anything added to the format
+/// since is ignored.
+pub const SUPPORTED_TILED_VERSION: &str = "1.10";
+
+/// The newest map `version` this crate supports; see
+/// `SUPPORTED_TILED_VERSION`.
+pub fn max_supported_map_version() -> &'static str {
+    SUPPORTED_TILED_VERSION
+}
+
+/// Whether `version` is a newer version than `SUPPORTED_TILED_VERSION`,
+/// comparing each dot separated number in turn. Missing numbers count as 0,
+/// so "1.10.0" is the same version as "1.10". Versions that aren't made of
+/// numbers are never considered newer.
+fn is_unsupported_version(version: &str) -> bool {
+    let parse = |v: &str| -> Option<Vec<u32>> { v.split('.').map(|n| n.parse().ok()).collect() };
+    match (parse(version), parse(SUPPORTED_TILED_VERSION)) {
+        (Some(mut version), Some(mut supported)) => {
+            let len = version.len().max(supported.len());
+            version.resize(len, 0);
+            supported.resize(len, 0);
+            version > supported
+        }
+        _ => false,
+    }
+}
+
+/// Options for `Map::parse_with_options`.
+#[derive(Default)]
+pub struct ParseOptions<'a> {
+    /// Called once for every tileset of the map as soon as it has been fully
+    /// parsed, whether it is embedded in the map or loaded from a `.tsx` file.
+    /// This allows e.g. texture loading to start before the rest of the map
+    /// has been parsed.
+    pub on_tileset: Option<Box<dyn FnMut(&Tileset) + 'a>>,
+    /// When set, an error in a tileset or layer no longer aborts the parse.
+    /// Instead it is passed to this callback and the offending element is
+    /// left out of the map. Errors in the XML itself are still fatal. See
+    /// also `Map::parse_collecting_errors`.
+    pub on_error: Option<Box<dyn FnMut(Error) + 'a>>,
+    /// Called with a message for things that don't stop the map from being
+    /// parsed but may mean it is not read as Tiled intended, such as a map
+    /// version newer than `SUPPORTED_TILED_VERSION`.
+    pub on_warning: Option<Box<dyn FnMut(String) + 'a>>,
+    /// When set, a property name used more than once in the same
+    /// `<properties>` element is an `Error::MalformedAttributes`, as with
+    /// `parse_properties_strict`, instead of the last value winning. This
+    /// applies to the properties of everything in the map.
+    pub strict_properties: bool,
+}
+
+impl<'a> ParseOptions<'a> {
+    /// Handles the `result` of parsing an element named `tag`. Without an
+    /// `on_error` callback, or for XML errors, this is `result` as is.
+    /// Otherwise an error is handed to the callback, the rest of the element
+    /// is skipped and `None` is returned.
+    pub(crate) fn recover<R: Read, T>(
+        &mut self,
+        parser: &mut EventReader<R>,
+        tag: &str,
+        result: Result<T, Error>,
+    ) -> Result<Option<T>, Error> {
+        let e = match result {
+            Ok(value) => return Ok(Some(value)),
+            Err(e) => e,
+        };
+        self.report(parser, e)?;
+        let mut depth = 0;
+        loop {
+            match parser.next()? {
+                XmlEvent::StartElement { name, .. } if name.local_name == tag => depth += 1,
+                XmlEvent::EndElement { name } if name.local_name == tag => {
+                    if depth == 0 {
+                        return Ok(None);
+                    }
+                    depth -= 1;
+                }
+                XmlEvent::EndDocument => {
+                    return Err(Error::PrematureEnd(
+                        "Document ended before we expected.".to_string(),
+                    ))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// As `recover`, for a `result` that is only known once the element has
+    /// been parsed to its end, so that nothing is left to skip.
+    pub(crate) fn recover_parsed<R: Read, T>(
+        &mut self,
+        parser: &EventReader<R>,
+        result: Result<T, Error>,
+    ) -> Result<Option<T>, Error> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(e) => self.report(parser, e).map(|()| None),
+        }
+    }
+
+    /// Hands `e` to the `on_error` callback, or returns it if there is none or
+    /// it can't be recovered from.
+    fn report<R: Read>(&mut self, parser: &EventReader<R>, e: Error) -> Result<(), Error> {
+        let on_error = match self.on_error {
+            Some(ref mut on_error) => on_error,
+            None => return Err(e),
+        };
+        match e {
+            Error::XmlDecodingError(_) | Error::PrematureEnd(_) => Err(e),
+            e => {
+                on_error(e.at_position(parser.position()));
+                Ok(())
+            }
         }
     }
 }
@@ -60,77 +233,178 @@ impl Default for Map {
 unsafe impl Send for Map {}
 unsafe impl Sync for Map {}
 
+/// Iterates over the top-level layers of the map in draw order, as
+/// `Map::layers_in_order`.
+impl<'a> IntoIterator for &'a Map {
+    type Item = LayerKind<'a>;
+    type IntoIter = std::vec::IntoIter<LayerKind<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        LayerKind::in_order(
+            &self.layers,
+            &self.image_layers,
+            &self.object_groups,
+            &self.groups,
+        )
+        .into_iter()
+    }
+}
+
 impl Map {
     fn new<R: Read>(
         parser: &mut EventReader<R>,
         attrs: Vec<OwnedAttribute>,
         map_path: Option<&Path>,
+        loader: &mut TilesetLoader,
+        mut cache: Option<&mut HashMap<PathBuf, Tileset>>,
+        options: &mut ParseOptions,
     ) -> Result<Map, Error> {
-        let (c, (v, o, w, h, tw, th)) = get_attrs!(
-            attrs,
-            optionals: [
-                ("backgroundcolor", color, |v:String| v.parse().ok()),
-            ],
-            required: [
-                ("version", version, |v| Some(v)),
-                ("orientation", orientation, |v:String| v.parse().ok()),
-                ("width", width, |v:String| v.parse().ok()),
-                ("height", height, |v:String| v.parse().ok()),
-                ("tilewidth", tile_width, |v:String| v.parse().ok()),
-                ("tileheight", tile_height, |v:String| v.parse().ok()),
-            ],
-            Error::MalformedAttributes("map must have a version, width and height with correct types".to_string())
-        );
-
-        let mut tilesets = Vec::new();
-        let mut layers = Vec::new();
-        let mut image_layers = Vec::new();
-        let mut properties = HashMap::new();
-        let mut object_groups = Vec::new();
+        let mut map = Map::from_attrs(attrs)?;
+        if is_unsupported_version(&map.version) {
+            if let Some(ref mut on_warning) = options.on_warning {
+                on_warning(format!(
+                    "map version {} is newer than the supported version {}",
+                    map.version, SUPPORTED_TILED_VERSION
+                ));
+            }
+        }
+        let (w, h) = (map.width, map.height);
+        let strict_properties = options.strict_properties;
         let mut layer_index = 0;
         parse_tag!(parser, "map", {
-            "tileset" => | attrs| {
-                tilesets.push(Tileset::new(parser, attrs, map_path)?);
+            "tileset" => |attrs| {
+                let tileset = Tileset::parse_in_map(
+                    parser,
+                    attrs,
+                    map.tilesets.len(),
+                    map_path,
+                    loader,
+                    cache.as_deref_mut(),
+                    strict_properties,
+                );
+                if let Some(tileset) = options.recover(parser, "tileset", tileset)? {
+                    if let Some(ref mut on_tileset) = options.on_tileset {
+                        on_tileset(&tileset);
+                    }
+                    map.tilesets.push(tileset);
+                }
                 Ok(())
             },
             "layer" => |attrs| {
-                layers.push(Layer::new(parser, attrs, w, layer_index)?);
-                layer_index += 1;
+                let layer = Layer::parse(parser, attrs, w, layer_index, strict_properties);
+                let layer = match options.recover(parser, "layer", layer)? {
+                    Some(layer) => options.recover_parsed(parser, check_map_height(layer, h))?,
+                    None => None,
+                };
+                if let Some(layer) = layer {
+                    map.layers.push(layer);
+                    layer_index += 1;
+                }
                 Ok(())
             },
             "imagelayer" => |attrs| {
-                image_layers.push(ImageLayer::new(parser, attrs, layer_index)?);
-                layer_index += 1;
+                let layer = ImageLayer::parse(parser, attrs, layer_index, strict_properties);
+                if let Some(layer) = options.recover(parser, "imagelayer", layer)? {
+                    map.image_layers.push(layer);
+                    layer_index += 1;
+                }
                 Ok(())
             },
             "properties" => |_| {
-                properties = parse_properties(parser)?;
+                map.properties = parse_properties_impl(parser, strict_properties)?;
                 Ok(())
             },
             "objectgroup" => |attrs| {
-                object_groups.push(ObjectGroup::new(parser, attrs, Some(layer_index))?);
-                layer_index += 1;
+                let mut templates = Templates {
+                    map_path,
+                    loader: &mut *loader,
+                    tilesets: Some(&map.tilesets),
+                };
+                let group = ObjectGroup::parse(
+                    parser,
+                    attrs,
+                    Some(layer_index),
+                    &mut templates,
+                    strict_properties,
+                );
+                if let Some(group) = options.recover(parser, "objectgroup", group)? {
+                    map.object_groups.push(group);
+                    layer_index += 1;
+                }
+                Ok(())
+            },
+            "group" => |attrs| {
+                let mut templates = Templates {
+                    map_path,
+                    loader: &mut *loader,
+                    tilesets: Some(&map.tilesets),
+                };
+                let group = Group::parse(parser, attrs, w, Some(h), &mut layer_index, &mut templates, options);
+                if let Some(group) = options.recover(parser, "group", group)? {
+                    map.groups.push(group);
+                }
+                Ok(())
+            },
+            "editorsettings" => |_| {
+                map.chunk_size = parse_editor_settings(parser)?;
                 Ok(())
             },
         });
+        Ok(map)
+    }
+
+    /// A map with the attributes of a `<map>` element and nothing in it yet.
+    pub(crate) fn from_attrs(attrs: Vec<OwnedAttribute>) -> Result<Map, Error> {
+        let mut extra = HashMap::new();
+        let ((c, ro, sa, hs, inf, nl, no, class), (v, o, w, h, tw, th)) = get_attrs!(
+            attrs,
+            optionals: [
+                ("backgroundcolor", color, |v:String| v.parse().ok()),
+                ("renderorder", render_order, |v:String| v.parse().ok()),
+                ("staggeraxis", stagger_axis, |v:String| v.parse().ok()),
+                ("hexsidelength", hex_side_length, |v:String| v.parse().ok()),
+                ("infinite", infinite, parse_bool_attr),
+                ("nextlayerid", next_layer_id, |v:String| v.parse().ok()),
+                ("nextobjectid", next_object_id, |v:String| v.parse().ok()),
+                ("class", class, |v| Some(v)),
+            ],
+            required: [
+                ("version", version, |v| Some(v)),
+                ("orientation", orientation, |v:String| v.parse().ok()),
+                ("width", width, |v:String| v.parse().ok()),
+                ("height", height, |v:String| v.parse().ok()),
+                ("tilewidth", tile_width, |v:String| v.parse().ok()),
+                ("tileheight", tile_height, |v:String| v.parse().ok()),
+            ],
+            Error::MalformedAttributes("map must have a version, width and height with correct types".to_string()),
+            extra: &mut extra
+        );
         Ok(Map {
             version: v,
+            class,
             orientation: o,
+            render_order: ro.unwrap_or(RenderOrder::RightDown),
             width: w,
             height: h,
             tile_width: tw,
             tile_height: th,
-            tilesets,
-            layers,
-            image_layers,
-            object_groups,
-            properties,
+            stagger_axis: sa,
+            hex_side_length: hs,
+            infinite: inf.unwrap_or(false),
             background_color: c,
+            next_layer_id: nl,
+            next_object_id: no,
+            extra,
+            ..Map::default()
         })
     }
 
-    /// This function will return the correct Tileset given a GID.
+    /// This function will return the correct Tileset given a GID. `None` for
+    /// gid 0, the empty tile.
     pub fn get_tileset_by_gid(&self, gid: u32) -> Option<&Tileset> {
+        if is_empty_gid(gid) {
+            return None;
+        }
         let mut maximum_gid: i32 = -1;
         let mut maximum_ts = None;
         for tileset in self.tilesets.iter() {
@@ -142,10 +416,370 @@ impl Map {
         maximum_ts
     }
 
+    /// Returns the id of the tile within its tileset given a GID. Flip flags
+    /// are ignored. `None` if no tileset contains the tile.
+    pub fn local_id(&self, gid: u32) -> Option<u32> {
+        let gid = gid & !ALL_FLIP_FLAGS;
+        self.get_tileset_by_gid(gid)
+            .filter(|tileset| tileset.contains_gid(gid))
+            .map(|tileset| gid - tileset.first_gid)
+    }
+
+    /// This function will return the `Tile` given a GID. Flip flags are
+    /// ignored. Only tiles that have extra information (properties,
+    /// animations, collision shapes...) are stored in a tileset, so this
+    /// returns `None` for any other tile.
+    pub fn get_tile_by_gid(&self, gid: u32) -> Option<&Tile> {
+        let id = self.local_id(gid)?;
+        let tileset = self.get_tileset_by_gid(gid & !ALL_FLIP_FLAGS)?;
+        tileset.tiles.iter().find(|tile| tile.id == id)
+    }
+
+    /// The first tile layer named `name`. Tiled allows several layers to
+    /// share a name; use `layers_by_name` to get all of them. Layers inside
+    /// groups are not searched.
+    pub fn layer_by_name(&self, name: &str) -> Option<&Layer> {
+        self.layers.iter().find(|layer| layer.name == name)
+    }
+
+    /// Every tile layer named `name`, in document order. Layers inside groups
+    /// are not searched.
+    pub fn layers_by_name(&self, name: &str) -> Vec<&Layer> {
+        self.layers
+            .iter()
+            .filter(|layer| layer.name == name)
+            .collect()
+    }
+
+    /// The first object group named `name`. Object groups inside groups are
+    /// not searched.
+    pub fn object_group_by_name(&self, name: &str) -> Option<&ObjectGroup> {
+        self.object_groups.iter().find(|group| group.name == name)
+    }
+
+    /// The object groups that would be drawn: those that are visible and not
+    /// fully transparent. Object groups inside groups are not included.
+    pub fn visible_object_groups(&self) -> impl Iterator<Item = &ObjectGroup> {
+        self.object_groups
+            .iter()
+            .filter(|group| group.visible && group.opacity > 0.0)
+    }
+
+    /// All of the top-level layers of the map, of every kind, in the order
+    /// they are drawn. The children of groups are only reachable through
+    /// their group.
+    pub fn layers_in_order(&self) -> impl Iterator<Item = LayerKind<'_>> {
+        self.into_iter()
+    }
+
+    /// The first image layer named `name`. Image layers inside groups are not
+    /// searched.
+    pub fn image_layer_by_name(&self, name: &str) -> Option<&ImageLayer> {
+        self.image_layers.iter().find(|layer| layer.name == name)
+    }
+
+    /// The width of the whole map in pixels, as Tiled renders it.
+    pub fn pixel_width(&self) -> u32 {
+        self.pixel_size().0
+    }
+
+    /// The height of the whole map in pixels, as Tiled renders it.
+    pub fn pixel_height(&self) -> u32 {
+        self.pixel_size().1
+    }
+
+    fn pixel_size(&self) -> (u32, u32) {
+        let (w, h) = (self.width, self.height);
+        let (tw, th) = (self.tile_width, self.tile_height);
+        // Staggered maps are laid out like hexagonal maps with no flat sides.
+        let side = match self.orientation {
+            Orientation::Orthogonal => return (w * tw, h * th),
+            Orientation::Isometric => return ((w + h) * tw / 2, (w + h) * th / 2),
+            Orientation::Staggered => 0,
+            Orientation::Hexagonal => self.hex_side_length.unwrap_or(0),
+        };
+        match self.stagger_axis.unwrap_or(StaggerAxis::Y) {
+            StaggerAxis::X => {
+                let side_offset = tw.saturating_sub(side) / 2;
+                let mut height = h * th;
+                if w > 1 {
+                    height += th / 2;
+                }
+                (w * (side_offset + side) + side_offset, height)
+            }
+            StaggerAxis::Y => {
+                let side_offset = th.saturating_sub(side) / 2;
+                let mut width = w * tw;
+                if h > 1 {
+                    width += tw / 2;
+                }
+                (width, h * (side_offset + side) + side_offset)
+            }
+        }
+    }
+
+    /// The gid of the tile drawn at pixel `x`, `y` on the tile layer at index
+    /// `layer` of `layers`, taking the offset of the layer into account.
+    /// `None` if the point is outside of the map, if there is no tile there,
+    /// or if there is no such layer.
+    ///
+    /// Only orthogonal maps are supported for now; for any other orientation
+    /// this is always `None`.
+    pub fn tile_at_pixel(&self, layer: usize, x: f32, y: f32) -> Option<u32> {
+        if self.orientation != Orientation::Orthogonal
+            || self.tile_width == 0
+            || self.tile_height == 0
+        {
+            return None;
+        }
+        let layer = self.layers.get(layer)?;
+        let column = ((x - layer.offset_x) / self.tile_width as f32).floor();
+        let row = ((y - layer.offset_y) / self.tile_height as f32).floor();
+        // Chunked layers can have tiles outside of the size of the map.
+        if !layer.is_chunked()
+            && (column < 0.0
+                || row < 0.0
+                || column >= self.width as f32
+                || row >= self.height as f32)
+        {
+            return None;
+        }
+        Some(tile_at(layer, column as i64, row as i64)).filter(|&gid| !is_empty_gid(gid))
+    }
+
+    /// Every object, including those in groups, whose bounding box intersects
+    /// the rectangle at `x`, `y` (its top-left corner) with size `w` by `h`.
+    /// Objects touching the edge of the rectangle are included.
+    pub fn objects_in_rect(&self, x: f32, y: f32, w: f32, h: f32) -> Vec<&Object> {
+        let mut objects = Vec::new();
+        let mut object_groups: Vec<&ObjectGroup> = self.object_groups.iter().collect();
+        let mut groups: Vec<&Group> = self.groups.iter().collect();
+        while let Some(group) = groups.pop() {
+            object_groups.extend(group.object_groups.iter());
+            groups.extend(group.groups.iter());
+        }
+        for object_group in object_groups {
+            for object in object_group.objects.iter() {
+                let (bx, by, bw, bh) = object.bounding_box();
+                if bx <= x + w && bx + bw >= x && by <= y + h && by + bh >= y {
+                    objects.push(object);
+                }
+            }
+        }
+        objects
+    }
+
+    /// Counts the layers of this map by kind, descending into groups.
+    pub fn layer_summary(&self) -> LayerSummary {
+        let mut summary = LayerSummary {
+            tile_layers: self.layers.len(),
+            image_layers: self.image_layers.len(),
+            object_groups: self.object_groups.len(),
+            groups: 0,
+        };
+        let mut groups: Vec<&Group> = self.groups.iter().collect();
+        while let Some(group) = groups.pop() {
+            summary.tile_layers += group.layers.len();
+            summary.image_layers += group.image_layers.len();
+            summary.object_groups += group.object_groups.len();
+            summary.groups += 1;
+            groups.extend(group.groups.iter());
+        }
+        summary
+    }
+
+    /// The number of tiles of every tile layer, including those in groups,
+    /// that belong to the tileset at `tileset_index` in `tilesets`.
+    pub fn tile_count_for_tileset(&self, tileset_index: usize) -> usize {
+        let mut layers: Vec<&Layer> = self.layers.iter().collect();
+        let mut groups: Vec<&Group> = self.groups.iter().collect();
+        while let Some(group) = groups.pop() {
+            layers.extend(group.layers.iter());
+            groups.extend(group.groups.iter());
+        }
+        layers
+            .iter()
+            .map(|layer| layer.tile_count_for_tileset(self, tileset_index))
+            .sum()
+    }
+
+    /// A new map holding the `w` by `h` tiles of this map whose top-left tile
+    /// is at column `x` and row `y`, for example to turn part of a level into
+    /// a reusable room. The region is clamped to this map, so it ends at the
+    /// last column and row of the map, or on infinite maps at the end of the
+    /// last chunk.
+    ///
+    /// Every tile layer is cropped to the region, and chunked layers become
+    /// ordinary ones. Objects are kept if their position is within the region,
+    /// going by tile size as on an orthogonal map, and are moved so that the
+    /// region starts at the origin. Only the tilesets used by the tiles and
+    /// tile objects that are kept are copied. Gids are left as they are, so
+    /// those tilesets keep their `first_gid`.
+    pub fn clone_region(&self, x: u32, y: u32, w: u32, h: u32) -> Map {
+        let (width, height) = self.region_extent();
+        let (x, y) = (x.min(width), y.min(height));
+        let (w, h) = (w.min(width - x), h.min(height - y));
+        let region = Region {
+            x,
+            y,
+            w,
+            h,
+            tile_width: self.tile_width,
+            tile_height: self.tile_height,
+        };
+        let mut map = Map {
+            version: self.version.clone(),
+            class: self.class.clone(),
+            orientation: self.orientation,
+            render_order: self.render_order,
+            width: w,
+            height: h,
+            tile_width: self.tile_width,
+            tile_height: self.tile_height,
+            stagger_axis: self.stagger_axis,
+            hex_side_length: self.hex_side_length,
+            infinite: false,
+            tilesets: Vec::new(),
+            layers: self.layers.iter().map(|l| region.layer(l)).collect(),
+            image_layers: self.image_layers.clone(),
+            object_groups: self
+                .object_groups
+                .iter()
+                .map(|g| region.object_group(g))
+                .collect(),
+            groups: self.groups.iter().map(|g| region.group(g)).collect(),
+            properties: self.properties.clone(),
+            background_color: self.background_color,
+            next_layer_id: self.next_layer_id,
+            next_object_id: self.next_object_id,
+            chunk_size: self.chunk_size,
+            extra: self.extra.clone(),
+        };
+
+        let mut gids = Vec::new();
+        let mut groups: Vec<&Group> = map.groups.iter().collect();
+        let mut layers: Vec<&Layer> = map.layers.iter().collect();
+        let mut object_groups: Vec<&ObjectGroup> = map.object_groups.iter().collect();
+        while let Some(group) = groups.pop() {
+            layers.extend(group.layers.iter());
+            object_groups.extend(group.object_groups.iter());
+            groups.extend(group.groups.iter());
+        }
+        for layer in layers {
+            gids.extend(layer.iter_tiles().map(|(_, _, gid)| gid));
+        }
+        for object_group in object_groups {
+            gids.extend(
+                object_group
+                    .objects
+                    .iter()
+                    .map(|o| o.gid)
+                    .filter(|&gid| !is_empty_gid(gid)),
+            );
+        }
+        let used: HashSet<usize> = gids
+            .into_iter()
+            .filter_map(|gid| self.get_tileset_by_gid(gid & !ALL_FLIP_FLAGS))
+            .map(|tileset| tileset.tileset_index)
+            .collect();
+        for tileset in self.tilesets.iter() {
+            if used.contains(&tileset.tileset_index) {
+                let mut tileset = tileset.clone();
+                tileset.tileset_index = map.tilesets.len();
+                map.tilesets.push(tileset);
+            }
+        }
+        map
+    }
+
+    /// The columns and rows `clone_region` can copy: the size of the map, or
+    /// on infinite maps the area from the origin to the end of the last chunk.
+    fn region_extent(&self) -> (u32, u32) {
+        if !self.infinite {
+            return (self.width, self.height);
+        }
+        let mut layers: Vec<&Layer> = self.layers.iter().collect();
+        let mut groups: Vec<&Group> = self.groups.iter().collect();
+        while let Some(group) = groups.pop() {
+            layers.extend(group.layers.iter());
+            groups.extend(group.groups.iter());
+        }
+        let (mut right, mut bottom) = (0, 0);
+        for layer in layers {
+            let (x, y, w, h) = layer_bounds(layer);
+            right = (x as i64 + w as i64).max(right);
+            bottom = (y as i64 + h as i64).max(bottom);
+        }
+        let clamp = |n: i64| n.min(u32::MAX as i64) as u32;
+        (clamp(right), clamp(bottom))
+    }
+
+    /// Mutable access to the tilesets of this map. Changing the `first_gid` of
+    /// a tileset through this does not update the gids used by the map; see
+    /// `rebase_tileset` for that.
+    pub fn tilesets_mut(&mut self) -> &mut Vec<Tileset> {
+        &mut self.tilesets
+    }
+
+    /// Moves the tileset at `index` so that its tiles start at
+    /// `new_first_gid`, rewriting every layer tile and tile object that used
+    /// one of its tiles to the new gid. Flip flags are preserved. It is up to
+    /// the caller to make sure the new range does not overlap another tileset.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn rebase_tileset(&mut self, index: usize, new_first_gid: u32) {
+        let tileset = &self.tilesets[index];
+        let old_first_gid = tileset.first_gid;
+        let tile_count = tileset.tile_count_effective();
+        let rebase = |gid: u32| {
+            let id = gid & !ALL_FLIP_FLAGS;
+            if id != 0 && id >= old_first_gid && id - old_first_gid < tile_count {
+                (id - old_first_gid + new_first_gid) | (gid & ALL_FLIP_FLAGS)
+            } else {
+                gid
+            }
+        };
+        rebase_layers(&mut self.layers, &mut self.object_groups, &rebase);
+        for group in self.groups.iter_mut() {
+            rebase_group(group, &rebase);
+        }
+        self.tilesets[index].first_gid = new_first_gid;
+    }
+
     /// Parse a buffer hopefully containing the contents of a Tiled file and try to
     /// parse it.
     pub fn parse<R: Read>(reader: R) -> Result<Map, Error> {
-        Self::parse_impl(reader, None)
+        Self::parse_with_loader(reader, no_external_tilesets)
+    }
+
+    /// Parse a string hopefully containing the contents of a Tiled file, such
+    /// as one embedded with `include_str!`. The map can't have external
+    /// tilesets; use `parse_with_loader` for those.
+    pub fn parse_str(contents: &str) -> Result<Map, Error> {
+        Self::parse(contents.as_bytes())
+    }
+
+    /// Parse a byte slice hopefully containing the contents of a Tiled file,
+    /// such as one embedded with `include_bytes!`. The map can't have external
+    /// tilesets; see `parse_bytes_with_path`.
+    pub fn parse_bytes(bytes: &[u8]) -> Result<Map, Error> {
+        Self::parse(bytes)
+    }
+
+    /// Like `parse_bytes`, with the location of the map file so that external
+    /// tilesets and templates can be loaded relative to it, as in
+    /// `parse_with_path`.
+    pub fn parse_bytes_with_path(bytes: &[u8], path: &Path) -> Result<Map, Error> {
+        Self::parse_with_path(bytes, path)
+    }
+
+    /// Parse a byte slice hopefully containing the contents of a Tiled file,
+    /// replacing any invalid UTF-8 sequences with U+FFFD first. This is lossy:
+    /// the replaced bytes are gone from the parsed map, but a single bad byte
+    /// in a property value no longer stops the whole map from loading.
+    pub fn parse_bytes_lossy(bytes: &[u8]) -> Result<Map, Error> {
+        let contents = String::from_utf8_lossy(bytes);
+        Self::parse(contents.as_bytes())
     }
 
     /// Parse a file hopefully containing a Tiled map and try to parse it.  If the
@@ -154,7 +788,7 @@ impl Map {
     pub fn parse_file(path: &Path) -> Result<Map, Error> {
         let file = File::open(path)
             .map_err(|_| Error::Other(format!("Map file not found: {:?}", path)))?;
-        Self::parse_impl(file, Some(path))
+        Self::parse_with_path(file, path)
     }
 
     /// Parse a buffer hopefully containing the contents of a Tiled file and try to
@@ -162,18 +796,106 @@ impl Map {
     /// (e.g. Amethyst) simply hand over a byte stream (and file location) for parsing,
     /// in which case this function may be required.
     pub fn parse_with_path<R: Read>(reader: R, path: &Path) -> Result<Map, Error> {
-        Self::parse_impl(reader, Some(path))
+        Self::parse_impl(
+            reader,
+            Some(path),
+            &mut open_relative_to(path),
+            None,
+            &mut ParseOptions::default(),
+        )
+    }
+
+    /// Like `parse_with_path`, but external tilesets are looked up in `cache`
+    /// (keyed by their resolved path) before being read from disk, and are
+    /// added to it once parsed. Sharing one cache between several maps avoids
+    /// re-parsing tilesets that they have in common.
+    pub fn parse_with_cache<R: Read>(
+        reader: R,
+        path: &Path,
+        cache: &mut HashMap<PathBuf, Tileset>,
+    ) -> Result<Map, Error> {
+        Self::parse_impl(
+            reader,
+            Some(path),
+            &mut open_relative_to(path),
+            Some(cache),
+            &mut ParseOptions::default(),
+        )
+    }
+
+    /// Parse a buffer hopefully containing the contents of a Tiled file, using
+    /// `loader` to open external tilesets and object templates. The loader is
+    /// given the `source` attribute of each external tileset and the
+    /// `template` attribute of each object exactly as written in the map,
+    /// which allows them to be read from archives, embedded bytes or any other
+    /// virtual file system.
+    pub fn parse_with_loader<R, F>(reader: R, mut loader: F) -> Result<Map, Error>
+    where
+        R: Read,
+        F: FnMut(&str) -> Result<Box<dyn Read>, Error>,
+    {
+        Self::parse_impl(
+            reader,
+            None,
+            &mut loader,
+            None,
+            &mut ParseOptions::default(),
+        )
+    }
+
+    /// Parse a buffer hopefully containing the contents of a Tiled file with
+    /// the given `options`. If `path` is given, external tilesets are loaded
+    /// relative to it as in `parse_with_path`.
+    pub fn parse_with_options<R: Read>(
+        reader: R,
+        path: Option<&Path>,
+        mut options: ParseOptions,
+    ) -> Result<Map, Error> {
+        match path {
+            Some(path) => Self::parse_impl(
+                reader,
+                Some(path),
+                &mut open_relative_to(path),
+                None,
+                &mut options,
+            ),
+            None => Self::parse_impl(reader, None, &mut no_external_tilesets, None, &mut options),
+        }
     }
 
-    fn parse_impl<R: Read>(reader: R, map_path: Option<&Path>) -> Result<Map, Error> {
+    /// Like `parse_with_options` with no options, except that errors in
+    /// tilesets and layers are collected and returned along with the map
+    /// instead of aborting the parse; the elements they occured in are left
+    /// out of the map. See `ParseOptions::on_error`.
+    pub fn parse_collecting_errors<R: Read>(
+        reader: R,
+        path: Option<&Path>,
+    ) -> Result<(Map, Vec<Error>), Error> {
+        let mut errors = Vec::new();
+        let options = ParseOptions {
+            on_error: Some(Box::new(|e| errors.push(e))),
+            ..ParseOptions::default()
+        };
+        let map = Self::parse_with_options(reader, path, options)?;
+        Ok((map, errors))
+    }
+
+    pub(crate) fn parse_impl<R: Read>(
+        reader: R,
+        map_path: Option<&Path>,
+        loader: &mut TilesetLoader,
+        cache: Option<&mut HashMap<PathBuf, Tileset>>,
+        options: &mut ParseOptions,
+    ) -> Result<Map, Error> {
         let mut parser = EventReader::new(reader);
         loop {
-            match parser.next().map_err(Error::XmlDecodingError)? {
+            match parser.next()? {
                 XmlEvent::StartElement {
                     name, attributes, ..
                 } => {
                     if name.local_name == "map" {
-                        return Map::new(&mut parser, attributes, map_path);
+                        return Map::new(&mut parser, attributes, map_path, loader, cache, options)
+                            .map_err(|e| e.at_position(parser.position()));
                     }
                 }
                 XmlEvent::EndDocument => {
@@ -187,6 +909,310 @@ impl Map {
     }
 }
 
+/// The part of a map copied by `Map::clone_region`, in tiles.
+struct Region {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+    tile_width: u32,
+    tile_height: u32,
+}
+
+impl Region {
+    fn layer(&self, layer: &Layer) -> Layer {
+        let tiles = (0..self.h)
+            .map(|j| {
+                (0..self.w)
+                    .map(|i| tile_at(layer, self.x as i64 + i as i64, self.y as i64 + j as i64))
+                    .collect()
+            })
+            .collect();
+        Layer {
+            id: layer.id,
+            name: layer.name.clone(),
+            class: layer.class.clone(),
+            opacity: layer.opacity,
+            visible: layer.visible,
+            locked: layer.locked,
+            tint_color: layer.tint_color,
+            offset_x: layer.offset_x,
+            offset_y: layer.offset_y,
+            tiles,
+            chunks: Vec::new(),
+            properties: layer.properties.clone(),
+            layer_index: layer.layer_index,
+            extra: layer.extra.clone(),
+        }
+    }
+
+    fn object_group(&self, object_group: &ObjectGroup) -> ObjectGroup {
+        let (left, top) = (
+            f64::from(self.x) * f64::from(self.tile_width),
+            f64::from(self.y) * f64::from(self.tile_height),
+        );
+        let (right, bottom) = (
+            left + f64::from(self.w) * f64::from(self.tile_width),
+            top + f64::from(self.h) * f64::from(self.tile_height),
+        );
+        let objects = object_group
+            .objects
+            .iter()
+            .filter(|o| {
+                let (x, y) = (f64::from(o.x), f64::from(o.y));
+                x >= left && x < right && y >= top && y < bottom
+            })
+            .map(|o| {
+                let mut object = o.clone();
+                object.x = (f64::from(o.x) - left) as f32;
+                object.y = (f64::from(o.y) - top) as f32;
+                object
+            })
+            .collect();
+        ObjectGroup {
+            id: object_group.id,
+            name: object_group.name.clone(),
+            class: object_group.class.clone(),
+            opacity: object_group.opacity,
+            visible: object_group.visible,
+            locked: object_group.locked,
+            objects,
+            color: object_group.color,
+            draw_order: object_group.draw_order,
+            layer_index: object_group.layer_index,
+            extra: object_group.extra.clone(),
+        }
+    }
+
+    fn group(&self, group: &Group) -> Group {
+        Group {
+            id: group.id,
+            name: group.name.clone(),
+            class: group.class.clone(),
+            opacity: group.opacity,
+            visible: group.visible,
+            locked: group.locked,
+            tint_color: group.tint_color,
+            offset_x: group.offset_x,
+            offset_y: group.offset_y,
+            properties: group.properties.clone(),
+            layers: group.layers.iter().map(|l| self.layer(l)).collect(),
+            image_layers: group.image_layers.clone(),
+            object_groups: group
+                .object_groups
+                .iter()
+                .map(|g| self.object_group(g))
+                .collect(),
+            groups: group.groups.iter().map(|g| self.group(g)).collect(),
+            layer_index: group.layer_index,
+            extra: group.extra.clone(),
+        }
+    }
+}
+
+/// The gid at column `x` and row `y` of `layer`, or 0 if there is no tile
+/// there.
+fn tile_at(layer: &Layer, x: i64, y: i64) -> u32 {
+    if !layer.is_chunked() {
+        return layer
+            .tiles
+            .get(y as usize)
+            .and_then(|row| row.get(x as usize))
+            .cloned()
+            .unwrap_or(0);
+    }
+    layer
+        .chunks
+        .iter()
+        .find(|c| {
+            let (cx, cy) = (c.x as i64, c.y as i64);
+            x >= cx && y >= cy && x < cx + c.width as i64 && y < cy + c.height as i64
+        })
+        .and_then(|c| {
+            let row = c.tiles.get((y - c.y as i64) as usize)?;
+            row.get((x - c.x as i64) as usize).cloned()
+        })
+        .unwrap_or(0)
+}
+
+fn rebase_layers(
+    layers: &mut [Layer],
+    object_groups: &mut [ObjectGroup],
+    rebase: &dyn Fn(u32) -> u32,
+) {
+    for layer in layers.iter_mut() {
+        for row in layer.tiles.iter_mut() {
+            for gid in row.iter_mut() {
+                *gid = rebase(*gid);
+            }
+        }
+    }
+    for object_group in object_groups.iter_mut() {
+        for object in object_group.objects.iter_mut() {
+            object.gid = rebase(object.gid);
+        }
+    }
+}
+
+fn rebase_group(group: &mut Group, rebase: &dyn Fn(u32) -> u32) {
+    rebase_layers(&mut group.layers, &mut group.object_groups, rebase);
+    for child in group.groups.iter_mut() {
+        rebase_group(child, rebase);
+    }
+}
+
+/// The loader used when the location of a map is unknown.
+pub(crate) fn no_external_tilesets(_source: &str) -> Result<Box<dyn Read>, Error> {
+    Err(Error::Other(
+        "Maps with external tilesets or object templates must know their file location.  See parse_with_path(Path)."
+            .to_string(),
+    ))
+}
+
+/// Opens external tilesets and templates from disk, relative to the map file
+/// at `map_path`.
+pub(crate) fn open_relative_to(
+    map_path: &Path,
+) -> impl FnMut(&str) -> Result<Box<dyn Read>, Error> + '_ {
+    move |source| {
+        let path = resolve_relative(map_path, source);
+        let file = File::open(&path)
+            .map_err(|_| Error::Other(format!("External file not found: {:?}", path)))?;
+        Ok(Box::new(file) as Box<dyn Read>)
+    }
+}
+
+/// The loader used for a map at `map_path`, as `parse_with_path` does, or
+/// `no_external_tilesets` if its location is unknown.
+pub(crate) fn default_loader(map_path: Option<&Path>) -> Box<TilesetLoader<'_>> {
+    match map_path {
+        Some(path) => Box::new(open_relative_to(path)),
+        None => Box::new(no_external_tilesets),
+    }
+}
+
+/// The `source` of every external tileset and the `template` of every object
+/// of the map in `data`, each listed once, in document order. This allows the
+/// files to be loaded before the map is parsed when they can only be read
+/// asynchronously.
+///
+/// With `file`, `data` is instead the file the map refers to as `file`, such
+/// as an external tileset whose collision objects use templates. Only the
+/// templates are listed then, as the loader is asked for them: relative to
+/// the map.
+#[cfg(any(feature = "tokio", feature = "bevy"))]
+pub(crate) fn external_sources(data: &[u8], file: Option<&str>) -> Result<Vec<String>, Error> {
+    // Nothing that a JSON tileset refers to is loaded through the loader.
+    if let Some(file) = file {
+        if file.ends_with(".tsj") || file.ends_with(".json") {
+            return Ok(Vec::new());
+        }
+    }
+    let mut sources = Vec::new();
+    for event in EventReader::new(data) {
+        if let XmlEvent::StartElement {
+            name, attributes, ..
+        } = event?
+        {
+            // The tilesets of a template are only used to remap its gid, so
+            // they are never loaded.
+            let attr = match (&name.local_name[..], file) {
+                ("tileset", None) => "source",
+                ("object", _) => "template",
+                _ => continue,
+            };
+            let source = attributes.into_iter().find(|a| a.name.local_name == attr);
+            if let Some(source) = source {
+                let source = match file {
+                    Some(file) => nested_source(file, &source.value),
+                    None => source.value,
+                };
+                if !sources.contains(&source) {
+                    sources.push(source);
+                }
+            }
+        }
+    }
+    Ok(sources)
+}
+
+/// Opens an external tileset or template from the contents loaded for each
+/// `source` with the help of `external_sources`.
+#[cfg(any(feature = "tokio", feature = "bevy"))]
+pub(crate) fn open_loaded(
+    files: &HashMap<String, Vec<u8>>,
+    source: &str,
+) -> Result<Box<dyn Read>, Error> {
+    match files.get(source) {
+        Some(contents) => Ok(Box::new(std::io::Cursor::new(contents.clone()))),
+        None => Err(Error::Other(format!(
+            "External file was not loaded: {}",
+            source
+        ))),
+    }
+}
+
+/// The path of `source`, a file referenced by the map at `map_path`. Without
+/// a map path, it is relative to the directory of the map.
+pub(crate) fn source_path(map_path: Option<&Path>, source: &str) -> PathBuf {
+    resolve_relative(map_path.unwrap_or_else(|| Path::new("")), source)
+}
+
+/// The path, relative to the map, of `source` written in `file`, a file that
+/// the map itself refers to, such as a template used by an external tileset.
+pub(crate) fn nested_source(file: &str, source: &str) -> String {
+    resolve_relative(Path::new(file), source)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Resolves `source`, a path written in the file at `base` and so relative to
+/// its directory. `.` and `..` components are collapsed, without looking at
+/// the file system, so that `source` may pass through any directory.
+pub(crate) fn resolve_relative(base: &Path, source: &str) -> PathBuf {
+    let joined = base.parent().unwrap_or_else(|| Path::new("")).join(source);
+    let mut path = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match path.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    path.pop();
+                }
+                // The parent of the root is the root itself.
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                _ => path.push(".."),
+            },
+            component => path.push(component),
+        }
+    }
+    path
+}
+
+/// Reads the chunk size out of `<editorsettings>`. Its other children, such
+/// as `<export>`, are skipped.
+pub(crate) fn parse_editor_settings<R: Read>(
+    parser: &mut EventReader<R>,
+) -> Result<Option<(u32, u32)>, Error> {
+    let mut chunk_size = None;
+    parse_tag!(parser, "editorsettings", {
+        "chunksize" => |attrs: Vec<OwnedAttribute>| {
+            let ((), (w, h)) = get_attrs!(
+                attrs,
+                optionals: [],
+                required: [
+                    ("width", width, |v:String| v.parse().ok()),
+                    ("height", height, |v:String| v.parse().ok()),
+                ],
+                Error::MalformedAttributes("chunksize must have a width and height with correct types".to_string())
+            );
+            chunk_size = Some((w, h));
+            Ok(())
+        },
+    });
+    Ok(chunk_size)
+}
+
 #[cfg(feature = "amethyst")]
 impl Asset for Map {
     const NAME: &'static str = "tiled::Map";
@@ -202,8 +1228,7 @@ impl From<Map> for Result<ProcessingState<Map>, amethyst_error::Error> {
     }
 }
 
-
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
 pub enum Orientation {
     Orthogonal,
     Isometric,
@@ -225,11 +1250,88 @@ impl FromStr for Orientation {
     }
 }
 
+impl fmt::Display for Orientation {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            Orientation::Orthogonal => write!(fmt, "orthogonal"),
+            Orientation::Isometric => write!(fmt, "isometric"),
+            Orientation::Staggered => write!(fmt, "staggered"),
+            Orientation::Hexagonal => write!(fmt, "hexagonal"),
+        }
+    }
+}
+
+/// The order in which tiles of orthogonal maps are drawn.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum RenderOrder {
+    RightDown,
+    RightUp,
+    LeftDown,
+    LeftUp,
+}
+
+impl FromStr for RenderOrder {
+    type Err = ParseTileError;
+
+    fn from_str(s: &str) -> Result<RenderOrder, ParseTileError> {
+        match s {
+            "right-down" => Ok(RenderOrder::RightDown),
+            "right-up" => Ok(RenderOrder::RightUp),
+            "left-down" => Ok(RenderOrder::LeftDown),
+            "left-up" => Ok(RenderOrder::LeftUp),
+            _ => Err(ParseTileError::RenderOrderError),
+        }
+    }
+}
+
+/// The axis along which staggered and hexagonal maps are shifted.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum StaggerAxis {
+    X,
+    Y,
+}
+
+impl FromStr for StaggerAxis {
+    type Err = ParseTileError;
+
+    fn from_str(s: &str) -> Result<StaggerAxis, ParseTileError> {
+        match s {
+            "x" => Ok(StaggerAxis::X),
+            "y" => Ok(StaggerAxis::Y),
+            _ => Err(ParseTileError::StaggerAxisError),
+        }
+    }
+}
+
 pub fn parse_data<R: Read>(
     parser: &mut EventReader<R>,
     attrs: Vec<OwnedAttribute>,
     width: u32,
 ) -> Result<Vec<Vec<u32>>, Error> {
+    match parse_layer_data(parser, attrs, width, None)? {
+        LayerData::Tiles(tiles) => Ok(tiles),
+        LayerData::Chunks(_) => Err(Error::Other(
+            "layer data is split into chunks; use Layer::new to parse it".to_string(),
+        )),
+    }
+}
+
+/// The tiles of a `<data>` element, which infinite maps split into chunks.
+pub(crate) enum LayerData {
+    Tiles(Vec<Vec<u32>>),
+    Chunks(Vec<Chunk>),
+}
+
+/// Parses a `<data>` element holding rows of `width` tiles, or chunks if it
+/// has any `<chunk>` children, whether or not the map says it is infinite.
+/// If the layer is known to be `height` tiles high, compressed data is
+/// decompressed into a buffer of the right size.
+pub(crate) fn parse_layer_data<R: Read>(
+    parser: &mut EventReader<R>,
+    attrs: Vec<OwnedAttribute>,
+    width: u32,
+    height: Option<u32>,
+) -> Result<LayerData, Error> {
     let ((e, c), ()) = get_attrs!(
         attrs,
         optionals: [
@@ -239,120 +1341,241 @@ pub fn parse_data<R: Read>(
         required: [],
         Error::MalformedAttributes("data must have an encoding and a compression".to_string())
     );
-
-    match (e, c) {
-        (None, None) => {
-            return Err(Error::Other(
-                "XML format is currently not supported".to_string(),
-            ))
-        }
-        (Some(e), None) => match e.as_ref() {
-            "base64" => return parse_base64(parser).map(|v| convert_to_u32(&v, width)),
-            "csv" => return decode_csv(parser),
-            e => return Err(Error::Other(format!("Unknown encoding format {}", e))),
-        },
-        (Some(e), Some(c)) => match (e.as_ref(), c.as_ref()) {
-            ("base64", "zlib") => {
-                return parse_base64(parser)
-                    .and_then(decode_zlib)
-                    .map(|v| convert_to_u32(&v, width))
-            }
-            ("base64", "gzip") => {
-                return parse_base64(parser)
-                    .and_then(decode_gzip)
-                    .map(|v| convert_to_u32(&v, width))
-            }
-            (e, c) => {
-                return Err(Error::Other(format!(
-                    "Unknown combination of {} encoding and {} compression",
-                    e, c
-                )))
-            }
-        },
-        _ => return Err(Error::Other("Missing encoding format".to_string())),
-    };
-}
-
-fn parse_base64<R: Read>(parser: &mut EventReader<R>) -> Result<Vec<u8>, Error> {
+    let (e, c) = (e.as_deref(), c.as_deref());
+    // The text may arrive in several events, e.g. when it is split by a
+    // comment or contains character references.
+    let mut text = String::new();
+    let mut chunks = Vec::new();
     loop {
-        match parser.next().map_err(Error::XmlDecodingError)? {
-            XmlEvent::Characters(s) => {
-                return base64::decode(s.trim().as_bytes()).map_err(Error::Base64DecodingError)
+        match parser.next()? {
+            XmlEvent::Characters(s) | XmlEvent::Whitespace(s) => text.push_str(&s),
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                if name.local_name == "chunk" {
+                    chunks.push(Chunk::new(parser, attributes, e, c)?);
+                }
             }
             XmlEvent::EndElement { name, .. } => {
                 if name.local_name == "data" {
-                    return Ok(Vec::new());
+                    break;
                 }
             }
             _ => {}
         }
     }
+    if !chunks.is_empty() {
+        return Ok(LayerData::Chunks(chunks));
+    }
+    decode_tiles(&text, e, c, width, height).map(LayerData::Tiles)
 }
 
-fn decode_zlib(data: Vec<u8>) -> Result<Vec<u8>, Error> {
-    use libflate::zlib::Decoder;
-    let mut zd =
-        Decoder::new(BufReader::new(&data[..])).map_err(|e| Error::DecompressingError(e))?;
-    let mut data = Vec::new();
-    match zd.read_to_end(&mut data) {
-        Ok(_v) => {}
-        Err(e) => return Err(Error::DecompressingError(e)),
+/// A rectangular part of a tile layer of an infinite map. Chunk coordinates
+/// are in tiles and may be negative.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Chunk {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// The tiles of the chunk, in rows, as in `Layer::tiles`.
+    pub tiles: Vec<Vec<u32>>,
+}
+
+impl Chunk {
+    fn new<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+        encoding: Option<&str>,
+        compression: Option<&str>,
+    ) -> Result<Chunk, Error> {
+        let ((), (x, y, width, height)) = get_attrs!(
+            attrs,
+            optionals: [],
+            required: [
+                ("x", x, |v:String| v.parse().ok()),
+                ("y", y, |v:String| v.parse().ok()),
+                ("width", width, |v:String| v.parse().ok()),
+                ("height", height, |v:String| v.parse().ok()),
+            ],
+            Error::MalformedAttributes("chunks must have a position and a size".to_string())
+        );
+        let mut text = String::new();
+        loop {
+            match parser.next()? {
+                XmlEvent::Characters(s) | XmlEvent::Whitespace(s) => text.push_str(&s),
+                XmlEvent::EndElement { name, .. } => {
+                    if name.local_name == "chunk" {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let tiles = decode_tiles(&text, encoding, compression, width, Some(height))?;
+        Ok(Chunk {
+            x,
+            y,
+            width,
+            height,
+            tiles,
+        })
+    }
+}
+
+/// Decodes the text of a `<data>` or `<chunk>` element into rows of `width`
+/// tiles.
+pub(crate) fn decode_tiles(
+    text: &str,
+    encoding: Option<&str>,
+    compression: Option<&str>,
+    width: u32,
+    height: Option<u32>,
+) -> Result<Vec<Vec<u32>>, Error> {
+    // Every tile is stored as 4 bytes once decompressed.
+    let size = height.map_or(0, |height| width as usize * height as usize * 4);
+    match (encoding, compression) {
+        (None, None) => Err(Error::Other(
+            "XML format is currently not supported".to_string(),
+        )),
+        (Some(e), None) => match e {
+            "base64" => decode_base64(text).and_then(|v| convert_to_u32(&v, width)),
+            "csv" => decode_csv(text).and_then(|rows| check_row_widths(rows, width)),
+            e => Err(Error::Other(format!("Unknown encoding format {}", e))),
+        },
+        (Some(e), Some(c)) => match (e, c) {
+            ("base64", "zlib") => decode_base64(text)
+                .and_then(|v| decode_zlib(v, size))
+                .and_then(|v| convert_to_u32(&v, width)),
+            ("base64", "gzip") => decode_base64(text)
+                .and_then(|v| decode_gzip(v, size))
+                .and_then(|v| convert_to_u32(&v, width)),
+            (e, c) => Err(Error::Other(format!(
+                "Unknown combination of {} encoding and {} compression",
+                e, c
+            ))),
+        },
+        _ => Err(Error::Other("Missing encoding format".to_string())),
     }
+}
+
+fn decode_base64(data: &str) -> Result<Vec<u8>, Error> {
+    let data: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+    if let Some(c) = data
+        .chars()
+        .find(|&c| !(c.is_ascii_alphanumeric() || c == '+' || c == '/' || c == '='))
+    {
+        return Err(Error::Other(format!(
+            "base64 layer data contains the invalid character {:?}",
+            c
+        )));
+    }
+    Ok(base64::decode(data.as_bytes())?)
+}
+
+fn decode_zlib(data: Vec<u8>, size: usize) -> Result<Vec<u8>, Error> {
+    // Some exporters write raw deflate streams without the zlib header, whose
+    // first byte is always 0x78 for the window sizes zlib uses.
+    if data.first() != Some(&0x78) {
+        return decode_deflate(data, size);
+    }
+    use libflate::zlib::Decoder;
+    let mut zd = Decoder::new(&data[..]).map_err(Error::DecompressingError)?;
+    let mut data = Vec::with_capacity(size);
+    zd.read_to_end(&mut data)
+        .map_err(Error::DecompressingError)?;
     Ok(data)
 }
 
-fn decode_gzip(data: Vec<u8>) -> Result<Vec<u8>, Error> {
-    use libflate::gzip::Decoder;
-    let mut zd =
-        Decoder::new(BufReader::new(&data[..])).map_err(|e| Error::DecompressingError(e))?;
+fn decode_deflate(data: Vec<u8>, size: usize) -> Result<Vec<u8>, Error> {
+    use libflate::deflate::Decoder;
+    let mut zd = Decoder::new(&data[..]);
+    let mut data = Vec::with_capacity(size);
+    zd.read_to_end(&mut data)
+        .map_err(Error::DecompressingError)?;
+    Ok(data)
+}
 
-    let mut data = Vec::new();
+fn decode_gzip(data: Vec<u8>, size: usize) -> Result<Vec<u8>, Error> {
+    use libflate::gzip::Decoder;
+    let mut zd = Decoder::new(&data[..]).map_err(Error::DecompressingError)?;
+    let mut data = Vec::with_capacity(size);
     zd.read_to_end(&mut data)
-        .map_err(|e| Error::DecompressingError(e))?;
+        .map_err(Error::DecompressingError)?;
     Ok(data)
 }
 
-fn decode_csv<R: Read>(parser: &mut EventReader<R>) -> Result<Vec<Vec<u32>>, Error> {
-    loop {
-        match parser.next().map_err(Error::XmlDecodingError)? {
-            XmlEvent::Characters(s) => {
-                let mut rows: Vec<Vec<u32>> = Vec::new();
-                for row in s.split('\n') {
-                    if row.trim() == "" {
-                        continue;
-                    }
-                    rows.push(
-                        row.split(',')
-                            .filter(|v| v.trim() != "")
-                            .map(|v| v.replace('\r', "").parse().unwrap())
-                            .collect(),
-                    );
-                }
-                return Ok(rows);
-            }
-            XmlEvent::EndElement { name, .. } => {
-                if name.local_name == "data" {
-                    return Ok(Vec::new());
-                }
-            }
-            _ => {}
+fn decode_csv(data: &str) -> Result<Vec<Vec<u32>>, Error> {
+    let mut rows: Vec<Vec<u32>> = Vec::new();
+    for row in data.split('\n') {
+        if row.trim() == "" {
+            continue;
         }
+        rows.push(
+            row.split(',')
+                .filter(|v| v.trim() != "")
+                .map(|v| {
+                    v.trim().parse().map_err(|_| {
+                        Error::MalformedAttributes(format!(
+                            "layer data contains the invalid gid {:?}",
+                            v.trim()
+                        ))
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+        );
     }
+    Ok(rows)
 }
 
-fn convert_to_u32(all: &Vec<u8>, width: u32) -> Vec<Vec<u32>> {
-    let mut data = Vec::new();
-    for chunk in all.chunks((width * 4) as usize) {
-        let mut row = Vec::new();
-        for i in 0..width {
-            let start: usize = i as usize * 4;
-            let n = ((chunk[start + 3] as u32) << 24)
-                + ((chunk[start + 2] as u32) << 16)
-                + ((chunk[start + 1] as u32) << 8)
-                + chunk[start] as u32;
-            row.push(n);
-        }
-        data.push(row);
+/// `rows` if each of them holds `width` tiles.
+fn check_row_widths(rows: Vec<Vec<u32>>, width: u32) -> Result<Vec<Vec<u32>>, Error> {
+    if let Some((y, row)) = rows
+        .iter()
+        .enumerate()
+        .find(|(_, row)| row.len() != width as usize)
+    {
+        return Err(Error::MalformedAttributes(format!(
+            "row {} of the layer data has {} tiles, but the layer is {} tiles wide",
+            y,
+            row.len(),
+            width
+        )));
+    }
+    Ok(rows)
+}
+
+/// `data` split into rows of `width` tiles. Fails if `data` doesn't hold a
+/// whole number of rows.
+pub(crate) fn split_rows(data: &[u32], width: u32) -> Result<Vec<Vec<u32>>, Error> {
+    let width = width as usize;
+    if data
+        .len()
+        .checked_rem(width)
+        .map_or(!data.is_empty(), |rem| rem != 0)
+    {
+        return Err(Error::MalformedAttributes(format!(
+            "layer data has {} tiles, which is not a whole number of rows of {} tiles",
+            data.len(),
+            width
+        )));
+    }
+    Ok(data.chunks(width.max(1)).map(<[u32]>::to_vec).collect())
+}
+
+fn convert_to_u32(all: &Vec<u8>, width: u32) -> Result<Vec<Vec<u32>>, Error> {
+    let row_len = width as usize * 4;
+    if row_len == 0 || all.len() % row_len != 0 {
+        return Err(Error::MalformedAttributes(format!(
+            "layer data is {} bytes long, which is not a whole number of rows of {} tiles ({} bytes each)",
+            all.len(),
+            width,
+            row_len
+        )));
     }
-    data
+    let data: Vec<u32> = all
+        .chunks(4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    split_rows(&data, width)
 }
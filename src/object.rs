@@ -1,55 +1,152 @@
 use crate::{
     color::Color,
-    error::Error,
-    property::{parse_properties, Properties},
+    error::{Error, ParseTileError},
+    macros::{parse_bool_attr, parse_float_attr, parse_opacity_attr},
+    map::{default_loader, resolve_relative, source_path, Map},
+    property::{parse_properties_impl, Properties},
+    tile::{is_empty_gid, Tile, ALL_FLIP_FLAGS},
+    tileset::{Tileset, TilesetLoader},
+};
+use std::{collections::HashMap, io::Read, path::Path, str::FromStr};
+use xml::{
+    attribute::OwnedAttribute,
+    reader::{EventReader, XmlEvent},
 };
-use std::{collections::HashMap, io::Read};
-use xml::{attribute::OwnedAttribute, reader::EventReader};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct ObjectGroup {
+    /// The unique id Tiled gave this layer, if the file has one.
+    pub id: Option<u32>,
     pub name: String,
+    /// The class of this layer, used by Tiled for custom types.
+    pub class: Option<String>,
     pub opacity: f32,
     pub visible: bool,
+    /// Whether the layer is locked in Tiled, which only affects editing.
+    pub locked: bool,
     pub objects: Vec<Object>,
     pub color: Option<Color>,
+    /// The order in which the objects are drawn.
+    pub draw_order: DrawOrder,
     /**
-     * Layer index is not preset for tile collision boxes
+     * Layer index is not preset for tile collision boxes. Object groups that
+     * are layers of a map always have one, so `None` means this is the
+     * collision group of a tile; see `Tile::collision_shapes`.
      */
     pub layer_index: Option<u32>,
+    /// The attributes of the object group that this crate doesn't parse; see
+    /// `Map::extra`.
+    pub extra: HashMap<String, String>,
+}
+
+/// What the objects of a map need to load their templates.
+pub(crate) struct Templates<'a, 'l> {
+    /// The location of the map, if known.
+    pub map_path: Option<&'a Path>,
+    /// Opens template files, like it does external tilesets.
+    pub loader: &'a mut TilesetLoader<'l>,
+    /// The tilesets of the map, which the gids of tile templates are remapped
+    /// to. `None` if they aren't known, in which case gids are kept as written
+    /// in the template.
+    pub tilesets: Option<&'a [Tileset]>,
+}
+
+impl Templates<'_, '_> {
+    /// Converts `gid`, written in the template file `template` whose tilesets
+    /// are `template_tilesets` as `(firstgid, source)`, to the gid of the same
+    /// tile in the map.
+    fn remap_gid(
+        &self,
+        template: &str,
+        template_tilesets: &[(u32, String)],
+        gid: u32,
+    ) -> Result<u32, Error> {
+        let tilesets = match self.tilesets {
+            Some(tilesets) if !is_empty_gid(gid) => tilesets,
+            _ => return Ok(gid),
+        };
+        let id = gid & !ALL_FLIP_FLAGS;
+        let template_path = source_path(self.map_path, template);
+        template_tilesets
+            .iter()
+            .filter(|(first_gid, _)| *first_gid <= id)
+            .max_by_key(|(first_gid, _)| *first_gid)
+            .and_then(|(first_gid, source)| {
+                let path = resolve_relative(&template_path, source);
+                let tileset = tilesets
+                    .iter()
+                    .find(|tileset| tileset.source.as_ref() == Some(&path))?;
+                Some((tileset.first_gid + id - first_gid) | (gid & ALL_FLIP_FLAGS))
+            })
+            .ok_or_else(|| {
+                Error::Other(format!(
+                    "the tileset of object template {} is not used by the map",
+                    template
+                ))
+            })
+    }
 }
 
 impl ObjectGroup {
+    /// Parses an `<objectgroup>` element. The location of the map is unknown
+    /// here, so objects that use a template can't be parsed; see `Object::new`.
     pub fn new<R: Read>(
         parser: &mut EventReader<R>,
         attrs: Vec<OwnedAttribute>,
         layer_index: Option<u32>,
     ) -> Result<ObjectGroup, Error> {
-        let ((o, v, c, n), ()) = get_attrs!(
+        let mut loader = default_loader(None);
+        let mut templates = Templates {
+            map_path: None,
+            loader: &mut *loader,
+            tilesets: None,
+        };
+        ObjectGroup::parse(parser, attrs, layer_index, &mut templates, false)
+    }
+
+    pub(crate) fn parse<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+        layer_index: Option<u32>,
+        templates: &mut Templates,
+        strict_properties: bool,
+    ) -> Result<ObjectGroup, Error> {
+        let mut extra = HashMap::new();
+        let ((id, class, o, v, l, c, n, d), ()) = get_attrs!(
             attrs,
             optionals: [
-                ("opacity", opacity, |v:String| v.parse().ok()),
-                ("visible", visible, |v:String| v.parse().ok().map(|x:i32| x == 1)),
+                ("id", id, |v:String| v.parse().ok()),
+                ("class", class, |v| Some(v)),
+                ("opacity", opacity, parse_opacity_attr),
+                ("visible", visible, parse_bool_attr),
+                ("locked", locked, parse_bool_attr),
                 ("color", color, |v:String| v.parse().ok()),
                 ("name", name, |v:String| v.into()),
+                ("draworder", draw_order, |v:String| v.parse().ok()),
             ],
             required: [],
-            Error::MalformedAttributes("object groups must have a name".to_string())
+            Error::MalformedAttributes("object groups must have a name".to_string()),
+            extra: &mut extra
         );
         let mut objects = Vec::new();
         parse_tag!(parser, "objectgroup", {
             "object" => |attrs| {
-                objects.push(Object::new(parser, attrs)?);
+                objects.push(Object::parse(parser, attrs, templates, false, strict_properties)?);
                 Ok(())
             },
         });
         Ok(ObjectGroup {
+            id,
             name: n.unwrap_or(String::new()),
+            class,
             opacity: o.unwrap_or(1.0),
             visible: v.unwrap_or(true),
+            locked: l.unwrap_or(false),
             objects: objects,
             color: c,
+            draw_order: d.unwrap_or(DrawOrder::TopDown),
             layer_index,
+            extra,
         })
     }
 }
@@ -60,6 +157,78 @@ pub enum ObjectShape {
     Ellipse { width: f32, height: f32 },
     Polyline { points: Vec<(f32, f32)> },
     Polygon { points: Vec<(f32, f32)> },
+    Point,
+    Text { width: f32, height: f32, text: Text },
+}
+
+impl ObjectShape {
+    fn size(&self) -> Option<(f32, f32)> {
+        match *self {
+            ObjectShape::Rect { width, height }
+            | ObjectShape::Ellipse { width, height }
+            | ObjectShape::Text { width, height, .. } => Some((width, height)),
+            _ => None,
+        }
+    }
+
+    fn with_size(self, width: f32, height: f32) -> ObjectShape {
+        match self {
+            ObjectShape::Rect { .. } => ObjectShape::Rect { width, height },
+            ObjectShape::Ellipse { .. } => ObjectShape::Ellipse { width, height },
+            ObjectShape::Text { text, .. } => ObjectShape::Text {
+                width,
+                height,
+                text,
+            },
+            shape => shape,
+        }
+    }
+
+    /// The area enclosed by a polygon, computed with the shoelace formula.
+    /// Returns `None` for any shape that is not a polygon.
+    pub fn polygon_area(&self) -> Option<f32> {
+        match *self {
+            ObjectShape::Polygon { ref points } => {
+                let mut sum = 0.0;
+                for (i, &(x1, y1)) in points.iter().enumerate() {
+                    let (x2, y2) = points[(i + 1) % points.len()];
+                    sum += x1 * y2 - x2 * y1;
+                }
+                Some((sum / 2.0).abs())
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether a polygon is convex. Polygons with fewer than three points are
+    /// not considered convex. Returns `None` for any shape that is not a
+    /// polygon.
+    pub fn is_convex(&self) -> Option<bool> {
+        match *self {
+            ObjectShape::Polygon { ref points } => {
+                if points.len() < 3 {
+                    return Some(false);
+                }
+                let mut sign = 0.0;
+                for i in 0..points.len() {
+                    let (x1, y1) = points[i];
+                    let (x2, y2) = points[(i + 1) % points.len()];
+                    let (x3, y3) = points[(i + 2) % points.len()];
+                    let cross = (x2 - x1) * (y3 - y2) - (y2 - y1) * (x3 - x2);
+                    if cross == 0.0 {
+                        continue;
+                    }
+                    if sign == 0.0 {
+                        sign = cross.signum();
+                    } else if cross.signum() != sign {
+                        return Some(false);
+                    }
+                }
+                Some(true)
+            }
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -74,39 +243,100 @@ pub struct Object {
     pub visible: bool,
     pub shape: ObjectShape,
     pub properties: Properties,
+    /// The attributes of the object that this crate doesn't parse; see
+    /// `Map::extra`. For an object using a template, these are merged with
+    /// those of the template object like its properties are.
+    pub extra: HashMap<String, String>,
 }
 
 impl Object {
+    /// Parses an `<object>` element. Templates are written relative to the
+    /// map, whose location is unknown here, so an object that uses a template
+    /// is an error. Parse the whole map with `Map::parse_with_path` to load
+    /// them.
     pub fn new<R: Read>(
         parser: &mut EventReader<R>,
         attrs: Vec<OwnedAttribute>,
     ) -> Result<Object, Error> {
-        let ((id, gid, n, t, w, h, v, r), (x, y)) = get_attrs!(
+        let mut loader = default_loader(None);
+        let mut templates = Templates {
+            map_path: None,
+            loader: &mut *loader,
+            tilesets: None,
+        };
+        Object::parse(parser, attrs, &mut templates, false, false)
+    }
+
+    /// `new`, with templates loaded from `templates`. Objects that use a
+    /// template are merged with it, with the attributes, shape and properties
+    /// of the object itself taking precedence.
+    pub(crate) fn parse<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+        templates: &mut Templates,
+        in_template: bool,
+        strict_properties: bool,
+    ) -> Result<Object, Error> {
+        let mut extra = HashMap::new();
+        let ((id, gid, n, t, class, w, h, v, r, x, y, template), ()) = get_attrs!(
             attrs,
             optionals: [
                 ("id", id, |v:String| v.parse().ok()),
                 ("gid", gid, |v:String| v.parse().ok()),
                 ("name", name, |v:String| v.parse().ok()),
                 ("type", obj_type, |v:String| v.parse().ok()),
-                ("width", width, |v:String| v.parse().ok()),
-                ("height", height, |v:String| v.parse().ok()),
-                ("visible", visible, |v:String| v.parse().ok()),
-                ("rotation", rotation, |v:String| v.parse().ok()),
-            ],
-            required: [
-                ("x", x, |v:String| v.parse().ok()),
-                ("y", y, |v:String| v.parse().ok()),
+                ("class", class, |v| Some(v)),
+                ("width", width, |v| Some(v)),
+                ("height", height, |v| Some(v)),
+                ("visible", visible, parse_bool_attr),
+                ("rotation", rotation, |v| Some(v)),
+                ("x", x, |v| Some(v)),
+                ("y", y, |v| Some(v)),
+                ("template", template, |v| Some(v)),
             ],
-            Error::MalformedAttributes("objects must have an x and a y number".to_string())
+            required: [],
+            Error::MalformedAttributes("objects must have an x and a y number".to_string()),
+            extra: &mut extra
         );
-        let v = v.unwrap_or(true);
-        let w = w.unwrap_or(0f32);
-        let h = h.unwrap_or(0f32);
-        let r = r.unwrap_or(0f32);
+        // Tiled 1.9 renamed the object type to class.
+        let t = class.or(t);
+        let parse_float = |name: &str, v: Option<String>| match v {
+            Some(v) => parse_float_attr(name, &v).map(Some),
+            None => Ok(None),
+        };
+        let (x, y) = (parse_float("object x", x)?, parse_float("object y", y)?);
+        let (w, h) = (
+            parse_float("object width", w)?,
+            parse_float("object height", h)?,
+        );
+        let r = parse_float("object rotation", r)?;
+        // Objects inside a template have no position of their own.
+        let (x, y) = match (x, y) {
+            (Some(x), Some(y)) => (x, y),
+            _ if in_template => (0.0, 0.0),
+            _ => {
+                return Err(Error::MalformedAttributes(
+                    "objects must have an x and a y number".to_string(),
+                ))
+            }
+        };
+        // Tiled doesn't chain templates, so only one level is resolved.
+        let template = match template {
+            Some(_) if in_template => {
+                return Err(Error::Other(
+                    "nested templates are not supported".to_string(),
+                ))
+            }
+            Some(ref source) => Some(Object::new_template(source, templates, strict_properties)?),
+            None => None,
+        };
+        let (template_w, template_h) = template
+            .as_ref()
+            .and_then(|t| t.shape.size())
+            .unwrap_or((0f32, 0f32));
+        let w = w.unwrap_or(template_w);
+        let h = h.unwrap_or(template_h);
         let id = id.unwrap_or(0u32);
-        let gid = gid.unwrap_or(0u32);
-        let n = n.unwrap_or(String::new());
-        let t = t.unwrap_or(String::new());
         let mut shape = None;
         let mut properties = HashMap::new();
 
@@ -118,6 +348,18 @@ impl Object {
                 });
                 Ok(())
             },
+            "text" => |attrs| {
+                shape = Some(ObjectShape::Text {
+                    width: w,
+                    height: h,
+                    text: Text::new(parser, attrs)?,
+                });
+                Ok(())
+            },
+            "point" => |_| {
+                shape = Some(ObjectShape::Point);
+                Ok(())
+            },
             "polyline" => |attrs| {
                 shape = Some(Object::new_polyline(attrs)?);
                 Ok(())
@@ -127,11 +369,31 @@ impl Object {
                 Ok(())
             },
             "properties" => |_| {
-                properties = parse_properties(parser)?;
+                properties = parse_properties_impl(parser, strict_properties)?;
                 Ok(())
             },
         });
 
+        if let Some(template) = template {
+            let mut merged_properties = template.properties;
+            merged_properties.extend(properties);
+            let mut merged_extra = template.extra;
+            merged_extra.extend(extra);
+            return Ok(Object {
+                id: id,
+                gid: gid.unwrap_or(template.gid),
+                name: n.unwrap_or(template.name),
+                obj_type: t.unwrap_or(template.obj_type),
+                x: x,
+                y: y,
+                rotation: r.unwrap_or(template.rotation),
+                visible: v.unwrap_or(template.visible),
+                shape: shape.unwrap_or(template.shape.with_size(w, h)),
+                properties: merged_properties,
+                extra: merged_extra,
+            });
+        }
+
         let shape = shape.unwrap_or(ObjectShape::Rect {
             width: w,
             height: h,
@@ -139,18 +401,125 @@ impl Object {
 
         Ok(Object {
             id: id,
-            gid: gid,
-            name: n.clone(),
-            obj_type: t.clone(),
+            gid: gid.unwrap_or(0u32),
+            name: n.unwrap_or(String::new()),
+            obj_type: t.unwrap_or(String::new()),
             x: x,
             y: y,
-            rotation: r,
-            visible: v,
+            rotation: r.unwrap_or(0f32),
+            visible: v.unwrap_or(true),
             shape: shape,
             properties: properties,
+            extra,
         })
     }
 
+    /// Loads the object stored in the template file `source` with the loader
+    /// of `templates`. The gid of a tile object is remapped from the tileset
+    /// it has in the template to the same tileset in the map.
+    fn new_template(
+        source: &str,
+        templates: &mut Templates,
+        strict_properties: bool,
+    ) -> Result<Object, Error> {
+        let file = (templates.loader)(source)?;
+        let mut template_parser = EventReader::new(file);
+        let mut tilesets = Vec::new();
+        loop {
+            match template_parser.next()? {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => {
+                    if name.local_name == "tileset" {
+                        let ((), tileset) = get_attrs!(
+                            attributes,
+                            optionals: [],
+                            required: [
+                                ("firstgid", first_gid, |v:String| v.parse().ok()),
+                                ("source", source, |v| Some(v)),
+                            ],
+                            Error::MalformedAttributes("template tilesets must have a firstgid and a source".to_string())
+                        );
+                        tilesets.push(tileset);
+                    } else if name.local_name == "object" {
+                        let mut object = Object::parse(
+                            &mut template_parser,
+                            attributes,
+                            templates,
+                            true,
+                            strict_properties,
+                        )?;
+                        object.gid = templates.remap_gid(source, &tilesets, object.gid)?;
+                        return Ok(object);
+                    }
+                }
+                XmlEvent::EndDocument => {
+                    return Err(Error::PrematureEnd(
+                        "Template Document ended before an object was parsed".to_string(),
+                    ))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The axis-aligned bounding box of this object in map pixels, as
+    /// `(x, y, width, height)` with `x` and `y` the top-left corner. The
+    /// object's `rotation` is taken into account: like Tiled, objects are
+    /// rotated clockwise around their position, which is the top-left corner
+    /// for most objects and the bottom-left corner for tile objects.
+    pub fn bounding_box(&self) -> (f32, f32, f32, f32) {
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let (mut min_x, mut min_y) = (f32::MAX, f32::MAX);
+        let (mut max_x, mut max_y) = (f32::MIN, f32::MIN);
+        for &(px, py) in self.outline().iter() {
+            let x = self.x + px * cos - py * sin;
+            let y = self.y + px * sin + py * cos;
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+        (min_x, min_y, max_x - min_x, max_y - min_y)
+    }
+
+    /// The points spanning the extent of the shape, relative to the object's
+    /// position.
+    fn outline(&self) -> Vec<(f32, f32)> {
+        match self.shape {
+            ObjectShape::Rect { width, height }
+            | ObjectShape::Ellipse { width, height }
+            | ObjectShape::Text { width, height, .. } => {
+                // Tile objects are positioned by their bottom-left corner.
+                let top = if is_empty_gid(self.gid) { 0.0 } else { -height };
+                vec![
+                    (0.0, top),
+                    (width, top),
+                    (width, top + height),
+                    (0.0, top + height),
+                ]
+            }
+            ObjectShape::Polyline { ref points } | ObjectShape::Polygon { ref points } => {
+                if points.is_empty() {
+                    vec![(0.0, 0.0)]
+                } else {
+                    points.clone()
+                }
+            }
+            ObjectShape::Point => vec![(0.0, 0.0)],
+        }
+    }
+
+    /// The tile a tile object displays, looked up in the tilesets of `map`.
+    /// Flip flags on the gid are ignored. Returns `None` if this is not a tile
+    /// object or if the tile has no extra information stored in its tileset.
+    pub fn get_tile<'a>(&self, map: &'a Map) -> Option<&'a Tile> {
+        if is_empty_gid(self.gid) {
+            return None;
+        }
+        map.get_tile_by_gid(self.gid)
+    }
+
     pub fn new_polyline(attrs: Vec<OwnedAttribute>) -> Result<ObjectShape, Error> {
         let ((), s) = get_attrs!(
             attrs,
@@ -178,7 +547,7 @@ impl Object {
     }
 
     pub fn parse_points(s: String) -> Result<Vec<(f32, f32)>, Error> {
-        let pairs = s.split(' ');
+        let pairs = s.split_whitespace();
         let mut points = Vec::new();
         for v in pairs.map(|p| p.split(',')) {
             let v: Vec<&str> = v.collect();
@@ -187,14 +556,154 @@ impl Object {
                     "one of a polyline's points does not have an x and y coordinate".to_string(),
                 ));
             }
-            let (x, y) = (v[0].parse().ok(), v[1].parse().ok());
-            if x.is_none() || y.is_none() {
-                return Err(Error::MalformedAttributes(
-                    "one of polyline's points does not have i32eger coordinates".to_string(),
-                ));
-            }
-            points.push((x.unwrap(), y.unwrap()));
+            let x = parse_float_attr("point x coordinate", v[0])?;
+            let y = parse_float_attr("point y coordinate", v[1])?;
+            points.push((x, y));
         }
         Ok(points)
     }
 }
+
+/// How the objects of an object group are sorted when drawn.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum DrawOrder {
+    /// In the order they are in `ObjectGroup::objects`.
+    Index,
+    /// By their y coordinate, so that objects further down are drawn on top.
+    TopDown,
+}
+
+impl FromStr for DrawOrder {
+    type Err = ParseTileError;
+
+    fn from_str(s: &str) -> Result<DrawOrder, ParseTileError> {
+        match s {
+            "index" => Ok(DrawOrder::Index),
+            "topdown" => Ok(DrawOrder::TopDown),
+            _ => Err(ParseTileError::DrawOrderError),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum HorizontalAlignment {
+    Left,
+    Center,
+    Right,
+    Justify,
+}
+
+impl FromStr for HorizontalAlignment {
+    type Err = ParseTileError;
+
+    fn from_str(s: &str) -> Result<HorizontalAlignment, ParseTileError> {
+        match s {
+            "left" => Ok(HorizontalAlignment::Left),
+            "center" => Ok(HorizontalAlignment::Center),
+            "right" => Ok(HorizontalAlignment::Right),
+            "justify" => Ok(HorizontalAlignment::Justify),
+            _ => Err(ParseTileError::AlignmentError),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum VerticalAlignment {
+    Top,
+    Center,
+    Bottom,
+}
+
+impl FromStr for VerticalAlignment {
+    type Err = ParseTileError;
+
+    fn from_str(s: &str) -> Result<VerticalAlignment, ParseTileError> {
+        match s {
+            "top" => Ok(VerticalAlignment::Top),
+            "center" => Ok(VerticalAlignment::Center),
+            "bottom" => Ok(VerticalAlignment::Bottom),
+            _ => Err(ParseTileError::AlignmentError),
+        }
+    }
+}
+
+/// The contents and styling of a text object.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Text {
+    pub font_family: String,
+    pub pixel_size: u32,
+    pub wrap: bool,
+    pub color: Color,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikeout: bool,
+    pub kerning: bool,
+    pub halign: HorizontalAlignment,
+    pub valign: VerticalAlignment,
+    pub contents: String,
+}
+
+impl Text {
+    pub fn new<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+    ) -> Result<Text, Error> {
+        let ((ff, ps, wr, c, b, i, u, s, k, ha, va), ()) = get_attrs!(
+            attrs,
+            optionals: [
+                ("fontfamily", font_family, |v| Some(v)),
+                ("pixelsize", pixel_size, |v:String| v.parse().ok()),
+                ("wrap", wrap, parse_bool_attr),
+                ("color", color, |v:String| v.parse().ok()),
+                ("bold", bold, parse_bool_attr),
+                ("italic", italic, parse_bool_attr),
+                ("underline", underline, parse_bool_attr),
+                ("strikeout", strikeout, parse_bool_attr),
+                ("kerning", kerning, parse_bool_attr),
+                ("halign", halign, |v:String| v.parse().ok()),
+                ("valign", valign, |v:String| v.parse().ok()),
+            ],
+            required: [],
+            Error::MalformedAttributes("text must have attributes with correct types".to_string())
+        );
+
+        let mut contents = String::new();
+        loop {
+            match parser.next()? {
+                XmlEvent::Characters(s) | XmlEvent::Whitespace(s) => contents.push_str(&s),
+                XmlEvent::EndElement { name, .. } => {
+                    if name.local_name == "text" {
+                        break;
+                    }
+                }
+                XmlEvent::EndDocument => {
+                    return Err(Error::PrematureEnd(
+                        "Document ended before text was parsed".to_string(),
+                    ))
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Text {
+            font_family: ff.unwrap_or_else(|| "sans-serif".to_string()),
+            pixel_size: ps.unwrap_or(16),
+            wrap: wr.unwrap_or(false),
+            color: c.unwrap_or(Color {
+                red: 0,
+                green: 0,
+                blue: 0,
+                alpha: 255,
+            }),
+            bold: b.unwrap_or(false),
+            italic: i.unwrap_or(false),
+            underline: u.unwrap_or(false),
+            strikeout: s.unwrap_or(false),
+            kerning: k.unwrap_or(true),
+            halign: ha.unwrap_or(HorizontalAlignment::Left),
+            valign: va.unwrap_or(VerticalAlignment::Top),
+            contents,
+        })
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{error::Error, get_attrs, parse_tag};
 use std::collections::HashMap;
 use std::io::Read;
+use std::path::PathBuf;
 use xml::{attribute::OwnedAttribute, reader::EventReader};
 
 #[derive(Debug, PartialEq, Clone)]
@@ -10,31 +11,38 @@ pub enum PropertyValue {
     IntValue(i32),
     ColorValue(u32),
     StringValue(String),
+    /// A path, relative to the file the property is written in.
+    FileValue(PathBuf),
+    /// The id of an object in the map, or 0 if no object is referenced.
+    ObjectValue(u32),
 }
 
 impl PropertyValue {
     pub fn new(property_type: String, value: String) -> Result<PropertyValue, Error> {
-        use std::error::Error as StdError;
-
         // Check the property type against the value.
         match property_type.as_str() {
             "bool" => match value.parse() {
                 Ok(val) => Ok(PropertyValue::BoolValue(val)),
-                Err(err) => Err(Error::Other(err.description().into())),
+                Err(err) => Err(Error::Other(err.to_string())),
             },
             "float" => match value.parse() {
                 Ok(val) => Ok(PropertyValue::FloatValue(val)),
-                Err(err) => Err(Error::Other(err.description().into())),
+                Err(err) => Err(Error::Other(err.to_string())),
             },
             "int" => match value.parse() {
                 Ok(val) => Ok(PropertyValue::IntValue(val)),
-                Err(err) => Err(Error::Other(err.description().into())),
+                Err(err) => Err(Error::Other(err.to_string())),
             },
             "color" if value.len() > 1 => match u32::from_str_radix(&value[1..], 16) {
                 Ok(color) => Ok(PropertyValue::ColorValue(color)),
                 Err(_) => Err(Error::Other(format!("Improperly formatted color property"))),
             },
             "string" => Ok(PropertyValue::StringValue(value)),
+            "file" => Ok(PropertyValue::FileValue(PathBuf::from(value))),
+            "object" => match value.parse() {
+                Ok(val) => Ok(PropertyValue::ObjectValue(val)),
+                Err(err) => Err(Error::Other(err.to_string())),
+            },
             _ => Err(Error::Other(format!(
                 "Unknown property type \"{}\"",
                 property_type
@@ -45,7 +53,73 @@ impl PropertyValue {
 
 pub type Properties = HashMap<String, PropertyValue>;
 
+/// Typed getters for `Properties`. Each returns `None` if there is no
+/// property named `key` or if it has a different type.
+pub trait PropertiesExt {
+    fn get_bool(&self, key: &str) -> Option<bool>;
+    fn get_int(&self, key: &str) -> Option<i32>;
+    fn get_float(&self, key: &str) -> Option<f32>;
+    fn get_string(&self, key: &str) -> Option<&str>;
+    /// The color as stored in `PropertyValue::ColorValue`.
+    fn get_color(&self, key: &str) -> Option<u32>;
+}
+
+impl PropertiesExt for Properties {
+    fn get_bool(&self, key: &str) -> Option<bool> {
+        match self.get(key) {
+            Some(&PropertyValue::BoolValue(v)) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn get_int(&self, key: &str) -> Option<i32> {
+        match self.get(key) {
+            Some(&PropertyValue::IntValue(v)) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn get_float(&self, key: &str) -> Option<f32> {
+        match self.get(key) {
+            Some(&PropertyValue::FloatValue(v)) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn get_string(&self, key: &str) -> Option<&str> {
+        match self.get(key) {
+            Some(PropertyValue::StringValue(v)) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn get_color(&self, key: &str) -> Option<u32> {
+        match self.get(key) {
+            Some(&PropertyValue::ColorValue(v)) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+/// Parses the `<property>` children of a `<properties>` element, the start of
+/// which must just have been read from `parser`. When a name is used more than
+/// once, the last value wins, as in Tiled.
 pub fn parse_properties<R: Read>(parser: &mut EventReader<R>) -> Result<Properties, Error> {
+    parse_properties_impl(parser, false)
+}
+
+/// Like `parse_properties`, but a name used more than once is an
+/// `Error::MalformedAttributes` instead of overwriting the earlier value. This
+/// is meant for tools that check maps for authoring mistakes.
+pub fn parse_properties_strict<R: Read>(parser: &mut EventReader<R>) -> Result<Properties, Error> {
+    parse_properties_impl(parser, true)
+}
+
+/// `parse_properties`, or `parse_properties_strict` if `strict` is set.
+pub(crate) fn parse_properties_impl<R: Read>(
+    parser: &mut EventReader<R>,
+    strict: bool,
+) -> Result<Properties, Error> {
     let mut p = HashMap::new();
     parse_tag!(parser, "properties", {
         "property" => |attrs:Vec<OwnedAttribute>| {
@@ -62,6 +136,12 @@ pub fn parse_properties<R: Read>(parser: &mut EventReader<R>) -> Result<Properti
             );
             let t = t.unwrap_or("string".into());
 
+            if strict && p.contains_key(&k) {
+                return Err(Error::MalformedAttributes(format!(
+                    "property {:?} is defined more than once",
+                    k
+                )));
+            }
             p.insert(k, PropertyValue::new(t, v)?);
             Ok(())
         },
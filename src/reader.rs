@@ -0,0 +1,208 @@
+use crate::{
+    error::Error,
+    layer::{check_map_height, Group, ImageLayer, Layer},
+    map::{default_loader, parse_editor_settings, Map, ParseOptions},
+    object::{ObjectGroup, Templates},
+    property::parse_properties,
+    tileset::Tileset,
+};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use xml::{attribute::OwnedAttribute, common::Position, reader::XmlEvent, EventReader};
+
+/// A top-level layer of a map, as produced by `MapReader`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParsedLayer {
+    Tile(Layer),
+    Image(ImageLayer),
+    Objects(ObjectGroup),
+    Group(Group),
+}
+
+/// Reads a map one top-level layer at a time, so that the tile data of each
+/// layer can be processed and dropped before the next one is parsed.
+///
+/// Everything but the layers is available from `header` as soon as the
+/// reader is created. The layers of the header are always empty; they are
+/// yielded by iterating over the reader instead, in document order. Tiled
+/// writes tilesets before any layer, but should a tileset come after a layer
+/// it is added to the header once the reader gets to it.
+pub struct MapReader<R: Read> {
+    parser: EventReader<R>,
+    map_path: Option<PathBuf>,
+    header: Map,
+    layer_index: u32,
+    /// The first layer, which is read ahead while parsing the header.
+    pending: Option<Result<ParsedLayer, Error>>,
+    finished: bool,
+}
+
+impl<R: Read> MapReader<R> {
+    /// Starts reading a map, parsing everything up to its first layer. If
+    /// `path` is given, external tilesets are loaded relative to it as in
+    /// `Map::parse_with_path`.
+    pub fn new(reader: R, path: Option<&Path>) -> Result<MapReader<R>, Error> {
+        let mut parser = EventReader::new(reader);
+        let attrs = loop {
+            match parser.next()? {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => {
+                    if name.local_name == "map" {
+                        break attributes;
+                    }
+                }
+                XmlEvent::EndDocument => {
+                    return Err(Error::PrematureEnd(
+                        "Document ended before map was parsed".to_string(),
+                    ))
+                }
+                _ => {}
+            }
+        };
+        let header = Map::from_attrs(attrs).map_err(|e| e.at_position(parser.position()))?;
+        let mut reader = MapReader {
+            parser,
+            map_path: path.map(Path::to_path_buf),
+            header,
+            layer_index: 0,
+            pending: None,
+            finished: false,
+        };
+        reader.pending = reader.next_layer().transpose();
+        Ok(reader)
+    }
+
+    /// The map without any of its layers.
+    pub fn header(&self) -> &Map {
+        &self.header
+    }
+
+    /// Consumes the reader, returning the header. Any layers that have not
+    /// been read yet are never parsed.
+    pub fn into_header(self) -> Map {
+        self.header
+    }
+
+    fn next_layer(&mut self) -> Result<Option<ParsedLayer>, Error> {
+        if self.finished {
+            return Ok(None);
+        }
+        let layer = self.read_layer();
+        if !matches!(layer, Ok(Some(_))) {
+            self.finished = true;
+        }
+        let position = self.parser.position();
+        layer.map_err(|e| e.at_position(position))
+    }
+
+    /// Parses the children of the map until the next layer, or the end of the
+    /// map.
+    fn read_layer(&mut self) -> Result<Option<ParsedLayer>, Error> {
+        loop {
+            match self.parser.next()? {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => {
+                    if let Some(layer) = self.read_element(&name.local_name, attributes)? {
+                        return Ok(Some(layer));
+                    }
+                }
+                XmlEvent::EndElement { name } => {
+                    if name.local_name == "map" {
+                        return Ok(None);
+                    }
+                }
+                XmlEvent::EndDocument => {
+                    return Err(Error::PrematureEnd(
+                        "Document ended before we expected.".to_string(),
+                    ))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn read_element(
+        &mut self,
+        name: &str,
+        attrs: Vec<OwnedAttribute>,
+    ) -> Result<Option<ParsedLayer>, Error> {
+        let parser = &mut self.parser;
+        let map_path = self.map_path.as_deref();
+        let (width, height) = (self.header.width, self.header.height);
+        let mut loader = default_loader(map_path);
+        let mut templates = Templates {
+            map_path,
+            loader: &mut *loader,
+            tilesets: Some(&self.header.tilesets),
+        };
+        let layer = match name {
+            "tileset" => {
+                let index = self.header.tilesets.len();
+                let tileset = Tileset::parse_in_map(
+                    parser,
+                    attrs,
+                    index,
+                    map_path,
+                    &mut *loader,
+                    None,
+                    false,
+                )?;
+                self.header.tilesets.push(tileset);
+                return Ok(None);
+            }
+            "properties" => {
+                self.header.properties = parse_properties(parser)?;
+                return Ok(None);
+            }
+            "editorsettings" => {
+                self.header.chunk_size = parse_editor_settings(parser)?;
+                return Ok(None);
+            }
+            "layer" => {
+                let layer = Layer::parse(parser, attrs, width, self.layer_index, false)?;
+                let layer = check_map_height(layer, height)?;
+                self.layer_index += 1;
+                ParsedLayer::Tile(layer)
+            }
+            "imagelayer" => {
+                let layer = ImageLayer::new(parser, attrs, self.layer_index)?;
+                self.layer_index += 1;
+                ParsedLayer::Image(layer)
+            }
+            "objectgroup" => {
+                let layer = ObjectGroup::parse(
+                    parser,
+                    attrs,
+                    Some(self.layer_index),
+                    &mut templates,
+                    false,
+                )?;
+                self.layer_index += 1;
+                ParsedLayer::Objects(layer)
+            }
+            "group" => ParsedLayer::Group(Group::parse(
+                parser,
+                attrs,
+                width,
+                Some(height),
+                &mut self.layer_index,
+                &mut templates,
+                &mut ParseOptions::default(),
+            )?),
+            _ => return Ok(None),
+        };
+        Ok(Some(layer))
+    }
+}
+
+impl<R: Read> Iterator for MapReader<R> {
+    type Item = Result<ParsedLayer, Error>;
+
+    fn next(&mut self) -> Option<Result<ParsedLayer, Error>> {
+        self.pending
+            .take()
+            .or_else(|| self.next_layer().transpose())
+    }
+}
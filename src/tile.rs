@@ -1,6 +1,14 @@
 use crate::{
-    error::Error, get_attrs, image::Image, object::ObjectGroup, parse_tag,
-    property::parse_properties, property::Properties,
+    color::Color,
+    error::Error,
+    get_attrs,
+    image::Image,
+    macros::parse_float_attr,
+    map::default_loader,
+    object::{Object, ObjectGroup, Templates},
+    parse_tag,
+    property::parse_properties_impl,
+    property::Properties,
 };
 use std::{collections::HashMap, io::Read};
 use xml::{attribute::OwnedAttribute, EventReader};
@@ -10,30 +18,66 @@ pub struct Tile {
     pub id: u32,
     pub flip_h: bool,
     pub flip_v: bool,
+    /// Whether the diagonal flip flag was set, meaning the tile is flipped
+    /// over its top-left to bottom-right diagonal. `flip_h` and `flip_v`
+    /// already have this folded in, so together with this flag they describe
+    /// the rotation Tiled applies.
+    pub flip_d: bool,
     pub images: Vec<Image>,
     pub properties: Properties,
     pub objectgroup: Option<ObjectGroup>,
     pub animation: Option<Vec<Frame>>,
     pub tile_type: Option<String>,
     pub probability: f32,
+    /// The legacy terrain at the top left, top right, bottom left and bottom
+    /// right corner of this tile, as indices into `Tileset::terrain_types`.
+    pub terrain: Option<[Option<u32>; 4]>,
 }
 
-const FLIPPED_HORIZONTALLY_FLAG: u32 = 0x8;
-const FLIPPED_VERTICALLY_FLAG: u32 = 0x4;
-const FLIPPED_DIAGONALLY_FLAG: u32 = 0x2;
-const ALL_FLIP_FLAGS: u32 =
+pub(crate) const FLIPPED_HORIZONTALLY_FLAG: u32 = 0x80000000;
+pub(crate) const FLIPPED_VERTICALLY_FLAG: u32 = 0x40000000;
+pub(crate) const FLIPPED_DIAGONALLY_FLAG: u32 = 0x20000000;
+pub(crate) const ALL_FLIP_FLAGS: u32 =
     FLIPPED_HORIZONTALLY_FLAG | FLIPPED_VERTICALLY_FLAG | FLIPPED_DIAGONALLY_FLAG;
 
+/// Whether `gid` is the empty tile. Tiled writes gid 0 for cells without a
+/// tile; flip flags on an empty cell are ignored.
+pub fn is_empty_gid(gid: u32) -> bool {
+    gid & !ALL_FLIP_FLAGS == 0
+}
+
 impl Tile {
+    /// Parses a `<tile>` element. The location of its tileset is unknown
+    /// here, so collision objects that use a template can't be parsed.
     pub fn new<R: Read>(
         parser: &mut EventReader<R>,
         attrs: Vec<OwnedAttribute>,
     ) -> Result<Tile, Error> {
-        let ((tile_type, probability), id) = get_attrs!(
+        let mut loader = default_loader(None);
+        let mut templates = Templates {
+            map_path: None,
+            loader: &mut *loader,
+            tilesets: None,
+        };
+        Tile::parse(parser, attrs, &mut templates, false)
+    }
+
+    /// `new`, with the templates of collision objects loaded from `templates`.
+    /// The tileset itself is still being parsed, so the gids of tile templates
+    /// are kept as written in the template.
+    pub(crate) fn parse<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+        templates: &mut Templates,
+        strict_properties: bool,
+    ) -> Result<Tile, Error> {
+        let ((tile_type, class, probability, terrain), id) = get_attrs!(
             attrs,
             optionals: [
                 ("type", tile_type, |v:String| v.parse().ok()),
-                ("probability", probability, |v:String| v.parse().ok()),
+                ("class", class, |v| Some(v)),
+                ("probability", probability, |v| Some(v)),
+                ("terrain", terrain, |v:String| parse_terrain(&v)),
             ],
             required: [
                 ("id", id, |v:String| v.parse::<u32>().ok()),
@@ -41,7 +85,14 @@ impl Tile {
             Error::MalformedAttributes("tile must have an id with the correct type".to_string())
         );
 
-        let flags = (id & ALL_FLIP_FLAGS) >> 28;
+        // Tiled 1.9 renamed the tile type to class.
+        let tile_type = class.or(tile_type);
+        let probability = match probability {
+            Some(v) => Some(parse_float_attr("tile probability", &v)?),
+            None => None,
+        };
+
+        let flags = id & ALL_FLIP_FLAGS;
         let id: u32 = id & !ALL_FLIP_FLAGS;
         let diagon = flags & FLIPPED_DIAGONALLY_FLAG == FLIPPED_DIAGONALLY_FLAG;
         let flip_h = (flags & FLIPPED_HORIZONTALLY_FLAG == FLIPPED_HORIZONTALLY_FLAG) ^ diagon;
@@ -57,11 +108,17 @@ impl Tile {
                 Ok(())
             },
             "properties" => |_| {
-                properties = parse_properties(parser)?;
+                properties = parse_properties_impl(parser, strict_properties)?;
                 Ok(())
             },
             "objectgroup" => |attrs| {
-                objectgroup = Some(ObjectGroup::new(parser, attrs, None)?);
+                objectgroup = Some(ObjectGroup::parse(
+                    parser,
+                    attrs,
+                    None,
+                    templates,
+                    strict_properties,
+                )?);
                 Ok(())
             },
             "animation" => |_| {
@@ -73,20 +130,82 @@ impl Tile {
             id,
             flip_h,
             flip_v,
+            flip_d: diagon,
             images,
             properties,
             objectgroup,
             animation,
             tile_type,
             probability: probability.unwrap_or(1.0),
+            terrain,
         })
     }
+
+    /// The objects making up the collision shape of this tile, relative to its
+    /// top-left corner. Empty if the tile has no collision group.
+    pub fn collision_shapes(&self) -> impl Iterator<Item = &Object> {
+        self.objectgroup
+            .iter()
+            .flat_map(|group| group.objects.iter())
+    }
+
+    /// The length of one loop of the animation of this tile, in milliseconds.
+    /// `None` if the tile is not animated.
+    pub fn animation_duration(&self) -> Option<u32> {
+        match self.animation {
+            Some(ref frames) if !frames.is_empty() => {
+                Some(frames.iter().map(|frame| frame.duration).sum())
+            }
+            _ => None,
+        }
+    }
+
+    /// The local id of the tile to show `elapsed_ms` milliseconds after the
+    /// animation of this tile started, looping as Tiled does. `None` if the
+    /// tile is not animated.
+    pub fn animation_frame_at(&self, elapsed_ms: u32) -> Option<u32> {
+        let frames = self.animation.as_ref()?;
+        let total = self.animation_duration()?;
+        if total == 0 {
+            return Some(frames[0].tile_id);
+        }
+        let mut time = elapsed_ms % total;
+        for frame in frames.iter() {
+            if time < frame.duration {
+                return Some(frame.tile_id);
+            }
+            time -= frame.duration;
+        }
+        None
+    }
+
+    /// The color that is drawn as transparent in the image of this tile, as
+    /// used by tiles of image collection tilesets.
+    pub fn transparent_color(&self) -> Option<Color> {
+        self.images.first()?.transparent_color
+    }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// Parses the four comma separated corner terrains of a tile, where an empty
+/// value means the corner has no terrain.
+fn parse_terrain(v: &str) -> Option<[Option<u32>; 4]> {
+    let corners: Vec<&str> = v.split(',').collect();
+    if corners.len() != 4 {
+        return None;
+    }
+    let mut terrain = [None; 4];
+    for (corner, v) in terrain.iter_mut().zip(corners) {
+        if !v.trim().is_empty() {
+            *corner = Some(v.trim().parse().ok()?);
+        }
+    }
+    Some(terrain)
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct Frame {
-    tile_id: u32,
-    duration: u32,
+    pub(crate) tile_id: u32,
+    pub(crate) duration: u32,
 }
 
 impl Frame {
@@ -105,6 +224,16 @@ impl Frame {
             duration: duration,
         })
     }
+
+    /// The local id of the tile shown during this frame.
+    pub fn tile_id(&self) -> u32 {
+        self.tile_id
+    }
+
+    /// How long this frame is shown for, in milliseconds.
+    pub fn duration(&self) -> u32 {
+        self.duration
+    }
 }
 
 fn parse_animation<R: Read>(parser: &mut EventReader<R>) -> Result<Vec<Frame>, Error> {
@@ -1,41 +1,136 @@
-use crate::{error::Error, get_attrs, image::Image, parse_tag, tile::Tile};
-use std::{fs::File, io::Read, path::Path};
-use xml::{attribute::OwnedAttribute, reader::XmlEvent, EventReader};
+use crate::{
+    error::Error,
+    get_attrs,
+    image::Image,
+    map::{default_loader, nested_source, resolve_relative, source_path},
+    object::Templates,
+    parse_tag,
+    tile::{is_empty_gid, Tile, ALL_FLIP_FLAGS},
+    wangset::{parse_wang_sets, WangSet},
+};
+use std::{
+    collections::HashMap,
+    io::Read,
+    path::{Path, PathBuf},
+};
+use xml::{attribute::OwnedAttribute, common::Position, reader::XmlEvent, EventReader};
+
+/// Resolves the `source` attribute of an external tileset, or the `template`
+/// attribute of an object, to a reader over the `.tsx` or `.tx` file's
+/// contents.
+pub type TilesetLoader<'a> = dyn FnMut(&str) -> Result<Box<dyn Read>, Error> + 'a;
 
 /// A tileset, usually the tilesheet image.
 #[derive(Debug, PartialEq, Clone)]
 pub struct Tileset {
     /// The GID of the first tile stored
     pub first_gid: u32,
+    /// The index of this tileset within `Map::tilesets`.
+    pub tileset_index: usize,
     pub name: String,
+    /// The class of this tileset, used by Tiled for custom types.
+    pub class: Option<String>,
     pub tile_width: u32,
     pub tile_height: u32,
     pub spacing: u32,
     pub margin: u32,
+    /// The number of tiles in this tileset, as written in the `tilecount`
+    /// attribute. Older files may not have it; see `tile_count_effective`.
+    pub tile_count: Option<u32>,
+    /// The number of tile columns in the tilesheet, as written in the
+    /// `columns` attribute. Older files may not have it.
+    pub columns: Option<u32>,
     /// The Tiled spec says that a tileset can have mutliple images so a `Vec`
-    /// is used. Usually you will only use one.
+    /// is used. Usually you will only use one. Any images after the first are
+    /// kept in document order but are otherwise unused: helpers such as
+    /// `image` always refer to the image at index 0.
     pub images: Vec<Image>,
     pub tiles: Vec<Tile>,
+    pub wang_sets: Vec<WangSet>,
+    /// The terrains of Tiled versions before 1.5, which replaced them with
+    /// wang sets.
+    pub terrain_types: Vec<Terrain>,
+    /// The path of the `.tsx` file an external tileset was loaded from,
+    /// resolved relative to the map. `None` for tilesets embedded in the map.
+    pub source: Option<PathBuf>,
+    /// The `source` attribute of an external tileset as written in the map,
+    /// relative to the map's directory. Writing the map refers to the tileset
+    /// with it, or with `source` if this is `None`.
+    pub source_attribute: Option<String>,
+    /// The attributes of the `<tileset>` element that this crate doesn't
+    /// parse; see `Map::extra`. For an external tileset these come from the
+    /// `.tsx` file.
+    pub extra: HashMap<String, String>,
 }
 
 impl Tileset {
+    /// Parses a `<tileset>` element of the map at `map_path`, which an
+    /// external tileset is loaded relative to. The tileset gets a
+    /// `tileset_index` of 0.
     pub fn new<R: Read>(
         parser: &mut EventReader<R>,
         attrs: Vec<OwnedAttribute>,
         map_path: Option<&Path>,
     ) -> Result<Tileset, Error> {
-        Tileset::new_internal(parser, &attrs).or_else(|_| Tileset::new_reference(&attrs, map_path))
+        let mut loader = default_loader(map_path);
+        Tileset::parse_in_map(parser, attrs, 0, map_path, &mut *loader, None, false)
+    }
+
+    /// `new`, for the tileset at `tileset_index` in the map. External tilesets
+    /// and the templates of collision objects are opened with `loader`, going
+    /// through `cache` if there is one for the former, and property names used
+    /// more than once are an error if `strict_properties` is set; see
+    /// `ParseOptions::strict_properties`.
+    pub(crate) fn parse_in_map<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+        tileset_index: usize,
+        map_path: Option<&Path>,
+        loader: &mut TilesetLoader,
+        cache: Option<&mut HashMap<PathBuf, Tileset>>,
+        strict_properties: bool,
+    ) -> Result<Tileset, Error> {
+        if attrs.iter().any(|attr| attr.name.local_name == "source") {
+            Tileset::new_reference(
+                &attrs,
+                tileset_index,
+                map_path,
+                loader,
+                cache,
+                strict_properties,
+            )
+        } else {
+            let mut templates = Templates {
+                map_path,
+                loader,
+                tilesets: None,
+            };
+            Tileset::new_internal(
+                parser,
+                &attrs,
+                tileset_index,
+                &mut templates,
+                strict_properties,
+            )
+        }
     }
 
     fn new_internal<R: Read>(
         parser: &mut EventReader<R>,
         attrs: &Vec<OwnedAttribute>,
+        tileset_index: usize,
+        templates: &mut Templates,
+        strict_properties: bool,
     ) -> Result<Tileset, Error> {
-        let ((spacing, margin), (first_gid, name, width, height)) = get_attrs!(
+        let mut extra = HashMap::new();
+        let ((spacing, margin, tile_count, columns, class), (first_gid, name, width, height)) = get_attrs!(
            attrs,
            optionals: [
                 ("spacing", spacing, |v:String| v.parse().ok()),
                 ("margin", margin, |v:String| v.parse().ok()),
+                ("tilecount", tile_count, |v:String| v.parse().ok()),
+                ("columns", columns, |v:String| v.parse().ok()),
+                ("class", class, |v| Some(v)),
             ],
            required: [
                 ("firstgid", first_gid, |v:String| v.parse().ok()),
@@ -43,37 +138,61 @@ impl Tileset {
                 ("tilewidth", width, |v:String| v.parse().ok()),
                 ("tileheight", height, |v:String| v.parse().ok()),
             ],
-            Error::MalformedAttributes("tileset must have a firstgid, name tile width and height with correct types".to_string())
+            Error::MalformedAttributes("tileset must have a firstgid, name tile width and height with correct types".to_string()),
+            extra: &mut extra
         );
 
         let mut images = Vec::new();
         let mut tiles = Vec::new();
+        let mut wang_sets = Vec::new();
+        let mut terrain_types = Vec::new();
         parse_tag!(parser, "tileset", {
             "image" => |attrs| {
                 images.push(Image::new(parser, attrs)?);
                 Ok(())
             },
             "tile" => |attrs| {
-                tiles.push(Tile::new(parser, attrs)?);
+                tiles.push(Tile::parse(parser, attrs, templates, strict_properties)?);
+                Ok(())
+            },
+            "wangsets" => |_| {
+                wang_sets = parse_wang_sets(parser, strict_properties)?;
+                Ok(())
+            },
+            "terraintypes" => |_| {
+                terrain_types = parse_terrain_types(parser)?;
                 Ok(())
             },
         });
 
         Ok(Tileset {
             first_gid: first_gid,
+            tileset_index,
             name: name,
+            class,
             tile_width: width,
             tile_height: height,
             spacing: spacing.unwrap_or(0),
             margin: margin.unwrap_or(0),
+            tile_count,
+            columns,
             images: images,
             tiles: tiles,
+            wang_sets,
+            terrain_types,
+            source: None,
+            source_attribute: None,
+            extra,
         })
     }
 
     fn new_reference(
         attrs: &Vec<OwnedAttribute>,
+        tileset_index: usize,
         map_path: Option<&Path>,
+        loader: &mut TilesetLoader,
+        cache: Option<&mut HashMap<PathBuf, Tileset>>,
+        strict_properties: bool,
     ) -> Result<Tileset, Error> {
         let ((), (first_gid, source)) = get_attrs!(
             attrs,
@@ -85,28 +204,79 @@ impl Tileset {
             Error::MalformedAttributes("tileset must have a firstgid, name tile width and height with correct types".to_string())
         );
 
-        let tileset_path = map_path.ok_or(Error::Other("Maps with external tilesets must know their file location.  See parse_with_path(Path).".to_string()))?.with_file_name(source);
-        let file = File::open(&tileset_path).map_err(|_| {
-            Error::Other(format!(
-                "External tileset file not found: {:?}",
-                tileset_path
-            ))
-        })?;
-        Tileset::new_external(file, first_gid)
+        let tileset_path = source_path(map_path, &source);
+        if let Some(cached) = cache.as_ref().and_then(|c| c.get(&tileset_path)) {
+            let mut tileset = cached.clone();
+            tileset.first_gid = first_gid;
+            tileset.tileset_index = tileset_index;
+            tileset.source_attribute = Some(source);
+            return Ok(tileset);
+        }
+
+        let file = loader(&source)?;
+        let mut tileset = Tileset::parse_source(
+            file,
+            first_gid,
+            tileset_index,
+            &source,
+            loader,
+            strict_properties,
+        )?;
+        tileset.source = Some(tileset_path.clone());
+        tileset.source_attribute = Some(source);
+        if let Some(cache) = cache {
+            cache.insert(tileset_path, tileset.clone());
+        }
+        Ok(tileset)
+    }
+
+    /// Parses the `.tsx` file that the map refers to as `source`, and that
+    /// `loader` opened as `file`. The templates of collision objects are
+    /// written relative to the tileset file, and are opened with `loader` as
+    /// well.
+    pub(crate) fn parse_source<R: Read>(
+        file: R,
+        first_gid: u32,
+        tileset_index: usize,
+        source: &str,
+        loader: &mut TilesetLoader,
+        strict_properties: bool,
+    ) -> Result<Tileset, Error> {
+        let mut tileset_loader = |template: &str| loader(&nested_source(source, template));
+        let mut templates = Templates {
+            map_path: None,
+            loader: &mut tileset_loader,
+            tilesets: None,
+        };
+        Tileset::new_external(
+            &mut EventReader::new(file),
+            first_gid,
+            tileset_index,
+            &mut templates,
+            strict_properties,
+        )
     }
 
-    fn new_external<R: Read>(file: R, first_gid: u32) -> Result<Tileset, Error> {
-        let mut tileset_parser = EventReader::new(file);
+    fn new_external<R: Read>(
+        tileset_parser: &mut EventReader<R>,
+        first_gid: u32,
+        tileset_index: usize,
+        templates: &mut Templates,
+        strict_properties: bool,
+    ) -> Result<Tileset, Error> {
         loop {
-            match tileset_parser.next().map_err(Error::XmlDecodingError)? {
+            match tileset_parser.next()? {
                 XmlEvent::StartElement {
                     name, attributes, ..
                 } => {
                     if name.local_name == "tileset" {
                         return Tileset::parse_external(
                             first_gid,
-                            &mut tileset_parser,
+                            tileset_index,
+                            tileset_parser,
                             &attributes,
+                            templates,
+                            strict_properties,
                         );
                     }
                 }
@@ -122,45 +292,211 @@ impl Tileset {
 
     fn parse_external<R: Read>(
         first_gid: u32,
+        tileset_index: usize,
         parser: &mut EventReader<R>,
         attrs: &Vec<OwnedAttribute>,
+        templates: &mut Templates,
+        strict_properties: bool,
     ) -> Result<Tileset, Error> {
-        let ((spacing, margin), (name, width, height)) = get_attrs!(
+        let mut extra = HashMap::new();
+        let ((spacing, margin, tile_count, columns, class), (name, width, height)) = get_attrs!(
             attrs,
             optionals: [
                 ("spacing", spacing, |v:String| v.parse().ok()),
                 ("margin", margin, |v:String| v.parse().ok()),
+                ("tilecount", tile_count, |v:String| v.parse().ok()),
+                ("columns", columns, |v:String| v.parse().ok()),
+                ("class", class, |v| Some(v)),
             ],
             required: [
                 ("name", name, |v| Some(v)),
                 ("tilewidth", width, |v:String| v.parse().ok()),
                 ("tileheight", height, |v:String| v.parse().ok()),
             ],
-            Error::MalformedAttributes("tileset must have a firstgid, name tile width and height with correct types".to_string())
+            Error::MalformedAttributes("tileset must have a firstgid, name tile width and height with correct types".to_string()),
+            extra: &mut extra
         );
 
         let mut images = Vec::new();
         let mut tiles = Vec::new();
+        let mut wang_sets = Vec::new();
+        let mut terrain_types = Vec::new();
         parse_tag!(parser, "tileset", {
             "image" => |attrs| {
                 images.push(Image::new(parser, attrs)?);
                 Ok(())
             },
             "tile" => |attrs| {
-                tiles.push(Tile::new(parser, attrs)?);
+                tiles.push(Tile::parse(parser, attrs, templates, strict_properties)?);
+                Ok(())
+            },
+            "wangsets" => |_| {
+                wang_sets = parse_wang_sets(parser, strict_properties)?;
+                Ok(())
+            },
+            "terraintypes" => |_| {
+                terrain_types = parse_terrain_types(parser)?;
                 Ok(())
             },
         });
 
         Ok(Tileset {
             first_gid: first_gid,
+            tileset_index,
             name: name,
+            class,
             tile_width: width,
             tile_height: height,
             spacing: spacing.unwrap_or(0),
             margin: margin.unwrap_or(0),
+            tile_count,
+            columns,
             images: images,
             tiles: tiles,
+            wang_sets,
+            terrain_types,
+            source: None,
+            source_attribute: None,
+            extra,
+        })
+    }
+
+    /// The number of tiles in this tileset. This is `tile_count` when the file
+    /// provides it. Otherwise it is worked out from the size of the tilesheet
+    /// image, or, for tilesets without one, from the highest tile id.
+    pub fn tile_count_effective(&self) -> u32 {
+        if let Some(tile_count) = self.tile_count {
+            return tile_count;
+        }
+        match self.image() {
+            Some(image) => {
+                let (columns, rows) = self.grid_size(image);
+                columns * rows
+            }
+            None => self.tiles.iter().map(|tile| tile.id + 1).max().unwrap_or(0),
+        }
+    }
+
+    /// The number of columns and rows of tiles that fit in the tilesheet
+    /// `image`, or `(0, 0)` if the tiles have no size.
+    fn grid_size(&self, image: &Image) -> (u32, u32) {
+        let (step_x, step_y) = (
+            self.tile_width + self.spacing,
+            self.tile_height + self.spacing,
+        );
+        if step_x == 0 || step_y == 0 {
+            return (0, 0);
+        }
+        (
+            (image.width + self.spacing).saturating_sub(2 * self.margin) / step_x,
+            (image.height + self.spacing).saturating_sub(2 * self.margin) / step_y,
+        )
+    }
+
+    /// The area of the tilesheet image holding the tile with global id `gid`,
+    /// as `(x, y, width, height)` in pixels. Flip flags are ignored. `None` if
+    /// `gid` is not in this tileset, or if the tileset has no tilesheet image.
+    pub fn tile_rect(&self, gid: u32) -> Option<(u32, u32, u32, u32)> {
+        if !self.contains_gid(gid) {
+            return None;
+        }
+        let image = self.image()?;
+        let columns = match self.columns {
+            Some(columns) => columns,
+            None => self.grid_size(image).0,
+        };
+        if columns == 0 {
+            return None;
+        }
+        let local_id = (gid & !ALL_FLIP_FLAGS) - self.first_gid;
+        let x = self.margin + (local_id % columns) * (self.tile_width + self.spacing);
+        let y = self.margin + (local_id / columns) * (self.tile_height + self.spacing);
+        Some((x, y, self.tile_width, self.tile_height))
+    }
+
+    /// The global id of `tile`, which must be one of the tiles of this
+    /// tileset.
+    pub fn gid_of(&self, tile: &Tile) -> u32 {
+        self.first_gid + tile.id
+    }
+
+    /// Whether `gid` refers to a tile of this tileset. Flip flags are ignored
+    /// and gid 0, the empty tile, never belongs to a tileset.
+    pub fn contains_gid(&self, gid: u32) -> bool {
+        let gid = gid & !ALL_FLIP_FLAGS;
+        !is_empty_gid(gid)
+            && gid >= self.first_gid
+            && gid - self.first_gid < self.tile_count_effective()
+    }
+
+    /// The frames of the animation of the tile with id `local_id`, as
+    /// `(gid, duration)` with the duration in milliseconds, or `None` if the
+    /// tile is not animated. A frame that refers to a tile past the end of
+    /// this tileset is clamped to its last tile, so that every gid belongs to
+    /// this tileset.
+    pub fn animation_gids(&self, local_id: u32) -> Option<Vec<(u32, u32)>> {
+        let tile = self.tiles.iter().find(|tile| tile.id == local_id)?;
+        let last_id = self.tile_count_effective().saturating_sub(1);
+        let frames = tile
+            .animation
+            .as_ref()?
+            .iter()
+            .map(|frame| (self.first_gid + frame.tile_id.min(last_id), frame.duration));
+        Some(frames.collect())
+    }
+
+    /// Picks the local id of a tile of this tileset at random, in proportion
+    /// to the `probability` of each tile. In a tilesheet, tiles without a
+    /// `<tile>` element have a probability of 1; in an image collection only
+    /// the tiles in `tiles` exist, whatever their ids. `None` if no tile can be
+    /// picked because all of them have a probability of 0.
+    #[cfg(feature = "rand")]
+    pub fn weighted_random_tile<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> Option<u32> {
+        use rand::distributions::{Distribution, WeightedIndex};
+
+        if self.is_collection() {
+            let weights = self.tiles.iter().map(|tile| tile.probability.max(0.0));
+            let distribution = WeightedIndex::new(weights).ok()?;
+            return Some(self.tiles[distribution.sample(rng)].id);
+        }
+        let probabilities: HashMap<u32, f32> = self
+            .tiles
+            .iter()
+            .map(|tile| (tile.id, tile.probability.max(0.0)))
+            .collect();
+        let weights = (0..self.tile_count_effective())
+            .map(|id| probabilities.get(&id).cloned().unwrap_or(1.0));
+        let distribution = WeightedIndex::new(weights).ok()?;
+        Some(distribution.sample(rng) as u32)
+    }
+
+    /// Whether this is a collection of images rather than a tilesheet: it has
+    /// no image of its own, and each tile has its own image in `Tile::images`.
+    pub fn is_collection(&self) -> bool {
+        self.images.is_empty() && self.tiles.iter().any(|tile| !tile.images.is_empty())
+    }
+
+    /// The tilesheet image of this tileset, which is the first of `images`.
+    pub fn image(&self) -> Option<&Image> {
+        self.images.first()
+    }
+
+    /// The path of the tilesheet image. Image paths are relative to the file
+    /// they are written in, so for an external tileset this is resolved
+    /// against `source`, and for an embedded one against `map_path`.
+    pub fn image_source_path(&self, map_path: &Path) -> Option<PathBuf> {
+        let image = self.image()?;
+        let base = self.source.as_deref().unwrap_or(map_path);
+        Some(resolve_relative(base, &image.source))
+    }
+
+    /// The `source` attribute to write for an external tileset, or `None` if
+    /// the tileset is embedded.
+    pub(crate) fn written_source(&self) -> Option<String> {
+        self.source_attribute.clone().or_else(|| {
+            self.source
+                .as_ref()
+                .map(|source| source.to_string_lossy().into_owned())
         })
     }
 
@@ -168,8 +504,76 @@ impl Tileset {
     ///
     /// External tilesets do not have a firstgid attribute.  That lives in the
     /// map. You must pass in `first_gid`.  If you do not need to use gids for anything,
-    /// passing in 1 will work fine.
+    /// passing in 1 will work fine. A tileset parsed on its own has a
+    /// `tileset_index` of 0.
+    ///
+    /// The location of the tileset is unknown, so tiles with collision objects
+    /// that use a template can't be parsed; see `parse_with_path`.
     pub fn parse<R: Read>(reader: R, first_gid: u32) -> Result<Tileset, Error> {
-        Tileset::new_external(reader, first_gid)
+        Tileset::parse_impl(reader, first_gid, None)
+    }
+
+    /// Like `parse`, for a tileset held in a string, such as one embedded with
+    /// `include_str!`.
+    pub fn parse_str(contents: &str, first_gid: u32) -> Result<Tileset, Error> {
+        Tileset::parse(contents.as_bytes(), first_gid)
+    }
+
+    /// Like `parse`, for a tileset read from the `.tsx` file at `path`. The
+    /// path is kept as `source`, so that `image_source_path` resolves the
+    /// tilesheet image relative to the tileset file. Templates of collision
+    /// objects are loaded relative to it too.
+    pub fn parse_with_path<R: Read>(
+        reader: R,
+        first_gid: u32,
+        path: &Path,
+    ) -> Result<Tileset, Error> {
+        let mut tileset = Tileset::parse_impl(reader, first_gid, Some(path))?;
+        tileset.source = Some(path.to_path_buf());
+        Ok(tileset)
     }
+
+    fn parse_impl<R: Read>(
+        reader: R,
+        first_gid: u32,
+        path: Option<&Path>,
+    ) -> Result<Tileset, Error> {
+        let mut parser = EventReader::new(reader);
+        let mut loader = default_loader(path);
+        let mut templates = Templates {
+            map_path: path,
+            loader: &mut *loader,
+            tilesets: None,
+        };
+        Tileset::new_external(&mut parser, first_gid, 0, &mut templates, false)
+            .map_err(|e| e.at_position(parser.position()))
+    }
+}
+
+/// A terrain of a tileset made with a Tiled version before 1.5.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Terrain {
+    pub name: String,
+    /// The id of the tile representing this terrain, or -1 if there is none.
+    pub tile: i32,
+}
+
+fn parse_terrain_types<R: Read>(parser: &mut EventReader<R>) -> Result<Vec<Terrain>, Error> {
+    let mut terrain_types = Vec::new();
+    parse_tag!(parser, "terraintypes", {
+        "terrain" => |attrs: Vec<OwnedAttribute>| {
+            let ((), (name, tile)) = get_attrs!(
+                attrs,
+                optionals: [],
+                required: [
+                    ("name", name, |v| Some(v)),
+                    ("tile", tile, |v:String| v.parse().ok()),
+                ],
+                Error::MalformedAttributes("terrain must have a name and tile".to_string())
+            );
+            terrain_types.push(Terrain { name, tile });
+            Ok(())
+        },
+    });
+    Ok(terrain_types)
 }
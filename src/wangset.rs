@@ -0,0 +1,204 @@
+use crate::{
+    color::Color,
+    error::Error,
+    get_attrs,
+    macros::parse_float_attr,
+    parse_tag,
+    property::{parse_properties_impl, Properties},
+};
+use std::collections::HashMap;
+use std::io::Read;
+use xml::{attribute::OwnedAttribute, EventReader};
+
+/// Parses the `<wangset>`s inside `<wangsets>`.
+pub(crate) fn parse_wang_sets<R: Read>(
+    parser: &mut EventReader<R>,
+    strict_properties: bool,
+) -> Result<Vec<WangSet>, Error> {
+    let mut wang_sets = Vec::new();
+    parse_tag!(parser, "wangsets", {
+        "wangset" => |attrs| {
+            wang_sets.push(WangSet::parse(parser, attrs, strict_properties)?);
+            Ok(())
+        },
+    });
+    Ok(wang_sets)
+}
+
+/// A set of rules Tiled uses to pick tiles when painting terrain.
+#[derive(Debug, PartialEq, Clone)]
+pub struct WangSet {
+    pub name: String,
+    /// The class of this wang set, used by Tiled for custom types.
+    pub class: Option<String>,
+    /// Whether the set matches tiles by their `corner`s, `edge`s or both
+    /// (`mixed`). Files saved before Tiled 1.5 don't have it.
+    pub wang_type: Option<String>,
+    /// The id of the tile representing this set, if any.
+    pub tile: Option<u32>,
+    pub colors: Vec<WangColor>,
+    pub tiles: Vec<WangTile>,
+    pub properties: Properties,
+}
+
+impl WangSet {
+    pub fn new<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+    ) -> Result<WangSet, Error> {
+        WangSet::parse(parser, attrs, false)
+    }
+
+    pub(crate) fn parse<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+        strict_properties: bool,
+    ) -> Result<WangSet, Error> {
+        let ((class, wang_type, tile), name) = get_attrs!(
+            attrs,
+            optionals: [
+                ("class", class, |v| Some(v)),
+                ("type", wang_type, |v| Some(v)),
+                ("tile", tile, |v:String| v.parse().ok()),
+            ],
+            required: [
+                ("name", name, |v| Some(v)),
+            ],
+            Error::MalformedAttributes("wang sets must have a name".to_string())
+        );
+        let mut colors = Vec::new();
+        let mut tiles = Vec::new();
+        let mut properties = HashMap::new();
+        parse_tag!(parser, "wangset", {
+            "wangcolor" => |attrs| {
+                colors.push(WangColor::parse(parser, attrs, strict_properties)?);
+                Ok(())
+            },
+            "wangtile" => |attrs| {
+                tiles.push(WangTile::new(attrs)?);
+                Ok(())
+            },
+            "properties" => |_| {
+                properties = parse_properties_impl(parser, strict_properties)?;
+                Ok(())
+            },
+        });
+        Ok(WangSet {
+            name,
+            class,
+            wang_type,
+            tile,
+            colors,
+            tiles,
+            properties,
+        })
+    }
+}
+
+/// One of the terrains of a `WangSet`. Wang ids refer to colors by their
+/// index in `WangSet::colors` plus one, 0 meaning no color.
+#[derive(Debug, PartialEq, Clone)]
+pub struct WangColor {
+    pub name: String,
+    /// The class of this color, used by Tiled for custom types.
+    pub class: Option<String>,
+    pub color: Color,
+    /// The id of the tile representing this color, if any.
+    pub tile: Option<u32>,
+    pub probability: f32,
+    pub properties: Properties,
+}
+
+impl WangColor {
+    pub fn new<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+    ) -> Result<WangColor, Error> {
+        WangColor::parse(parser, attrs, false)
+    }
+
+    pub(crate) fn parse<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+        strict_properties: bool,
+    ) -> Result<WangColor, Error> {
+        let ((class, tile, probability), (name, color)) = get_attrs!(
+            attrs,
+            optionals: [
+                ("class", class, |v| Some(v)),
+                ("tile", tile, |v:String| v.parse().ok()),
+                ("probability", probability, |v| Some(v)),
+            ],
+            required: [
+                ("name", name, |v| Some(v)),
+                ("color", color, |v:String| v.parse().ok()),
+            ],
+            Error::MalformedAttributes("wang colors must have a name and color".to_string())
+        );
+        let probability = match probability {
+            Some(v) => parse_float_attr("probability", &v)?,
+            None => 1.0,
+        };
+        let mut properties = HashMap::new();
+        parse_tag!(parser, "wangcolor", {
+            "properties" => |_| {
+                properties = parse_properties_impl(parser, strict_properties)?;
+                Ok(())
+            },
+        });
+        Ok(WangColor {
+            name,
+            class,
+            color,
+            tile,
+            probability,
+            properties,
+        })
+    }
+}
+
+/// The colors of one tile of a `WangSet`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct WangTile {
+    pub tile_id: u32,
+    /// The color at the top, top right, right, bottom right, bottom, bottom
+    /// left, left and top left of the tile, in that order.
+    pub wang_id: [u8; 8],
+}
+
+impl WangTile {
+    pub fn new(attrs: Vec<OwnedAttribute>) -> Result<WangTile, Error> {
+        let ((), (tile_id, wang_id)) = get_attrs!(
+            attrs,
+            optionals: [],
+            required: [
+                ("tileid", tile_id, |v:String| v.parse().ok()),
+                ("wangid", wang_id, |v:String| parse_wang_id(&v)),
+            ],
+            Error::MalformedAttributes("wang tiles must have a tileid and a valid wangid".to_string())
+        );
+        Ok(WangTile { tile_id, wang_id })
+    }
+}
+
+/// Parses a wang id, which is a list of 8 comma separated color indices.
+/// Files from before Tiled 1.5 store it as a hexadecimal number with one
+/// color index per digit, starting from the least significant one.
+fn parse_wang_id(v: &str) -> Option<[u8; 8]> {
+    let mut wang_id = [0; 8];
+    if let Some(hex) = v.strip_prefix("0x") {
+        let id = u32::from_str_radix(hex, 16).ok()?;
+        for (i, color) in wang_id.iter_mut().enumerate() {
+            *color = ((id >> (i * 4)) & 0xf) as u8;
+        }
+        return Some(wang_id);
+    }
+    let colors: Vec<&str> = v.split(',').collect();
+    if colors.len() != wang_id.len() {
+        return None;
+    }
+    for (color, v) in wang_id.iter_mut().zip(colors) {
+        *color = v.trim().parse().ok()?;
+    }
+    Some(wang_id)
+}
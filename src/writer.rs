@@ -0,0 +1,649 @@
+use crate::{
+    color::Color,
+    error::Error,
+    image::Image,
+    layer::{Group, ImageLayer, Layer, LayerKind},
+    map::{Map, RenderOrder, StaggerAxis},
+    object::{DrawOrder, HorizontalAlignment, Object, ObjectGroup, ObjectShape, VerticalAlignment},
+    property::{Properties, PropertyValue},
+    tile::Tile,
+    tileset::Tileset,
+    wangset::WangSet,
+};
+use std::{collections::HashMap, io::Write};
+use xml::writer::{EmitterConfig, EventWriter, XmlEvent};
+
+impl Map {
+    /// Writes this map as a TMX file. Tile layer data is always written CSV
+    /// encoded. External tilesets are written as references with the `source`
+    /// attribute they were read with, or their `source` path if they have
+    /// none; all other tilesets are embedded in the map.
+    ///
+    /// Objects that were loaded from a template are written with the template
+    /// merged into them and without a `template` attribute. The written map
+    /// no longer refers to the `.tx` file, so later changes to the template
+    /// don't reach these objects.
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), Error> {
+        let mut writer = TmxWriter {
+            writer: EventWriter::new_with_config(writer, EmitterConfig::new().perform_indent(true)),
+        };
+        writer.map(self)
+    }
+}
+
+struct TmxWriter<W: Write> {
+    writer: EventWriter<W>,
+}
+
+impl<W: Write> TmxWriter<W> {
+    fn start(&mut self, name: &str, attrs: &[(&str, String)]) -> Result<(), Error> {
+        let mut element = XmlEvent::start_element(name);
+        for &(key, ref value) in attrs.iter() {
+            element = element.attr(key, value);
+        }
+        Ok(self.writer.write(element)?)
+    }
+
+    fn end(&mut self) -> Result<(), Error> {
+        Ok(self.writer.write(XmlEvent::end_element())?)
+    }
+
+    fn empty(&mut self, name: &str, attrs: &[(&str, String)]) -> Result<(), Error> {
+        self.start(name, attrs)?;
+        self.end()
+    }
+
+    fn characters(&mut self, data: &str) -> Result<(), Error> {
+        Ok(self.writer.write(XmlEvent::characters(data))?)
+    }
+
+    fn map(&mut self, map: &Map) -> Result<(), Error> {
+        let mut attrs = vec![("version", map.version.clone())];
+        push_opt(&mut attrs, "class", map.class.clone());
+        attrs.push(("orientation", map.orientation.to_string()));
+        attrs.push(("renderorder", render_order(map.render_order).to_string()));
+        attrs.push(("width", map.width.to_string()));
+        attrs.push(("height", map.height.to_string()));
+        attrs.push(("tilewidth", map.tile_width.to_string()));
+        attrs.push(("tileheight", map.tile_height.to_string()));
+        push_opt(&mut attrs, "hexsidelength", map.hex_side_length);
+        push_opt(
+            &mut attrs,
+            "staggeraxis",
+            map.stagger_axis.map(stagger_axis),
+        );
+        if map.infinite {
+            attrs.push(("infinite", bool_attr(map.infinite)));
+        }
+        push_opt(
+            &mut attrs,
+            "backgroundcolor",
+            map.background_color.map(color),
+        );
+        push_opt(&mut attrs, "nextlayerid", map.next_layer_id);
+        push_opt(&mut attrs, "nextobjectid", map.next_object_id);
+        push_extra(&mut attrs, &map.extra);
+        self.start("map", &attrs)?;
+        if let Some((width, height)) = map.chunk_size {
+            self.start("editorsettings", &[])?;
+            self.empty(
+                "chunksize",
+                &[("width", width.to_string()), ("height", height.to_string())],
+            )?;
+            self.end()?;
+        }
+        self.properties(&map.properties)?;
+        for tileset in map.tilesets.iter() {
+            self.tileset(tileset)?;
+        }
+        self.layers(
+            &map.layers,
+            &map.image_layers,
+            &map.object_groups,
+            &map.groups,
+        )?;
+        self.end()
+    }
+
+    fn properties(&mut self, properties: &Properties) -> Result<(), Error> {
+        if properties.is_empty() {
+            return Ok(());
+        }
+        let mut properties: Vec<_> = properties.iter().collect();
+        properties.sort_by(|a, b| a.0.cmp(b.0));
+        self.start("properties", &[])?;
+        for (name, value) in properties {
+            let (property_type, value) = match *value {
+                PropertyValue::BoolValue(v) => ("bool", v.to_string()),
+                PropertyValue::FloatValue(v) => ("float", v.to_string()),
+                PropertyValue::IntValue(v) => ("int", v.to_string()),
+                PropertyValue::ColorValue(v) => ("color", format!("#{:08x}", v)),
+                PropertyValue::StringValue(ref v) => ("string", v.clone()),
+                PropertyValue::FileValue(ref v) => ("file", v.to_string_lossy().into_owned()),
+                PropertyValue::ObjectValue(v) => ("object", v.to_string()),
+            };
+            self.empty(
+                "property",
+                &[
+                    ("name", name.clone()),
+                    ("type", property_type.to_string()),
+                    ("value", value),
+                ],
+            )?;
+        }
+        self.end()
+    }
+
+    fn tileset(&mut self, tileset: &Tileset) -> Result<(), Error> {
+        if let Some(source) = tileset.written_source() {
+            return self.empty(
+                "tileset",
+                &[
+                    ("firstgid", tileset.first_gid.to_string()),
+                    ("source", source),
+                ],
+            );
+        }
+        let mut attrs = vec![
+            ("firstgid", tileset.first_gid.to_string()),
+            ("name", tileset.name.clone()),
+        ];
+        push_opt(&mut attrs, "class", tileset.class.clone());
+        attrs.push(("tilewidth", tileset.tile_width.to_string()));
+        attrs.push(("tileheight", tileset.tile_height.to_string()));
+        if tileset.spacing != 0 {
+            attrs.push(("spacing", tileset.spacing.to_string()));
+        }
+        if tileset.margin != 0 {
+            attrs.push(("margin", tileset.margin.to_string()));
+        }
+        push_opt(&mut attrs, "tilecount", tileset.tile_count);
+        push_opt(&mut attrs, "columns", tileset.columns);
+        push_extra(&mut attrs, &tileset.extra);
+        self.start("tileset", &attrs)?;
+        for image in tileset.images.iter() {
+            self.image(image)?;
+        }
+        if !tileset.terrain_types.is_empty() {
+            self.start("terraintypes", &[])?;
+            for terrain in tileset.terrain_types.iter() {
+                self.empty(
+                    "terrain",
+                    &[
+                        ("name", terrain.name.clone()),
+                        ("tile", terrain.tile.to_string()),
+                    ],
+                )?;
+            }
+            self.end()?;
+        }
+        for tile in tileset.tiles.iter() {
+            self.tile(tile)?;
+        }
+        if !tileset.wang_sets.is_empty() {
+            self.start("wangsets", &[])?;
+            for wang_set in tileset.wang_sets.iter() {
+                self.wang_set(wang_set)?;
+            }
+            self.end()?;
+        }
+        self.end()
+    }
+
+    fn wang_set(&mut self, wang_set: &WangSet) -> Result<(), Error> {
+        let mut attrs = vec![("name", wang_set.name.clone())];
+        push_opt(&mut attrs, "class", wang_set.class.clone());
+        push_opt(&mut attrs, "type", wang_set.wang_type.clone());
+        attrs.push(("tile", tile_attr(wang_set.tile)));
+        self.start("wangset", &attrs)?;
+        self.properties(&wang_set.properties)?;
+        for wang_color in wang_set.colors.iter() {
+            let mut attrs = vec![("name", wang_color.name.clone())];
+            push_opt(&mut attrs, "class", wang_color.class.clone());
+            attrs.push(("color", color(wang_color.color)));
+            attrs.push(("tile", tile_attr(wang_color.tile)));
+            attrs.push(("probability", wang_color.probability.to_string()));
+            self.start("wangcolor", &attrs)?;
+            self.properties(&wang_color.properties)?;
+            self.end()?;
+        }
+        for wang_tile in wang_set.tiles.iter() {
+            self.empty(
+                "wangtile",
+                &[
+                    ("tileid", wang_tile.tile_id.to_string()),
+                    ("wangid", wang_id(&wang_tile.wang_id)),
+                ],
+            )?;
+        }
+        self.end()
+    }
+
+    fn image(&mut self, image: &Image) -> Result<(), Error> {
+        let mut attrs = vec![
+            ("source", image.source.clone()),
+            ("width", image.width.to_string()),
+            ("height", image.height.to_string()),
+        ];
+        // Tiled writes the transparent color without a leading `#`.
+        push_opt(
+            &mut attrs,
+            "trans",
+            image.transparent_color.map(|c| color(c)[1..].to_string()),
+        );
+        self.empty("image", &attrs)
+    }
+
+    fn tile(&mut self, tile: &Tile) -> Result<(), Error> {
+        let mut attrs = vec![("id", tile.id.to_string())];
+        push_opt(&mut attrs, "type", tile.tile_type.clone());
+        push_opt(&mut attrs, "terrain", tile.terrain.map(terrain_attr));
+        if tile.probability != 1.0 {
+            attrs.push(("probability", tile.probability.to_string()));
+        }
+        self.start("tile", &attrs)?;
+        self.properties(&tile.properties)?;
+        for image in tile.images.iter() {
+            self.image(image)?;
+        }
+        if let Some(ref object_group) = tile.objectgroup {
+            self.object_group(object_group)?;
+        }
+        if let Some(ref animation) = tile.animation {
+            self.start("animation", &[])?;
+            for frame in animation.iter() {
+                self.empty(
+                    "frame",
+                    &[
+                        ("tileid", frame.tile_id.to_string()),
+                        ("duration", frame.duration.to_string()),
+                    ],
+                )?;
+            }
+            self.end()?;
+        }
+        self.end()
+    }
+
+    fn layers(
+        &mut self,
+        layers: &[Layer],
+        image_layers: &[ImageLayer],
+        object_groups: &[ObjectGroup],
+        groups: &[Group],
+    ) -> Result<(), Error> {
+        for layer in LayerKind::in_order(layers, image_layers, object_groups, groups) {
+            match layer {
+                LayerKind::Tile(layer) => self.layer(layer)?,
+                LayerKind::Image(layer) => self.image_layer(layer)?,
+                LayerKind::Objects(layer) => self.object_group(layer)?,
+                LayerKind::Group(layer) => self.group(layer)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn layer(&mut self, layer: &Layer) -> Result<(), Error> {
+        let mut attrs = Vec::new();
+        push_opt(&mut attrs, "id", layer.id);
+        attrs.push(("name", layer.name.clone()));
+        push_opt(&mut attrs, "class", layer.class.clone());
+        let (_, _, width, height) = layer_bounds(layer);
+        attrs.push(("width", width.to_string()));
+        attrs.push(("height", height.to_string()));
+        push_layer_attrs(
+            &mut attrs,
+            layer.opacity,
+            layer.visible,
+            layer.locked,
+            layer.tint_color,
+            (layer.offset_x, layer.offset_y),
+        );
+        push_extra(&mut attrs, &layer.extra);
+        self.start("layer", &attrs)?;
+        self.properties(&layer.properties)?;
+        self.start("data", &[("encoding", "csv".to_string())])?;
+        if layer.is_chunked() {
+            for chunk in layer.chunks.iter() {
+                let attrs = [
+                    ("x", chunk.x.to_string()),
+                    ("y", chunk.y.to_string()),
+                    ("width", chunk.width.to_string()),
+                    ("height", chunk.height.to_string()),
+                ];
+                self.start("chunk", &attrs)?;
+                self.characters(&csv(&chunk.tiles))?;
+                self.end()?;
+            }
+        } else {
+            self.characters(&csv(&layer.tiles))?;
+        }
+        self.end()?;
+        self.end()
+    }
+
+    fn image_layer(&mut self, layer: &ImageLayer) -> Result<(), Error> {
+        let mut attrs = Vec::new();
+        push_opt(&mut attrs, "id", layer.id);
+        attrs.push(("name", layer.name.clone()));
+        push_opt(&mut attrs, "class", layer.class.clone());
+        push_layer_attrs(
+            &mut attrs,
+            layer.opacity,
+            layer.visible,
+            layer.locked,
+            layer.tint_color,
+            (layer.offset_x, layer.offset_y),
+        );
+        push_extra(&mut attrs, &layer.extra);
+        self.start("imagelayer", &attrs)?;
+        if let Some(ref image) = layer.image {
+            self.image(image)?;
+        }
+        self.properties(&layer.properties)?;
+        self.end()
+    }
+
+    fn object_group(&mut self, object_group: &ObjectGroup) -> Result<(), Error> {
+        let mut attrs = Vec::new();
+        push_opt(&mut attrs, "id", object_group.id);
+        attrs.push(("name", object_group.name.clone()));
+        push_opt(&mut attrs, "class", object_group.class.clone());
+        push_opt(&mut attrs, "color", object_group.color.map(color));
+        if object_group.draw_order != DrawOrder::TopDown {
+            attrs.push(("draworder", draw_order(object_group.draw_order).to_string()));
+        }
+        push_layer_attrs(
+            &mut attrs,
+            object_group.opacity,
+            object_group.visible,
+            object_group.locked,
+            None,
+            (0.0, 0.0),
+        );
+        push_extra(&mut attrs, &object_group.extra);
+        self.start("objectgroup", &attrs)?;
+        for object in object_group.objects.iter() {
+            self.object(object)?;
+        }
+        self.end()
+    }
+
+    fn object(&mut self, object: &Object) -> Result<(), Error> {
+        let mut attrs = vec![("id", object.id.to_string())];
+        if object.gid != 0 {
+            attrs.push(("gid", object.gid.to_string()));
+        }
+        if !object.name.is_empty() {
+            attrs.push(("name", object.name.clone()));
+        }
+        if !object.obj_type.is_empty() {
+            attrs.push(("type", object.obj_type.clone()));
+        }
+        attrs.push(("x", object.x.to_string()));
+        attrs.push(("y", object.y.to_string()));
+        match object.shape {
+            ObjectShape::Rect { width, height }
+            | ObjectShape::Ellipse { width, height }
+            | ObjectShape::Text { width, height, .. } => {
+                if width != 0.0 {
+                    attrs.push(("width", width.to_string()));
+                }
+                if height != 0.0 {
+                    attrs.push(("height", height.to_string()));
+                }
+            }
+            _ => {}
+        }
+        if object.rotation != 0.0 {
+            attrs.push(("rotation", object.rotation.to_string()));
+        }
+        if !object.visible {
+            attrs.push(("visible", "0".to_string()));
+        }
+        push_extra(&mut attrs, &object.extra);
+        self.start("object", &attrs)?;
+        self.properties(&object.properties)?;
+        match object.shape {
+            ObjectShape::Rect { .. } => {}
+            ObjectShape::Ellipse { .. } => self.empty("ellipse", &[])?,
+            ObjectShape::Point => self.empty("point", &[])?,
+            ObjectShape::Polyline { ref points } => {
+                self.empty("polyline", &[("points", points_attr(points))])?
+            }
+            ObjectShape::Polygon { ref points } => {
+                self.empty("polygon", &[("points", points_attr(points))])?
+            }
+            ObjectShape::Text { ref text, .. } => {
+                let mut attrs = Vec::new();
+                if text.font_family != "sans-serif" {
+                    attrs.push(("fontfamily", text.font_family.clone()));
+                }
+                if text.pixel_size != 16 {
+                    attrs.push(("pixelsize", text.pixel_size.to_string()));
+                }
+                let black = Color {
+                    red: 0,
+                    green: 0,
+                    blue: 0,
+                    alpha: 255,
+                };
+                if text.color != black {
+                    attrs.push(("color", color(text.color)));
+                }
+                let flags = [
+                    ("wrap", text.wrap, false),
+                    ("bold", text.bold, false),
+                    ("italic", text.italic, false),
+                    ("underline", text.underline, false),
+                    ("strikeout", text.strikeout, false),
+                    ("kerning", text.kerning, true),
+                ];
+                for &(name, value, default) in flags.iter() {
+                    if value != default {
+                        attrs.push((name, bool_attr(value)));
+                    }
+                }
+                if text.halign != HorizontalAlignment::Left {
+                    attrs.push(("halign", halign(text.halign).to_string()));
+                }
+                if text.valign != VerticalAlignment::Top {
+                    attrs.push(("valign", valign(text.valign).to_string()));
+                }
+                self.start("text", &attrs)?;
+                self.characters(&text.contents)?;
+                self.end()?;
+            }
+        }
+        self.end()
+    }
+
+    fn group(&mut self, group: &Group) -> Result<(), Error> {
+        let mut attrs = Vec::new();
+        push_opt(&mut attrs, "id", group.id);
+        attrs.push(("name", group.name.clone()));
+        push_opt(&mut attrs, "class", group.class.clone());
+        push_layer_attrs(
+            &mut attrs,
+            group.opacity,
+            group.visible,
+            group.locked,
+            group.tint_color,
+            (group.offset_x, group.offset_y),
+        );
+        push_extra(&mut attrs, &group.extra);
+        self.start("group", &attrs)?;
+        self.properties(&group.properties)?;
+        self.layers(
+            &group.layers,
+            &group.image_layers,
+            &group.object_groups,
+            &group.groups,
+        )?;
+        self.end()
+    }
+}
+
+fn push_opt<T: ToString>(attrs: &mut Vec<(&str, String)>, name: &'static str, value: Option<T>) {
+    if let Some(value) = value {
+        attrs.push((name, value.to_string()));
+    }
+}
+
+/// Adds the attributes in `extra` that aren't already in `attrs`, in name
+/// order. Attributes this crate writes itself, such as the size of a layer,
+/// may also be in `extra` when the element was parsed, and are skipped.
+fn push_extra<'a>(attrs: &mut Vec<(&'a str, String)>, extra: &'a HashMap<String, String>) {
+    let mut extra: Vec<_> = extra
+        .iter()
+        .filter(|&(name, _)| !attrs.iter().any(|&(written, _)| written == name))
+        .collect();
+    extra.sort_by(|a, b| a.0.cmp(b.0));
+    attrs.extend(
+        extra
+            .into_iter()
+            .map(|(name, value)| (name.as_str(), value.clone())),
+    );
+}
+
+/// The attributes shared by all kinds of layer, each only written when it
+/// differs from its default.
+fn push_layer_attrs(
+    attrs: &mut Vec<(&str, String)>,
+    opacity: f32,
+    visible: bool,
+    locked: bool,
+    tint_color: Option<Color>,
+    (offset_x, offset_y): (f32, f32),
+) {
+    if opacity != 1.0 {
+        attrs.push(("opacity", opacity.to_string()));
+    }
+    if !visible {
+        attrs.push(("visible", bool_attr(visible)));
+    }
+    if locked {
+        attrs.push(("locked", bool_attr(locked)));
+    }
+    push_opt(attrs, "tintcolor", tint_color.map(color));
+    if offset_x != 0.0 {
+        attrs.push(("offsetx", offset_x.to_string()));
+    }
+    if offset_y != 0.0 {
+        attrs.push(("offsety", offset_y.to_string()));
+    }
+}
+
+/// The position and size of the area covered by the tiles of `layer`, in
+/// tiles. Only chunked layers can start anywhere but at the origin.
+pub(crate) fn layer_bounds(layer: &Layer) -> (i32, i32, u32, u32) {
+    if !layer.is_chunked() {
+        let width = layer.tiles.first().map_or(0, |row| row.len());
+        return (0, 0, width as u32, layer.tiles.len() as u32);
+    }
+    let chunks = &layer.chunks;
+    let x = chunks.iter().map(|c| c.x).min().unwrap_or(0);
+    let y = chunks.iter().map(|c| c.y).min().unwrap_or(0);
+    let right = chunks
+        .iter()
+        .map(|c| c.x + c.width as i32)
+        .max()
+        .unwrap_or(0);
+    let bottom = chunks
+        .iter()
+        .map(|c| c.y + c.height as i32)
+        .max()
+        .unwrap_or(0);
+    (x, y, (right - x) as u32, (bottom - y) as u32)
+}
+
+/// Tile data in the CSV encoding, one row per line.
+fn csv(tiles: &[Vec<u32>]) -> String {
+    let rows: Vec<String> = tiles
+        .iter()
+        .map(|row| {
+            let row: Vec<String> = row.iter().map(|gid| gid.to_string()).collect();
+            row.join(",")
+        })
+        .collect();
+    format!("\n{}\n", rows.join(",\n"))
+}
+
+fn bool_attr(value: bool) -> String {
+    if value { "1" } else { "0" }.to_string()
+}
+
+/// Formats a color as `#RRGGBB`, or as `#AARRGGBB` if it is not opaque.
+pub(crate) fn color(color: Color) -> String {
+    if color.alpha == 255 {
+        format!("#{:02x}{:02x}{:02x}", color.red, color.green, color.blue)
+    } else {
+        format!(
+            "#{:02x}{:02x}{:02x}{:02x}",
+            color.alpha, color.red, color.green, color.blue
+        )
+    }
+}
+
+/// Tiled writes -1 when no tile represents a wang set or color.
+fn tile_attr(tile: Option<u32>) -> String {
+    tile.map_or("-1".to_string(), |tile| tile.to_string())
+}
+
+fn wang_id(wang_id: &[u8]) -> String {
+    let colors: Vec<String> = wang_id.iter().map(|color| color.to_string()).collect();
+    colors.join(",")
+}
+
+fn terrain_attr(terrain: [Option<u32>; 4]) -> String {
+    let corners: Vec<String> = terrain
+        .iter()
+        .map(|corner| corner.map_or(String::new(), |t| t.to_string()))
+        .collect();
+    corners.join(",")
+}
+
+fn points_attr(points: &[(f32, f32)]) -> String {
+    let points: Vec<String> = points.iter().map(|(x, y)| format!("{},{}", x, y)).collect();
+    points.join(" ")
+}
+
+pub(crate) fn render_order(render_order: RenderOrder) -> &'static str {
+    match render_order {
+        RenderOrder::RightDown => "right-down",
+        RenderOrder::RightUp => "right-up",
+        RenderOrder::LeftDown => "left-down",
+        RenderOrder::LeftUp => "left-up",
+    }
+}
+
+pub(crate) fn stagger_axis(stagger_axis: StaggerAxis) -> &'static str {
+    match stagger_axis {
+        StaggerAxis::X => "x",
+        StaggerAxis::Y => "y",
+    }
+}
+
+pub(crate) fn draw_order(draw_order: DrawOrder) -> &'static str {
+    match draw_order {
+        DrawOrder::Index => "index",
+        DrawOrder::TopDown => "topdown",
+    }
+}
+
+pub(crate) fn halign(halign: HorizontalAlignment) -> &'static str {
+    match halign {
+        HorizontalAlignment::Left => "left",
+        HorizontalAlignment::Center => "center",
+        HorizontalAlignment::Right => "right",
+        HorizontalAlignment::Justify => "justify",
+    }
+}
+
+pub(crate) fn valign(valign: VerticalAlignment) -> &'static str {
+    match valign {
+        VerticalAlignment::Top => "top",
+        VerticalAlignment::Center => "center",
+        VerticalAlignment::Bottom => "bottom",
+    }
+}
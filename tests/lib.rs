@@ -1,8 +1,15 @@
 extern crate tiled;
 
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::path::Path;
-use tiled::{Map, PropertyValue, Error, Tileset};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tiled::object::{DrawOrder, HorizontalAlignment, Object, ObjectShape, VerticalAlignment};
+use tiled::{
+    is_empty_gid, parse_properties, parse_properties_strict, Chunk, Color, Error, Image, Layer,
+    LayerKind, LayerSummary, Map, MapBuilder, MapReader, Orientation, ParseOptions, ParsedLayer,
+    PropertiesExt, PropertyValue, RenderOrder, StaggerAxis, Tileset, WangTile,
+};
 
 fn read_from_file(p: &Path) -> Result<Map, Error> {
     let file = File::open(p).unwrap();
@@ -24,10 +31,89 @@ fn test_gzip_and_zlib_encoded_and_raw_are_the_same() {
     assert_eq!(z, c);
 }
 
+#[test]
+fn test_zlib_without_header() {
+    let z = read_from_file(&Path::new("assets/tiled_base64_zlib.tmx")).unwrap();
+    let d = read_from_file(Path::new("assets/tiled_base64_zlib_headerless.tmx")).unwrap();
+    assert_eq!(z, d);
+}
+
+#[test]
+fn test_large_zlib_layer() {
+    let r = read_from_file(Path::new("assets/tiled_large_zlib.tmx")).unwrap();
+    let tiles = &r.layers[0].tiles;
+    assert_eq!(tiles.len(), 256);
+    assert!(tiles.iter().all(|row| row.len() == 256));
+    assert_eq!(tiles[0][0], 1);
+    assert_eq!(tiles[255][255], (255 + 255) % 84 + 1);
+}
+
+#[test]
+fn test_infinite_map_with_flat_data() {
+    let r = read_from_file(Path::new("assets/tiled_infinite_flat.tmx")).unwrap();
+    assert!(r.infinite);
+    let layer = &r.layers[0];
+    assert!(!layer.is_chunked());
+    assert_eq!(layer.tiles, vec![vec![1, 2, 3], vec![4, 5, 6]]);
+}
+
+#[test]
+fn test_csv_non_numeric_gid() {
+    let tmx = include_str!("../assets/tiled_infinite_flat.tmx").replace("4,5,6", "4,x,6");
+    match Map::parse_str(&tmx) {
+        Err(Error::MalformedAttributes(e)) => assert!(e.contains("\"x\""), "{}", e),
+        r => panic!("expected a malformed gid error, got {:?}", r),
+    }
+    // Cells of chunks are checked in the same way.
+    let tmx = include_str!("../assets/tiled_infinite.tmx").replace("7,0", "7,-1");
+    assert!(matches!(
+        Map::parse_str(&tmx),
+        Err(Error::MalformedAttributes(_))
+    ));
+}
+
+#[test]
+fn test_chunked_layers() {
+    let r = read_from_file(Path::new("assets/tiled_infinite.tmx")).unwrap();
+    assert!(r.infinite);
+    let layer = &r.layers[0];
+    assert!(layer.is_chunked());
+    assert!(layer.tiles.is_empty());
+    assert_eq!(
+        layer.chunks,
+        vec![
+            Chunk {
+                x: -2,
+                y: 0,
+                width: 2,
+                height: 2,
+                tiles: vec![vec![1, 2], vec![3, 4]],
+            },
+            Chunk {
+                x: 0,
+                y: 0,
+                width: 2,
+                height: 2,
+                tiles: vec![vec![5, 6], vec![7, 0]],
+            },
+        ]
+    );
+    let compressed = &r.layers[1].chunks[0];
+    assert_eq!((compressed.x, compressed.y), (0, -2));
+    assert_eq!(compressed.tiles, vec![vec![1, 2], vec![3, 4]]);
+}
+
 #[test]
 fn test_external_tileset() {
     let r = read_from_file(&Path::new("assets/tiled_base64.tmx")).unwrap();
-    let e = read_from_file_with_path(&Path::new("assets/tiled_base64_external.tmx")).unwrap();
+    let mut e = read_from_file_with_path(Path::new("assets/tiled_base64_external.tmx")).unwrap();
+    // The embedded tileset has no source file, otherwise the maps are identical.
+    assert_eq!(
+        e.tilesets[0].source_attribute.as_deref(),
+        Some("tilesheet.tsx")
+    );
+    e.tilesets[0].source = None;
+    e.tilesets[0].source_attribute = None;
     assert_eq!(r, e);
 }
 
@@ -66,7 +152,7 @@ fn test_image_layers() {
 
 #[test]
 fn test_tile_property() {
-    let r = read_from_file(&Path::new("assets/tiled_base64.tmx")).unwrap();
+    let r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
     let prop_value: String = if let Some(&PropertyValue::StringValue(ref v)) =
         r.tilesets[0].tiles[0].properties.get("a tile property")
     {
@@ -76,3 +162,2322 @@ fn test_tile_property() {
     };
     assert_eq!("123", prop_value);
 }
+
+#[test]
+fn test_duplicate_properties() {
+    let xml = "<properties>
+ <property name=\"speed\" type=\"int\" value=\"1\"/>
+ <property name=\"speed\" type=\"int\" value=\"2\"/>
+</properties>";
+    let mut parser = xml::EventReader::new(xml.as_bytes());
+    // Skip the start of the document and of the properties element.
+    parser.next().unwrap();
+    parser.next().unwrap();
+    let properties = parse_properties(&mut parser).unwrap();
+    assert_eq!(properties["speed"], PropertyValue::IntValue(2));
+
+    let mut parser = xml::EventReader::new(xml.as_bytes());
+    parser.next().unwrap();
+    parser.next().unwrap();
+    match parse_properties_strict(&mut parser) {
+        Err(Error::MalformedAttributes(message)) => {
+            assert!(message.contains("speed"), "{}", message)
+        }
+        other => panic!("expected a malformed attributes error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_strict_properties_option() {
+    let tmx = include_str!("../assets/tiled_properties_everywhere.tmx");
+    let strict = || ParseOptions {
+        strict_properties: true,
+        ..ParseOptions::default()
+    };
+    Map::parse_with_options(tmx.as_bytes(), None, strict()).unwrap();
+
+    // Repeating any one of the properties is an error in strict mode only.
+    let property = r#"<property name="speed" type="int" value="1"/>"#;
+    let repeated = r#"<property name="speed" type="int" value="1"/><property name="speed" type="int" value="2"/>"#;
+    let count = tmx.matches(property).count();
+    assert_eq!(count, 10);
+    for i in 0..count {
+        let start = tmx.match_indices(property).nth(i).unwrap().0;
+        let tmx = format!(
+            "{}{}{}",
+            &tmx[..start],
+            repeated,
+            &tmx[start + property.len()..]
+        );
+        assert!(Map::parse_str(&tmx).is_ok(), "property {}", i);
+        match Map::parse_with_options(tmx.as_bytes(), None, strict()) {
+            Err(Error::MalformedAttributes(message)) => {
+                assert!(message.contains("speed"), "{}", message)
+            }
+            other => panic!("property {}: expected an error, got {:?}", i, other),
+        }
+    }
+}
+
+#[test]
+fn test_typed_properties() {
+    let r = read_from_file(Path::new("assets/tiled_typed_properties.tmx")).unwrap();
+    let p = &r.properties;
+    assert_eq!(p.get_bool("solid"), Some(true));
+    assert_eq!(p.get_int("health"), Some(-5));
+    assert_eq!(p.get_float("speed"), Some(1.5));
+    assert_eq!(p.get_string("title"), Some("Cave"));
+    assert_eq!(p.get_color("tint"), Some(0xff80ff00));
+    assert_eq!(p.get_int("title"), None);
+    assert_eq!(p.get_bool("missing"), None);
+
+    let r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    let p = &r.tilesets[0].tiles[0].properties;
+    // Properties without a type are strings.
+    assert_eq!(p.get_string("a tile property"), Some("123"));
+    assert_eq!(p.get_int("a tile property"), None);
+}
+
+#[test]
+fn test_tileset_index() {
+    let r = read_from_file(Path::new("assets/tiled_multiple_tilesets.tmx")).unwrap();
+    assert_eq!(r.tilesets.len(), 2);
+    for (i, tileset) in r.tilesets.iter().enumerate() {
+        assert_eq!(tileset.tileset_index, i);
+    }
+    assert_eq!(r.tilesets[1].name, "tilesheet2");
+    assert_eq!(r.get_tileset_by_gid(86).unwrap().tileset_index, 1);
+}
+
+#[test]
+fn test_empty_gid() {
+    assert!(is_empty_gid(0));
+    assert!(is_empty_gid(0x80000000));
+    assert!(!is_empty_gid(1));
+    assert!(!is_empty_gid(0x80000001));
+
+    let mut r = read_from_file(Path::new("assets/tiled_multiple_tilesets.tmx")).unwrap();
+    assert!(r.get_tileset_by_gid(0).is_none());
+    assert!(r.get_tileset_by_gid(0x80000000).is_none());
+    assert_eq!(r.local_id(0), None);
+    assert_eq!(r.get_tile_by_gid(0), None);
+    // Even a tileset starting at 0 doesn't own the empty tile.
+    r.tilesets[0].first_gid = 0;
+    assert!(r.get_tileset_by_gid(0).is_none());
+    assert!(r.get_tileset_by_gid(1).is_some());
+
+    // A cell with only flip flags set is empty wherever gids are used.
+    let tmx = include_str!("../assets/tiled_infinite_flat.tmx").replace("4,5,6", "4,2147483648,6");
+    let mut r = Map::parse_str(&tmx).unwrap();
+    assert_eq!(r.layers[0].iter_tiles().count(), 5);
+    assert_eq!(r.layers[0].tile_count_for_tileset(&r, 0), 5);
+    r.layers[0].flip_horizontal();
+    assert_eq!(r.layers[0].tiles[1][1], 0x80000000);
+    let object = Object {
+        id: 1,
+        gid: 0x80000000,
+        name: String::new(),
+        obj_type: String::new(),
+        x: 0.0,
+        y: 32.0,
+        rotation: 0.0,
+        visible: true,
+        shape: ObjectShape::Rect {
+            width: 16.0,
+            height: 16.0,
+        },
+        properties: HashMap::new(),
+        extra: HashMap::new(),
+    };
+    assert_eq!(object.get_tile(&r), None);
+    assert_eq!(object.bounding_box(), (0.0, 32.0, 16.0, 16.0));
+}
+
+#[test]
+fn test_external_tileset_loader() {
+    let r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    let file = File::open(Path::new("assets/tiled_base64_external.tmx")).unwrap();
+    let mut requested = Vec::new();
+    let mut e = Map::parse_with_loader(file, |source| {
+        requested.push(source.to_string());
+        let tileset: &[u8] = include_bytes!("../assets/tilesheet.tsx");
+        Ok(Box::new(tileset) as Box<dyn Read>)
+    })
+    .unwrap();
+    assert_eq!(requested, vec!["tilesheet.tsx".to_string()]);
+    assert_eq!(e.tilesets[0].source, Some(PathBuf::from("tilesheet.tsx")));
+    e.tilesets[0].source = None;
+    e.tilesets[0].source_attribute = None;
+    assert_eq!(r, e);
+}
+
+#[test]
+fn test_external_tileset_loader_error() {
+    let file = File::open(Path::new("assets/tiled_base64_external.tmx")).unwrap();
+    let result = Map::parse_with_loader(file, |source| {
+        Err(Error::Other(format!("no such tileset: {}", source)))
+    });
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_crlf_line_endings() {
+    let c = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    let crlf = read_from_file(Path::new("assets/tiled_csv_crlf.tmx")).unwrap();
+    assert_eq!(c, crlf);
+    for value in crlf.layers[0].properties.values() {
+        if let PropertyValue::StringValue(ref v) = *value {
+            assert!(
+                !v.contains('\r'),
+                "{:?} should not contain a carriage return",
+                v
+            );
+        }
+    }
+}
+
+#[test]
+fn test_polygon_area_and_convexity() {
+    let square = ObjectShape::Polygon {
+        points: vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)],
+    };
+    assert_eq!(square.polygon_area(), Some(100.0));
+    assert_eq!(square.is_convex(), Some(true));
+
+    let l_shape = ObjectShape::Polygon {
+        points: vec![
+            (0.0, 0.0),
+            (20.0, 0.0),
+            (20.0, 10.0),
+            (10.0, 10.0),
+            (10.0, 20.0),
+            (0.0, 20.0),
+        ],
+    };
+    assert_eq!(l_shape.polygon_area(), Some(300.0));
+    assert_eq!(l_shape.is_convex(), Some(false));
+
+    let rect = ObjectShape::Rect {
+        width: 10.0,
+        height: 10.0,
+    };
+    assert_eq!(rect.polygon_area(), None);
+    assert_eq!(rect.is_convex(), None);
+}
+
+#[test]
+fn test_external_tileset_cache() {
+    let mut cache = HashMap::new();
+    let path = Path::new("assets/tiled_base64_external.tmx");
+    let first = Map::parse_with_cache(File::open(path).unwrap(), path, &mut cache).unwrap();
+    assert_eq!(cache.len(), 1);
+    assert!(cache.contains_key(&PathBuf::from("assets/tilesheet.tsx")));
+
+    // A cached tileset is used in place of the file on disk.
+    cache
+        .get_mut(&PathBuf::from("assets/tilesheet.tsx"))
+        .unwrap()
+        .name = "cached".to_string();
+    let second = Map::parse_with_cache(File::open(path).unwrap(), path, &mut cache).unwrap();
+    assert_eq!(second.tilesets[0].name, "cached");
+    assert_eq!(second.tilesets[0].first_gid, first.tilesets[0].first_gid);
+    assert_eq!(second.layers, first.layers);
+}
+
+#[test]
+fn test_get_tile_by_gid() {
+    let r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    assert_eq!(r.local_id(2), Some(1));
+    let tile = r.get_tile_by_gid(2).unwrap();
+    assert_eq!(tile.id, 1);
+    assert!(tile.properties.contains_key("a tile property"));
+    assert!(r.get_tile_by_gid(3).is_none());
+    // Flipped horizontally and diagonally.
+    assert_eq!(r.local_id(0xa0000002), Some(1));
+    assert_eq!(r.get_tile_by_gid(0xa0000002).unwrap().id, 1);
+    assert_eq!(r.local_id(85), None);
+
+    let r = read_from_file(Path::new("assets/tiled_multiple_tilesets.tmx")).unwrap();
+    assert_eq!(r.local_id(84), Some(83));
+    assert_eq!(r.local_id(85), Some(0));
+    assert_eq!(r.local_id(0x40000055), Some(0));
+    assert_eq!(r.local_id(169), None);
+}
+
+#[test]
+fn test_tileset_multiple_images() {
+    let file = File::open(Path::new("assets/tilesheet_multiple_images.tsx")).unwrap();
+    let t = Tileset::parse(file, 1).unwrap();
+    assert_eq!(t.images.len(), 2);
+    assert_eq!(t.images[0].source, "tilesheet.png");
+    assert_eq!(t.images[1].source, "tilesheet_alt.png");
+    assert_eq!(t.image(), Some(&t.images[0]));
+}
+
+#[test]
+fn test_parse_bytes_lossy() {
+    let mut bytes = br#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.0" orientation="orthogonal" width="1" height="1" tilewidth="32" tileheight="32">
+ <properties>
+  <property name="title" value="caf"#
+        .to_vec();
+    bytes.push(0xe9);
+    bytes.extend_from_slice(
+        br#""/>
+ </properties>
+</map>"#,
+    );
+    let r = Map::parse_bytes_lossy(&bytes).unwrap();
+    assert_eq!(
+        r.properties.get("title"),
+        Some(&PropertyValue::StringValue("caf\u{FFFD}".to_string()))
+    );
+}
+
+#[test]
+fn test_point_object() {
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let objects = &r.object_groups[0].objects;
+    let spawn = objects.iter().find(|o| o.name == "spawn").unwrap();
+    assert_eq!(spawn.shape, ObjectShape::Point);
+    assert_eq!((spawn.x, spawn.y), (48.0, 80.0));
+    let other = objects.iter().find(|o| o.name == "box").unwrap();
+    assert_eq!(
+        other.shape,
+        ObjectShape::Rect {
+            width: 32.0,
+            height: 32.0
+        }
+    );
+}
+
+#[test]
+fn test_text_object() {
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let label = r.object_groups[0]
+        .objects
+        .iter()
+        .find(|o| o.name == "label")
+        .unwrap();
+    match label.shape {
+        ObjectShape::Text {
+            width,
+            height,
+            ref text,
+        } => {
+            assert_eq!((width, height), (96.0, 24.0));
+            assert_eq!(text.contents, "Hello, world!");
+            assert_eq!(text.font_family, "Serif");
+            assert_eq!(text.pixel_size, 12);
+            assert!(text.wrap);
+            assert!(text.bold);
+            assert!(!text.italic);
+            assert!(text.kerning);
+            assert_eq!(text.color.red, 255);
+            assert_eq!(text.halign, HorizontalAlignment::Center);
+            assert_eq!(text.valign, VerticalAlignment::Bottom);
+        }
+        ref shape => panic!("expected a text object, got {:?}", shape),
+    }
+}
+
+#[test]
+fn test_polygon_object_properties() {
+    let r = read_from_file(Path::new("assets/tiled_polygon_properties.tmx")).unwrap();
+    let objects = &r.object_groups[0].objects;
+    assert_eq!(objects.len(), 2);
+    for object in objects {
+        assert_eq!(
+            object.shape,
+            ObjectShape::Polygon {
+                points: vec![(0.0, 0.0), (32.0, 0.0), (32.0, 32.0)]
+            },
+            "{}",
+            object.name
+        );
+        assert_eq!(
+            object.properties.get("solid"),
+            Some(&PropertyValue::BoolValue(true)),
+            "{}",
+            object.name
+        );
+    }
+}
+
+#[test]
+fn test_tile_object_in_external_tileset() {
+    let r = read_from_file_with_path(Path::new("assets/tiled_external_tile_object.tmx")).unwrap();
+    let object = &r.object_groups[0].objects[0];
+    assert_eq!(object.gid, 2);
+    let tile = object.get_tile(&r).unwrap();
+    assert_eq!(tile.id, 1);
+    assert!(tile.properties.contains_key("a tile property"));
+
+    // Flipped horizontally.
+    let flipped = &r.object_groups[0].objects[1];
+    assert_eq!(flipped.gid, 0x80000002);
+    assert_eq!(flipped.get_tile(&r), Some(tile));
+}
+
+#[test]
+fn test_layer_flip() {
+    let r = read_from_file(Path::new("assets/tiled_multiple_tilesets.tmx")).unwrap();
+    let original = r.layers[0].clone();
+    let mut layer = original.clone();
+
+    layer.flip_horizontal();
+    assert_eq!(
+        layer.tiles[0],
+        vec![
+            86 | 0x80000000,
+            85 | 0x80000000,
+            2 | 0x80000000,
+            1 | 0x80000000
+        ]
+    );
+    assert_eq!(layer.tiles[1][1], 0);
+    layer.flip_horizontal();
+    assert_eq!(layer, original);
+
+    layer.flip_vertical();
+    assert_eq!(layer.tiles[0], vec![4 | 0x40000000, 0, 0, 84 | 0x40000000]);
+    layer.flip_vertical();
+    assert_eq!(layer, original);
+
+    // The diagonal flip comes first, so mirroring toggles the flag of the
+    // same axis as for any other tile.
+    layer.tiles[0][0] = 1 | 0x20000000;
+    layer.flip_horizontal();
+    assert_eq!(layer.tiles[0][3], 1 | 0x20000000 | 0x80000000);
+    layer.flip_horizontal();
+    layer.flip_vertical();
+    assert_eq!(layer.tiles[3][0], 1 | 0x20000000 | 0x40000000);
+}
+
+#[test]
+fn test_color_alpha() {
+    let opaque: Color = "#ff8000".parse().unwrap();
+    assert_eq!(
+        opaque,
+        Color {
+            red: 0xff,
+            green: 0x80,
+            blue: 0x00,
+            alpha: 0xff
+        }
+    );
+    let translucent: Color = "#80ff8000".parse().unwrap();
+    assert_eq!(
+        translucent,
+        Color {
+            red: 0xff,
+            green: 0x80,
+            blue: 0x00,
+            alpha: 0x80
+        }
+    );
+    assert!("#ff80".parse::<Color>().is_err());
+    assert!("#zz808080".parse::<Color>().is_err());
+    assert!("#ééé".parse::<Color>().is_err());
+    assert!("#aébbb".parse::<Color>().is_err());
+}
+
+#[test]
+fn test_color_blend() {
+    let black: Color = "#000000".parse().unwrap();
+    let white: Color = "#80ffffff".parse().unwrap();
+    assert_eq!(black.blend(&white, 0.0), black);
+    assert_eq!(black.blend(&white, 1.0), white);
+    assert_eq!(black.blend(&white, 2.0), white);
+    let grey = black.blend(&white, 0.5);
+    assert_eq!(
+        (grey.red, grey.green, grey.blue, grey.alpha),
+        (128, 128, 128, 192)
+    );
+    assert_eq!(black.to_u32(), 0xff000000);
+    assert_eq!(white.to_u32(), 0x80ffffff);
+    assert_eq!("#ff8000".parse::<Color>().unwrap().to_u32(), 0xffff8000);
+}
+
+#[test]
+fn test_layer_tint_color() {
+    let r = read_from_file(Path::new("assets/tiled_layer_attributes.tmx")).unwrap();
+    assert_eq!(
+        r.layers[0].tint_color,
+        Some(Color {
+            red: 0xff,
+            green: 0,
+            blue: 0,
+            alpha: 0x80
+        })
+    );
+    assert_eq!(r.layers[1].tint_color, None);
+    assert_eq!(
+        r.image_layers[0].tint_color,
+        Some(Color {
+            red: 0,
+            green: 0xff,
+            blue: 0,
+            alpha: 0xff
+        })
+    );
+}
+
+#[test]
+fn test_future_version() {
+    let r = read_from_file(Path::new("assets/tiled_future_version.tmx")).unwrap();
+    assert_eq!(r.version, "99.0");
+    assert_eq!((r.width, r.height), (2, 2));
+    assert_eq!((r.tile_width, r.tile_height), (32, 32));
+    assert_eq!(r.tilesets[0].name, "tilesheet");
+    assert_eq!(r.layers[0].tiles, vec![vec![1, 2], vec![3, 4]]);
+    assert_eq!(r.extra["futureattribute"], "yes");
+
+    let mut warnings = Vec::new();
+    {
+        let file = File::open(Path::new("assets/tiled_future_version.tmx")).unwrap();
+        let options = ParseOptions {
+            on_warning: Some(Box::new(|w: String| warnings.push(w))),
+            ..ParseOptions::default()
+        };
+        Map::parse_with_options(file, None, options).unwrap();
+    }
+    assert_eq!(warnings.len(), 1);
+}
+
+#[test]
+fn test_tileset_contains_gid() {
+    let r = read_from_file(Path::new("assets/tiled_multiple_tilesets.tmx")).unwrap();
+    let (first, second) = (&r.tilesets[0], &r.tilesets[1]);
+    assert!(!first.contains_gid(0));
+    assert!(first.contains_gid(1));
+    assert!(first.contains_gid(84));
+    assert!(!first.contains_gid(85));
+    assert!(first.contains_gid(1 | 0x80000000));
+    assert!(!second.contains_gid(84));
+    assert!(second.contains_gid(85));
+    assert!(second.contains_gid(168));
+    assert!(!second.contains_gid(169));
+}
+
+#[test]
+fn test_tile_count_effective() {
+    let mut tileset = read_from_file(Path::new("assets/tiled_base64.tmx"))
+        .unwrap()
+        .tilesets[0]
+        .clone();
+    assert_eq!(tileset.tile_count, Some(84));
+    tileset.tile_count = None;
+    // 448x192 pixels of 32x32 tiles
+    assert_eq!(tileset.tile_count_effective(), 84);
+
+    tileset.tile_width = 0;
+    assert_eq!(tileset.tile_count_effective(), 0);
+    assert!(!tileset.contains_gid(1));
+    assert_eq!(tileset.tile_rect(1), None);
+}
+
+#[test]
+fn test_tile_rect() {
+    let t = Tileset::parse(
+        File::open(Path::new("assets/tiled_spacing.tsx")).unwrap(),
+        1,
+    )
+    .unwrap();
+    assert_eq!(t.columns, Some(4));
+    assert_eq!(t.tile_rect(1), Some((1, 1, 16, 16)));
+    assert_eq!(t.tile_rect(6), Some((19, 19, 16, 16)));
+    assert_eq!(t.tile_rect(8 | 0x40000000), Some((55, 19, 16, 16)));
+    assert_eq!(t.tile_rect(0), None);
+    assert_eq!(t.tile_rect(9), None);
+
+    // Without a columns attribute they are worked out from the image.
+    let r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    assert_eq!(r.tilesets[0].columns, None);
+    assert_eq!(r.tilesets[0].tile_rect(16), Some((32, 32, 32, 32)));
+}
+
+#[test]
+fn test_spaced_tilesets() {
+    let r = read_from_file_with_path(Path::new("assets/tiled_spacing.tmx")).unwrap();
+    let (embedded, external) = (&r.tilesets[0], &r.tilesets[1]);
+    assert_eq!((embedded.spacing, embedded.margin), (2, 1));
+    assert_eq!((external.spacing, external.margin), (2, 1));
+    assert_eq!(embedded.columns, None);
+    assert_eq!(embedded.tile_count_effective(), 8);
+    for local_id in 0..8 {
+        assert_eq!(
+            embedded.tile_rect(embedded.first_gid + local_id),
+            external.tile_rect(external.first_gid + local_id)
+        );
+    }
+    let tiles = &r.layers[0].tiles[0];
+    assert_eq!(embedded.tile_rect(tiles[0]), Some((19, 19, 16, 16)));
+    assert_eq!(external.tile_rect(tiles[1]), Some((19, 19, 16, 16)));
+}
+
+#[test]
+fn test_layer_offsets() {
+    let r = read_from_file(Path::new("assets/tiled_layer_attributes.tmx")).unwrap();
+    assert_eq!((r.layers[0].offset_x, r.layers[0].offset_y), (8.0, -4.5));
+    assert_eq!((r.layers[1].offset_x, r.layers[1].offset_y), (0.0, 0.0));
+}
+
+#[test]
+fn test_opacity_clamped() {
+    let map = "<map version=\"1.0\" orientation=\"orthogonal\" width=\"1\" height=\"1\" tilewidth=\"32\" tileheight=\"32\">
+ <layer name=\"Tile Layer 1\" width=\"1\" height=\"1\" opacity=\"2.5\">
+  <data encoding=\"csv\">0</data>
+ </layer>
+ <imagelayer name=\"Image Layer 1\" opacity=\"-1\"/>
+ <objectgroup name=\"Object Layer 1\" opacity=\"half\"/>
+</map>";
+    let r = Map::parse(map.as_bytes()).unwrap();
+    assert_eq!(r.layers[0].opacity, 1.0);
+    assert_eq!(r.image_layers[0].opacity, 0.0);
+    assert_eq!(r.object_groups[0].opacity, 1.0);
+}
+
+#[test]
+fn test_extra_attributes() {
+    let r = read_from_file(Path::new("assets/tiled_extra_attributes.tmx")).unwrap();
+    assert_eq!(r.extra["tiledversion"], "1.10.2");
+    assert_eq!(r.extra["exporter"], "level-tool");
+    assert!(!r.extra.contains_key("width"));
+    assert_eq!(r.tilesets[0].extra["atlas"], "terrain");
+    let layer = &r.layers[0];
+    assert_eq!(layer.extra["editor:note"], "do not edit");
+    assert_eq!(layer.extra["xmlns:editor"], "https://example.com/editor");
+    assert_eq!(layer.extra.len(), 2);
+    assert!(r.object_groups[0].extra.is_empty());
+    assert_eq!(r.object_groups[0].objects[0].extra["spawnweight"], "3");
+
+    let mut tmx = Vec::new();
+    r.write(&mut tmx).unwrap();
+    assert!(String::from_utf8_lossy(&tmx).contains("editor:note=\"do not edit\""));
+    assert_eq!(Map::parse(&tmx[..]).unwrap(), r);
+
+    let plain = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    assert!(plain.extra.is_empty());
+    assert!(plain.layers[0].extra.is_empty());
+}
+
+#[test]
+fn test_object_draw_order() {
+    let r = read_from_file(Path::new("assets/tiled_layer_attributes.tmx")).unwrap();
+    assert_eq!(r.object_groups[0].draw_order, DrawOrder::Index);
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    assert_eq!(r.object_groups[0].draw_order, DrawOrder::TopDown);
+}
+
+#[test]
+fn test_layers_in_order() {
+    fn name(layer: LayerKind<'_>) -> &str {
+        match layer {
+            LayerKind::Tile(layer) => &layer.name,
+            LayerKind::Image(layer) => &layer.name,
+            LayerKind::Objects(layer) => &layer.name,
+            LayerKind::Group(layer) => &layer.name,
+        }
+    }
+    let r = read_from_file(Path::new("assets/tiled_layer_attributes.tmx")).unwrap();
+    let names: Vec<&str> = r.layers_in_order().map(name).collect();
+    assert_eq!(names, vec!["Tinted", "Tinted image", "Plain", "Objects"]);
+    assert!(matches!(
+        r.layers_in_order().nth(2),
+        Some(LayerKind::Tile(_))
+    ));
+
+    let r = read_from_file(Path::new("assets/tiled_group.tmx")).unwrap();
+    let names: Vec<&str> = (&r).into_iter().map(name).collect();
+    assert_eq!(names, vec!["Background", "Group", "After group"]);
+    let indices: Vec<u32> = r.layers_in_order().map(|l| l.layer_index()).collect();
+    assert_eq!(indices, vec![0, 1, 4]);
+}
+
+#[test]
+fn test_locked_layers() {
+    let r = read_from_file(Path::new("assets/tiled_layer_attributes.tmx")).unwrap();
+    assert!(r.layers[0].locked);
+    assert!(!r.layers[1].locked);
+    assert!(r.image_layers[0].locked);
+    assert!(r.object_groups[0].locked);
+}
+
+#[test]
+fn test_layer_and_object_ids() {
+    let r = read_from_file(Path::new("assets/tiled_layer_attributes.tmx")).unwrap();
+    assert_eq!(r.next_layer_id, Some(5));
+    assert_eq!(r.next_object_id, Some(1));
+    assert_eq!(r.layers[0].id, Some(1));
+    assert_eq!(r.image_layers[0].id, Some(2));
+    assert_eq!(r.layers[1].id, Some(3));
+    assert_eq!(r.object_groups[0].id, Some(4));
+
+    let r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    assert_eq!(r.next_layer_id, None);
+    assert_eq!(r.layers[0].id, None);
+    let ids: Vec<u32> = r.object_groups[0].objects.iter().map(|o| o.id).collect();
+    assert_eq!(ids, vec![1, 2, 3, 4]);
+}
+
+#[test]
+fn test_non_square_tiles() {
+    let r = read_from_file(Path::new("assets/tiled_non_square.tmx")).unwrap();
+    assert_eq!((r.tile_width, r.tile_height), (16, 24));
+    assert_eq!(r.layers[0].tiles, vec![vec![1, 2, 3], vec![5, 6, 8]]);
+    let tileset = &r.tilesets[0];
+    // A 64x48 image holds 4 columns and 2 rows of 16x24 tiles.
+    assert_eq!(tileset.tile_count, None);
+    assert_eq!(tileset.tile_count_effective(), 8);
+    assert!(tileset.contains_gid(8));
+    assert!(!tileset.contains_gid(9));
+}
+
+#[test]
+fn test_group_properties_before_children() {
+    let r = read_from_file(Path::new("assets/tiled_group.tmx")).unwrap();
+    assert_eq!(r.layers.len(), 1);
+    assert_eq!(r.groups.len(), 1);
+    let group = &r.groups[0];
+    assert_eq!(group.name, "Group");
+    assert_eq!(group.opacity, 0.5);
+    assert_eq!(
+        group.properties.get("group property"),
+        Some(&PropertyValue::StringValue("yes".to_string()))
+    );
+    assert_eq!(group.layers.len(), 2);
+    assert_eq!(group.layers[0].name, "Child 1");
+    assert_eq!(group.layers[0].tiles, vec![vec![1, 2], vec![3, 4]]);
+    assert_eq!(group.layers[1].name, "Child 2");
+    assert_eq!(group.layers[1].tiles, vec![vec![5, 6], vec![7, 8]]);
+    // Layers are numbered in document order, through the group.
+    assert_eq!(r.layers[0].layer_index, 0);
+    assert_eq!(group.layer_index, 1);
+    assert_eq!(group.layers[0].layer_index, 2);
+    assert_eq!(group.layers[1].layer_index, 3);
+    assert_eq!(r.object_groups[0].layer_index, Some(4));
+}
+
+#[test]
+fn test_on_tileset_callback() {
+    let mut names = Vec::new();
+    {
+        let options = ParseOptions {
+            on_tileset: Some(Box::new(|tileset: &Tileset| {
+                names.push(tileset.name.clone())
+            })),
+            ..ParseOptions::default()
+        };
+        let file = File::open(Path::new("assets/tiled_multiple_tilesets.tmx")).unwrap();
+        Map::parse_with_options(file, None, options).unwrap();
+    }
+    assert_eq!(
+        names,
+        vec!["tilesheet".to_string(), "tilesheet2".to_string()]
+    );
+
+    let mut count = 0;
+    {
+        let options = ParseOptions {
+            on_tileset: Some(Box::new(|_: &Tileset| count += 1)),
+            ..ParseOptions::default()
+        };
+        let path = Path::new("assets/tiled_base64_external.tmx");
+        Map::parse_with_options(File::open(path).unwrap(), Some(path), options).unwrap();
+    }
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn test_render_order() {
+    let r = read_from_file(Path::new("assets/tiled_non_square.tmx")).unwrap();
+    assert_eq!(r.render_order, RenderOrder::LeftUp);
+    let r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    assert_eq!(r.render_order, RenderOrder::RightDown);
+    let r = Map::parse("<map version=\"1.0\" orientation=\"orthogonal\" width=\"1\" height=\"1\" tilewidth=\"32\" tileheight=\"32\"></map>".as_bytes()).unwrap();
+    assert_eq!(r.render_order, RenderOrder::RightDown);
+}
+
+#[test]
+fn test_object_visibility() {
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let objects = &r.object_groups[0].objects;
+    let hidden = objects.iter().find(|o| o.name == "box").unwrap();
+    assert!(!hidden.visible);
+    let shown = objects.iter().find(|o| o.name == "spawn").unwrap();
+    assert!(shown.visible);
+}
+
+#[test]
+fn test_visible_object_groups() {
+    let r = read_from_file(Path::new("assets/tiled_hidden_object_group.tmx")).unwrap();
+    assert_eq!(r.object_groups.len(), 3);
+    let names: Vec<&str> = r
+        .visible_object_groups()
+        .map(|group| group.name.as_str())
+        .collect();
+    assert_eq!(names, ["Enemies"]);
+}
+
+#[test]
+fn test_object_template() {
+    let r = read_from_file_with_path(Path::new("assets/tiled_object_template.tmx")).unwrap();
+    let objects = &r.object_groups[0].objects;
+
+    let plain = &objects[0];
+    assert_eq!(plain.name, "crate");
+    assert_eq!(plain.obj_type, "prop");
+    assert_eq!((plain.x, plain.y), (16.0, 32.0));
+    assert_eq!(
+        plain.shape,
+        ObjectShape::Rect {
+            width: 32.0,
+            height: 16.0
+        }
+    );
+    assert_eq!(
+        plain.properties.get("health"),
+        Some(&PropertyValue::IntValue(10))
+    );
+
+    let overridden = &objects[1];
+    assert_eq!(overridden.name, "heavy crate");
+    assert_eq!(overridden.obj_type, "prop");
+    assert_eq!(
+        overridden.shape,
+        ObjectShape::Rect {
+            width: 64.0,
+            height: 16.0
+        }
+    );
+    assert_eq!(
+        overridden.properties.get("health"),
+        Some(&PropertyValue::IntValue(50))
+    );
+    assert_eq!(
+        overridden.properties.get("breakable"),
+        Some(&PropertyValue::BoolValue(true))
+    );
+}
+
+#[test]
+fn test_tile_object_template() {
+    // The template refers to tile 1 of tilesheet.tsx with its own firstgid of
+    // 1, while the map gives that tileset a firstgid of 5.
+    let check = |r: &Map| {
+        let object = &r.object_groups[0].objects[0];
+        assert_eq!(object.name, "tile crate");
+        assert_eq!(object.gid, 6);
+        let tile = object.get_tile(r).unwrap();
+        assert!(tile.properties.contains_key("a tile property"));
+    };
+    check(&read_from_file_with_path(Path::new("assets/tiled_tile_template.tmx")).unwrap());
+
+    let file = File::open("assets/tiled_tile_template.tmx").unwrap();
+    let mut sources = Vec::new();
+    let r = Map::parse_with_loader(file, |source| {
+        sources.push(source.to_string());
+        let file = File::open(Path::new("assets").join(source))?;
+        Ok(Box::new(file) as Box<dyn Read>)
+    })
+    .unwrap();
+    check(&r);
+    assert_eq!(sources, vec!["tilesheet.tsx", "templates/tile_crate.tx"]);
+}
+
+#[test]
+fn test_collision_object_template() {
+    // Templates are relative to the file the tileset is written in.
+    let r = read_from_file_with_path(Path::new("assets/tiled_collision_template.tmx")).unwrap();
+    let positions = [(4.0, 8.0), (2.0, 2.0)];
+    for (tileset, &position) in r.tilesets.iter().zip(positions.iter()) {
+        let shapes: Vec<_> = tileset.tiles[0].collision_shapes().collect();
+        assert_eq!(shapes.len(), 1);
+        assert_eq!(shapes[0].name, "box");
+        assert_eq!((shapes[0].x, shapes[0].y), position);
+        assert_eq!(
+            shapes[0].shape,
+            ObjectShape::Rect {
+                width: 16.0,
+                height: 8.0
+            }
+        );
+    }
+
+    let path = Path::new("assets/templates/collision_tileset.tsx");
+    let t = Tileset::parse_with_path(File::open(path).unwrap(), 1, path).unwrap();
+    assert_eq!(t.tiles[0].collision_shapes().next().unwrap().name, "box");
+    assert!(Tileset::parse(File::open(path).unwrap(), 1).is_err());
+}
+
+#[test]
+fn test_nested_template_is_rejected() {
+    let e = read_from_file_with_path(Path::new("assets/tiled_nested_template.tmx")).unwrap_err();
+    match e {
+        Error::Other(s) => assert!(s.contains("nested templates are not supported")),
+        e => panic!("unexpected error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_tile_count_for_tileset() {
+    let r = read_from_file(Path::new("assets/tiled_multiple_tilesets.tmx")).unwrap();
+    let (first, second) = (&r.layers[0], &r.layers[1]);
+    assert_eq!(first.tile_count_for_tileset(&r, 0), 5);
+    assert_eq!(first.tile_count_for_tileset(&r, 1), 4);
+    assert_eq!(second.tile_count_for_tileset(&r, 0), 1);
+    assert_eq!(second.tile_count_for_tileset(&r, 1), 3);
+    assert_eq!(first.tile_count_for_tileset(&r, 2), 0);
+    assert_eq!(r.tile_count_for_tileset(0), 6);
+    assert_eq!(r.tile_count_for_tileset(1), 7);
+}
+
+#[test]
+fn test_tile_at_pixel() {
+    let mut r = read_from_file(Path::new("assets/tiled_region.tmx")).unwrap();
+    assert_eq!(r.tile_at_pixel(0, 0.0, 0.0), Some(1));
+    assert_eq!(r.tile_at_pixel(0, 40.0, 70.0), Some(10));
+    assert_eq!(r.tile_at_pixel(0, 127.9, 127.9), Some(169));
+    assert_eq!(r.tile_at_pixel(0, 128.0, 0.0), None);
+    assert_eq!(r.tile_at_pixel(0, -0.5, 0.0), None);
+    assert_eq!(r.tile_at_pixel(1, 0.0, 0.0), None);
+
+    r.layers[0].offset_x = 16.0;
+    assert_eq!(r.tile_at_pixel(0, 40.0, 0.0), Some(1));
+    assert_eq!(r.tile_at_pixel(0, 8.0, 0.0), None);
+    r.layers[0].tiles[0][0] = 0;
+    assert_eq!(r.tile_at_pixel(0, 16.0, 0.0), None);
+
+    r.orientation = Orientation::Isometric;
+    assert_eq!(r.tile_at_pixel(0, 40.0, 70.0), None);
+}
+
+#[test]
+fn test_clone_region() {
+    let r = read_from_file(Path::new("assets/tiled_region.tmx")).unwrap();
+    let region = r.clone_region(1, 1, 2, 2);
+    assert_eq!((region.width, region.height), (2, 2));
+    assert_eq!(region.layers[0].tiles, vec![vec![6, 7], vec![10, 85]]);
+
+    let objects = &region.object_groups[0].objects;
+    assert_eq!(objects.len(), 2);
+    assert_eq!(objects[0].name, "inside");
+    assert_eq!((objects[0].x, objects[0].y), (8.0, 40.0));
+    assert_eq!((objects[1].x, objects[1].y), (32.0, 32.0));
+
+    // The third tileset is only used outside of the region.
+    let names: Vec<&str> = region.tilesets.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(names, ["tilesheet", "tilesheet2"]);
+    assert_eq!(region.tilesets[1].tileset_index, 1);
+
+    // The region ends at the edge of the map.
+    let edge = r.clone_region(3, 3, 2, 2);
+    assert_eq!((edge.width, edge.height), (1, 1));
+    assert_eq!(edge.layers[0].tiles, vec![vec![169]]);
+
+    let rest = r.clone_region(2, 1, u32::MAX, u32::MAX);
+    assert_eq!((rest.width, rest.height), (2, 3));
+    assert_eq!(
+        rest.layers[0].tiles,
+        vec![vec![7, 8], vec![85, 12], vec![15, 169]]
+    );
+    let objects = &rest.object_groups[0].objects;
+    assert_eq!(objects.len(), 1);
+    assert_eq!(objects[0].name, "tile");
+
+    let outside = r.clone_region(u32::MAX, u32::MAX, u32::MAX, u32::MAX);
+    assert_eq!((outside.width, outside.height), (0, 0));
+    assert!(outside.layers[0].tiles_flat().is_empty());
+    assert!(outside.object_groups[0].objects.is_empty());
+    assert!(outside.tilesets.is_empty());
+}
+
+#[test]
+fn test_rebase_tileset() {
+    let mut r = read_from_file(Path::new("assets/tiled_multiple_tilesets.tmx")).unwrap();
+    // Flip a tile of the second tileset to check that flags survive.
+    r.layers[0].tiles[0][2] |= 0x80000000;
+    r.rebase_tileset(1, 201);
+    assert_eq!(r.tilesets[1].first_gid, 201);
+    assert_eq!(
+        r.layers[0].tiles,
+        vec![
+            vec![1, 2, 201 | 0x80000000, 202],
+            vec![3, 0, 0, 203],
+            vec![0, 0, 204, 0],
+            vec![4, 0, 0, 84],
+        ]
+    );
+    assert_eq!(r.layers[1].tiles[3], vec![0, 0, 284, 1]);
+    assert_eq!(r.get_tileset_by_gid(204).unwrap().name, "tilesheet2");
+    assert_eq!(r.local_id(284), Some(83));
+
+    let mut r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    r.tilesets_mut()[0].name = "renamed".to_string();
+    r.rebase_tileset(0, 1001);
+    let tile = r.get_tile_by_gid(1002).unwrap();
+    assert!(tile.properties.contains_key("a tile property"));
+    assert_eq!(r.get_tileset_by_gid(1001).unwrap().name, "renamed");
+}
+
+#[test]
+fn test_orientation_round_trip() {
+    let orientations = [
+        Orientation::Orthogonal,
+        Orientation::Isometric,
+        Orientation::Staggered,
+        Orientation::Hexagonal,
+    ];
+    for orientation in orientations.iter() {
+        assert_eq!(
+            orientation.to_string().parse::<Orientation>().unwrap(),
+            *orientation
+        );
+    }
+    assert_eq!(Orientation::Staggered.to_string(), "staggered");
+}
+
+#[test]
+fn test_iter_tiles() {
+    let r = read_from_file(Path::new("assets/tiled_multiple_tilesets.tmx")).unwrap();
+    let layer = &r.layers[0];
+    let tiles: Vec<_> = layer.iter_tiles().collect();
+    assert_eq!(
+        tiles,
+        vec![
+            (0, 0, 1),
+            (1, 0, 2),
+            (2, 0, 85),
+            (3, 0, 86),
+            (0, 1, 3),
+            (3, 1, 87),
+            (2, 2, 88),
+            (0, 3, 4),
+            (3, 3, 84),
+        ]
+    );
+    assert_eq!(layer.iter_all_tiles().count(), 16);
+    assert_eq!(layer.iter_all_tiles().nth(5), Some((1, 1, 0)));
+}
+
+#[test]
+fn test_tileset_image_source_path() {
+    let map_path = std::env::current_dir()
+        .unwrap()
+        .join("assets/maps/tiled_relative_tileset.tmx");
+    let r = read_from_file_with_path(&map_path).unwrap();
+    let image_path = Path::new("assets/tilesheet.png").canonicalize().unwrap();
+    // The external tileset's image is relative to the tsx, in another directory.
+    let external = r.tilesets[0].image_source_path(&map_path).unwrap();
+    assert!(external.is_absolute());
+    assert_eq!(external.canonicalize().unwrap(), image_path);
+    // The embedded tileset's image is relative to the map.
+    let embedded = r.tilesets[1].image_source_path(&map_path).unwrap();
+    assert_eq!(embedded.canonicalize().unwrap(), image_path);
+}
+
+#[test]
+fn test_layers_by_name() {
+    let r = read_from_file(Path::new("assets/tiled_duplicate_layer_names.tmx")).unwrap();
+    let terrain = r.layers_by_name("Terrain");
+    assert_eq!(terrain.len(), 2);
+    assert_eq!(terrain[0].id, Some(1));
+    assert_eq!(terrain[1].id, Some(3));
+    assert_eq!(r.layer_by_name("Terrain").unwrap().id, Some(1));
+    assert_eq!(r.layers_by_name("Decoration").len(), 1);
+    assert!(r.layers_by_name("Missing").is_empty());
+    assert!(r.layer_by_name("Missing").is_none());
+}
+
+#[test]
+fn test_tiles_flat() {
+    let r = read_from_file(Path::new("assets/tiled_multiple_tilesets.tmx")).unwrap();
+    let layer = &r.layers[0];
+    let flat = layer.tiles_flat();
+    let width = r.width as usize;
+    assert_eq!(flat.len(), width * r.height as usize);
+    for (x, y, gid) in layer.iter_all_tiles() {
+        assert_eq!(flat[y as usize * width + x as usize], gid);
+    }
+    assert_eq!(flat[2 * width + 2], 88);
+}
+
+#[test]
+fn test_parse_points_whitespace() {
+    let expected = vec![(0.0, 0.0), (32.0, 0.0), (32.0, 16.5)];
+    let tabs = Object::parse_points("0,0\t32,0\t32,16.5".to_string()).unwrap();
+    assert_eq!(tabs, expected);
+    let spaces = Object::parse_points(" 0,0  32,0   32,16.5 ".to_string()).unwrap();
+    assert_eq!(spaces, expected);
+    let mixed = Object::parse_points("0,0 \n\t32,0\r\n32,16.5".to_string()).unwrap();
+    assert_eq!(mixed, expected);
+}
+
+#[test]
+fn test_external_tileset_source() {
+    let r = read_from_file_with_path(Path::new("assets/tiled_base64_external.tmx")).unwrap();
+    assert_eq!(
+        r.tilesets[0].source,
+        Some(PathBuf::from("assets/tilesheet.tsx"))
+    );
+    let r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    assert_eq!(r.tilesets[0].source, None);
+    let t = Tileset::parse(File::open(Path::new("assets/tilesheet.tsx")).unwrap(), 1).unwrap();
+    assert_eq!(t.source, None);
+}
+
+#[test]
+fn test_objects_in_rect() {
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let names = |objects: Vec<&Object>| -> Vec<String> {
+        let mut names: Vec<_> = objects.iter().map(|o| o.name.clone()).collect();
+        names.sort();
+        names
+    };
+    assert_eq!(names(r.objects_in_rect(0.0, 0.0, 40.0, 40.0)), vec!["box"]);
+    assert_eq!(
+        names(r.objects_in_rect(40.0, 60.0, 30.0, 30.0)),
+        vec!["label", "spawn"]
+    );
+    assert!(r.objects_in_rect(200.0, 200.0, 10.0, 10.0).is_empty());
+    let label = r.object_groups[0]
+        .objects
+        .iter()
+        .find(|o| o.name == "label")
+        .unwrap();
+    assert_eq!(label.bounding_box(), (64.0, 64.0, 96.0, 24.0));
+}
+
+#[test]
+fn test_error_position() {
+    let map = "<map version=\"1.0\" orientation=\"orthogonal\" width=\"1\" height=\"1\" tilewidth=\"32\" tileheight=\"32\">
+ <objectgroup name=\"Objects\">
+  <object id=\"1\"/>
+ </objectgroup>
+</map>";
+    match Map::parse(map.as_bytes()) {
+        Err(Error::MalformedAttributes(message)) => {
+            assert!(message.starts_with("3:"), "{}", message)
+        }
+        other => panic!("expected malformed attributes, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_rotated_bounding_box() {
+    fn assert_box_eq(actual: (f32, f32, f32, f32), expected: (f32, f32, f32, f32)) {
+        let (a, e) = (
+            [actual.0, actual.1, actual.2, actual.3],
+            [expected.0, expected.1, expected.2, expected.3],
+        );
+        for (a, e) in a.iter().zip(e.iter()) {
+            assert!((a - e).abs() < 0.001, "{:?} != {:?}", actual, expected);
+        }
+    }
+    let mut rect = Object {
+        id: 1,
+        gid: 0,
+        name: String::new(),
+        obj_type: String::new(),
+        x: 100.0,
+        y: 50.0,
+        rotation: 0.0,
+        visible: true,
+        shape: ObjectShape::Rect {
+            width: 32.0,
+            height: 16.0,
+        },
+        properties: HashMap::new(),
+        extra: HashMap::new(),
+    };
+    assert_box_eq(rect.bounding_box(), (100.0, 50.0, 32.0, 16.0));
+    // A quarter turn clockwise around the top-left corner.
+    rect.rotation = 90.0;
+    assert_box_eq(rect.bounding_box(), (84.0, 50.0, 16.0, 32.0));
+    rect.rotation = 45.0;
+    let diagonal = 48.0 / 2f32.sqrt();
+    assert_box_eq(
+        rect.bounding_box(),
+        (100.0 - 16.0 / 2f32.sqrt(), 50.0, diagonal, diagonal),
+    );
+
+    // Tile objects rotate around their bottom-left corner.
+    let mut tile = rect.clone();
+    tile.gid = 1;
+    tile.rotation = 0.0;
+    assert_box_eq(tile.bounding_box(), (100.0, 34.0, 32.0, 16.0));
+    tile.rotation = 90.0;
+    assert_box_eq(tile.bounding_box(), (100.0, 50.0, 16.0, 32.0));
+
+    let mut polygon = rect.clone();
+    polygon.rotation = 180.0;
+    polygon.shape = ObjectShape::Polygon {
+        points: vec![(0.0, 0.0), (10.0, -5.0), (20.0, 10.0)],
+    };
+    assert_box_eq(polygon.bounding_box(), (80.0, 40.0, 20.0, 15.0));
+}
+
+#[test]
+fn test_comma_decimal_probability() {
+    match read_from_file(Path::new("assets/tiled_comma_probability.tmx")) {
+        Err(Error::MalformedAttributes(message)) => {
+            assert!(message.contains("\"0,5\""), "{}", message);
+            assert!(message.contains("decimal separator"), "{}", message);
+        }
+        other => panic!("expected malformed attributes, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_layer_summary() {
+    let r = read_from_file(Path::new("assets/tiled_nested_groups.tmx")).unwrap();
+    assert_eq!(
+        r.layer_summary(),
+        LayerSummary {
+            tile_layers: 3,
+            image_layers: 2,
+            object_groups: 2,
+            groups: 2,
+        }
+    );
+}
+
+#[test]
+fn test_file_and_object_properties() {
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let spawn = &r.object_groups[0].objects[0];
+    assert_eq!(
+        spawn.properties.get("script"),
+        Some(&PropertyValue::FileValue(PathBuf::from(
+            "scripts/spawn.lua"
+        )))
+    );
+    assert_eq!(
+        spawn.properties.get("target"),
+        Some(&PropertyValue::ObjectValue(2))
+    );
+    assert_eq!(
+        spawn.properties.get("no target"),
+        Some(&PropertyValue::ObjectValue(0))
+    );
+}
+
+#[test]
+fn test_fingerprint() {
+    let a = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let b = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    assert_eq!(a.fingerprint(), b.fingerprint());
+
+    let mut changed = b.clone();
+    changed.object_groups[0].objects[1].x += 1.0;
+    assert_ne!(a.fingerprint(), changed.fingerprint());
+
+    let mut changed = b.clone();
+    changed
+        .properties
+        .insert("new property".to_string(), PropertyValue::BoolValue(true));
+    assert_ne!(a.fingerprint(), changed.fingerprint());
+
+    // Maps keyed by name are hashed in key order, whatever the order of the
+    // `HashMap`.
+    let (mut forward, mut backward) = (b.clone(), b.clone());
+    for i in 0..20 {
+        forward.extra.insert(format!("attr{}", i), i.to_string());
+    }
+    for i in (0..20).rev() {
+        backward.extra.insert(format!("attr{}", i), i.to_string());
+    }
+    assert_eq!(forward.fingerprint(), backward.fingerprint());
+    assert_ne!(a.fingerprint(), forward.fingerprint());
+
+    // The layer index is derived by the parser, not part of the map.
+    let mut reindexed = b;
+    reindexed.object_groups[0].layer_index = Some(7);
+    assert_eq!(a.fingerprint(), reindexed.fingerprint());
+
+    // The order of the layers is.
+    let region = read_from_file(Path::new("assets/tiled_region.tmx")).unwrap();
+    let mut reordered = region.clone();
+    reordered.layers[0].layer_index = 1;
+    reordered.object_groups[0].layer_index = Some(0);
+    assert_ne!(region.fingerprint(), reordered.fingerprint());
+
+    let csv = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    let base64 = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    assert_eq!(csv.fingerprint(), base64.fingerprint());
+    let mut edited = csv.clone();
+    edited.layers[0].tiles[0][0] += 1;
+    assert_ne!(csv.fingerprint(), edited.fingerprint());
+}
+
+#[test]
+fn test_class() {
+    let r = read_from_file(Path::new("assets/tiled_class.tmx")).unwrap();
+    assert_eq!(r.class.as_deref(), Some("Level"));
+    let tileset = &r.tilesets[0];
+    assert_eq!(tileset.class.as_deref(), Some("Terrain"));
+    assert_eq!(tileset.tiles[0].tile_type.as_deref(), Some("Grass"));
+    // Files older than Tiled 1.9 use `type` instead.
+    assert_eq!(tileset.tiles[1].tile_type.as_deref(), Some("Water"));
+    assert_eq!(r.layers[0].class.as_deref(), Some("Walkable"));
+    assert_eq!(r.layers[1].class, None);
+    assert_eq!(r.image_layers[0].class.as_deref(), Some("Parallax"));
+    let group = &r.groups[0];
+    assert_eq!(group.class.as_deref(), Some("Room"));
+    let object_group = &group.object_groups[0];
+    assert_eq!(object_group.class.as_deref(), Some("Spawns"));
+    assert_eq!(object_group.objects[0].obj_type, "Player");
+    assert_eq!(object_group.objects[1].obj_type, "Enemy");
+}
+
+#[test]
+fn test_write_round_trip() {
+    let maps = [
+        "assets/tiled_base64.tmx",
+        "assets/tiled_csv.tmx",
+        "assets/tiled_objects.tmx",
+        "assets/tiled_group.tmx",
+        "assets/tiled_image_layers.tmx",
+        "assets/tiled_layer_attributes.tmx",
+        "assets/tiled_class.tmx",
+        "assets/tiled_nested_groups.tmx",
+        "assets/tiled_editorsettings.tmx",
+        "assets/tiled_infinite.tmx",
+        "assets/tiled_infinite_flat.tmx",
+    ];
+    for path in maps.iter() {
+        let map = read_from_file(Path::new(path)).unwrap();
+        let mut tmx = Vec::new();
+        map.write(&mut tmx).unwrap();
+        let written = Map::parse(&tmx[..]).unwrap();
+        assert_eq!(map, written, "{}", path);
+    }
+}
+
+#[test]
+fn test_write_flattens_templates() {
+    let map = read_from_file_with_path(Path::new("assets/tiled_object_template.tmx")).unwrap();
+    let mut tmx = Vec::new();
+    map.write(&mut tmx).unwrap();
+    assert!(!String::from_utf8(tmx.clone()).unwrap().contains("template"));
+    // Without its location, the map can only be read if nothing refers to the
+    // template anymore.
+    let written = Map::parse(&tmx[..]).unwrap();
+    assert_eq!(map, written);
+    assert_eq!(written.object_groups[0].objects[0].name, "crate");
+}
+
+#[test]
+fn test_map_builder() {
+    let file = File::open(Path::new("assets/tilesheet.tsx")).unwrap();
+    let tileset = Tileset::parse(file, 1).unwrap();
+    let layer = Layer {
+        id: Some(1),
+        name: "Ground".to_string(),
+        class: None,
+        opacity: 1.0,
+        visible: true,
+        locked: false,
+        tint_color: None,
+        offset_x: 0.0,
+        offset_y: 0.0,
+        tiles: vec![vec![1, 2], vec![0, 3]],
+        chunks: Vec::new(),
+        properties: Default::default(),
+        layer_index: 7,
+        extra: Default::default(),
+    };
+    let map = MapBuilder::new()
+        .orientation(Orientation::Orthogonal)
+        .dimensions(2, 2)
+        .tile_size(32, 32)
+        .add_tileset(tileset.clone())
+        .add_layer(layer)
+        .build()
+        .unwrap();
+    assert_eq!(map.layers[0].layer_index, 0);
+    assert_eq!(map.get_tileset_by_gid(3).unwrap().name, "tilesheet");
+
+    let mut tmx = Vec::new();
+    map.write(&mut tmx).unwrap();
+    assert_eq!(Map::parse(&tmx[..]).unwrap(), map);
+
+    let empty = MapBuilder::new().tile_size(32, 32).build();
+    assert!(matches!(empty, Err(Error::MalformedAttributes(_))));
+    let duplicate = MapBuilder::new()
+        .dimensions(2, 2)
+        .tile_size(32, 32)
+        .add_tileset(tileset.clone())
+        .add_tileset(tileset)
+        .build();
+    assert!(matches!(duplicate, Err(Error::MalformedAttributes(_))));
+}
+
+#[test]
+fn test_write_external_tileset_reference() {
+    let load = |source: &str| {
+        assert_eq!(source, "tilesheet.tsx");
+        let tileset: &[u8] = include_bytes!("../assets/tilesheet.tsx");
+        Ok(Box::new(tileset) as Box<dyn Read>)
+    };
+    let file = File::open(Path::new("assets/tiled_base64_external.tmx")).unwrap();
+    let map = Map::parse_with_loader(file, load).unwrap();
+    let mut tmx = Vec::new();
+    map.write(&mut tmx).unwrap();
+    let written = Map::parse_with_loader(&tmx[..], load).unwrap();
+    assert_eq!(map, written);
+
+    // Tilesets are referenced relative to the map, even though `source` has
+    // been resolved against the map's path.
+    let path = Path::new("assets/tiled_base64_external.tmx");
+    let map = Map::parse_file(path).unwrap();
+    assert_eq!(
+        map.tilesets[0].source.as_deref(),
+        Some(Path::new("assets/tilesheet.tsx"))
+    );
+    let copy = Path::new("assets/tiled_base64_external_written.tmx");
+    map.write(File::create(copy).unwrap()).unwrap();
+    let written = Map::parse_file(copy);
+    std::fs::remove_file(copy).unwrap();
+    assert_eq!(written.unwrap(), map);
+}
+
+#[test]
+fn test_csv_split_data() {
+    let r = read_from_file(Path::new("assets/tiled_csv_split.tmx")).unwrap();
+    assert_eq!(r.layers[0].tiles, vec![vec![1, 2, 3], vec![4, 5, 6]]);
+}
+
+#[test]
+fn test_layer_data_size_mismatch() {
+    match read_from_file(Path::new("assets/tiled_csv_missing_row.tmx")) {
+        Err(Error::MalformedAttributes(message)) => {
+            assert!(message.contains("2 rows"), "{}", message)
+        }
+        other => panic!("expected a malformed attributes error, got {:?}", other),
+    }
+    match read_from_file(Path::new("assets/tiled_csv_short_row.tmx")) {
+        Err(Error::MalformedAttributes(message)) => {
+            assert!(message.contains("row 1"), "{}", message)
+        }
+        other => panic!("expected a malformed attributes error, got {:?}", other),
+    }
+
+    // Only the layer with the wrong size is left out when collecting errors.
+    let file = File::open("assets/tiled_csv_missing_row.tmx").unwrap();
+    let (map, errors) = Map::parse_collecting_errors(file, None).unwrap();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_eq!(map.layers.len(), 1);
+    assert_eq!(map.layers[0].name, "Tile Layer 2");
+    assert_eq!(map.layers[0].tiles[2], [13, 14, 15]);
+
+    // Without a height of their own, layers are checked against the map.
+    let tmx = include_str!("../assets/tiled_csv_missing_row.tmx")
+        .replace(r#" width="3" height="3">"#, ">");
+    match Map::parse_str(&tmx) {
+        Err(Error::MalformedAttributes(message)) => {
+            assert!(message.contains("3 tiles high"), "{}", message)
+        }
+        other => panic!("expected a malformed attributes error, got {:?}", other),
+    }
+    let (map, errors) = Map::parse_collecting_errors(tmx.as_bytes(), None).unwrap();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_eq!(map.layers.len(), 1);
+    assert_eq!(map.layers[0].name, "Tile Layer 2");
+    assert_eq!(map.layers[0].layer_index, 0);
+    let mut reader = MapReader::new(tmx.as_bytes(), None).unwrap();
+    assert!(reader.next().unwrap().is_err());
+}
+
+#[test]
+fn test_base64_split_data() {
+    let r = read_from_file(Path::new("assets/tiled_base64_split.tmx")).unwrap();
+    assert_eq!(r.layers[0].tiles, vec![vec![1, 2], vec![3, 4]]);
+
+    let invalid = "<map version=\"1.0\" orientation=\"orthogonal\" width=\"1\" height=\"1\" tilewidth=\"32\" tileheight=\"32\">
+ <layer name=\"Tile Layer 1\" width=\"1\" height=\"1\">
+  <data encoding=\"base64\">AQAA*AA=</data>
+ </layer>
+</map>";
+    match Map::parse(invalid.as_bytes()) {
+        Err(Error::Other(message)) => assert!(message.contains("'*'"), "{}", message),
+        other => panic!("expected an invalid character error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_tileset_gid_of() {
+    let t = Tileset::parse(File::open(Path::new("assets/tilesheet.tsx")).unwrap(), 100).unwrap();
+    let tile = t.tiles.iter().find(|tile| tile.id == 1).unwrap();
+    assert_eq!(t.gid_of(tile), 101);
+
+    let r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    let tileset = &r.tilesets[0];
+    for tile in tileset.tiles.iter() {
+        assert_eq!(r.get_tile_by_gid(tileset.gid_of(tile)), Some(tile));
+    }
+}
+
+#[test]
+fn test_base64_short_row() {
+    // Three tiles of data for a layer two tiles wide.
+    let map = "<map version=\"1.0\" orientation=\"orthogonal\" width=\"2\" height=\"2\" tilewidth=\"32\" tileheight=\"32\">
+ <layer name=\"Tile Layer 1\" width=\"2\" height=\"2\">
+  <data encoding=\"base64\">AQAAAAIAAAADAAAA</data>
+ </layer>
+</map>";
+    match Map::parse(map.as_bytes()) {
+        Err(Error::MalformedAttributes(message)) => {
+            assert!(message.contains("12 bytes"), "{}", message)
+        }
+        other => panic!("expected malformed attributes, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_tile_flip_d() {
+    let tileset = "<tileset name=\"flipped\" tilewidth=\"32\" tileheight=\"32\">
+ <tile id=\"1\"/>
+ <tile id=\"536870914\"/>
+ <tile id=\"2684354563\"/>
+</tileset>";
+    let t = Tileset::parse(tileset.as_bytes(), 1).unwrap();
+    let flips: Vec<_> = t
+        .tiles
+        .iter()
+        .map(|tile| (tile.id, tile.flip_h, tile.flip_v, tile.flip_d))
+        .collect();
+    assert_eq!(
+        flips,
+        vec![
+            (1, false, false, false),
+            // Diagonal only.
+            (2, true, true, true),
+            // Diagonal and horizontal.
+            (3, false, true, true),
+        ]
+    );
+}
+
+#[test]
+fn test_empty_object_groups() {
+    let r = read_from_file(Path::new("assets/tiled_empty_objectgroup.tmx")).unwrap();
+    assert_eq!(r.object_groups.len(), 2);
+    let notes = &r.object_groups[0];
+    assert_eq!(notes.name, "Notes");
+    assert_eq!(
+        notes.color,
+        Some(Color {
+            red: 0xff,
+            green: 0x80,
+            blue: 0x00,
+            alpha: 0xff
+        })
+    );
+    assert_eq!(notes.opacity, 0.5);
+    assert!(notes.objects.is_empty());
+    let regions = &r.object_groups[1];
+    assert_eq!(regions.name, "Regions");
+    assert_eq!(regions.opacity, 0.25);
+    assert!(regions.objects.is_empty());
+    // The layer after the empty groups must still be parsed.
+    assert_eq!(r.layers.len(), 1);
+    assert_eq!(r.layers[0].name, "Ground");
+}
+
+#[test]
+fn test_object_group_and_image_layer_by_name() {
+    let r = read_from_file(Path::new("assets/tiled_empty_objectgroup.tmx")).unwrap();
+    assert_eq!(r.object_group_by_name("Regions").unwrap().id, Some(2));
+    assert!(r.object_group_by_name("Ground").is_none());
+    let r = read_from_file(Path::new("assets/tiled_image_layers.tmx")).unwrap();
+    let layer = r.image_layer_by_name("Image Layer 2").unwrap();
+    assert!(layer.image.is_some());
+    assert!(r.image_layer_by_name("Missing").is_none());
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_write_json() {
+    let map = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let mut tmj = Vec::new();
+    map.write_json(&mut tmj).unwrap();
+    let tmj: serde_json::Value = serde_json::from_slice(&tmj).unwrap();
+    assert_eq!(tmj["type"], "map");
+    assert_eq!(tmj["orientation"], "orthogonal");
+    assert_eq!(tmj["renderorder"], "right-down");
+    assert_eq!(tmj["tilewidth"], 32);
+    assert_eq!(tmj["tilesets"][0]["name"], "tilesheet");
+    assert_eq!(tmj["tilesets"][0]["imagewidth"], 448);
+    let objects = &tmj["layers"][0]["objects"];
+    assert_eq!(tmj["layers"][0]["type"], "objectgroup");
+    assert_eq!(objects[0]["point"], true);
+    assert_eq!(objects[0]["properties"][0]["name"], "no target");
+    assert_eq!(objects[0]["properties"][1]["type"], "file");
+    assert_eq!(objects[1]["visible"], false);
+    assert_eq!(objects[2]["text"]["text"], "Hello, world!");
+    assert_eq!(objects[2]["text"]["halign"], "center");
+
+    let map = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    let mut tmj = Vec::new();
+    map.write_json(&mut tmj).unwrap();
+    let tmj: serde_json::Value = serde_json::from_slice(&tmj).unwrap();
+    let layer = &tmj["layers"][0];
+    assert_eq!(layer["type"], "tilelayer");
+    assert_eq!(layer["width"], map.width);
+    let data: Vec<u32> = layer["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|gid| gid.as_u64().unwrap() as u32)
+        .collect();
+    assert_eq!(data, map.layers[0].tiles_flat());
+
+    let map = read_from_file(Path::new("assets/tiled_infinite.tmx")).unwrap();
+    let mut tmj = Vec::new();
+    map.write_json(&mut tmj).unwrap();
+    let tmj: serde_json::Value = serde_json::from_slice(&tmj).unwrap();
+    assert_eq!(tmj["infinite"], true);
+    let layer = &tmj["layers"][0];
+    assert_eq!(layer["startx"], -2);
+    assert_eq!(layer["width"], 4);
+    assert_eq!(layer["chunks"][1]["data"], serde_json::json!([5, 6, 7, 0]));
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_parse_json() {
+    let map = Map::parse_json_file(Path::new("assets/tiled_json.tmj")).unwrap();
+    assert_eq!(map.version, "1.10");
+    assert_eq!(map.render_order, RenderOrder::LeftUp);
+    assert_eq!(map.properties.get_string("title"), Some("JSON map"));
+    assert_eq!(map.tilesets.len(), 2);
+    assert_eq!(
+        map.tilesets[0].tiles[0].tile_type.as_deref(),
+        Some("liquid")
+    );
+    assert_eq!(map.tilesets[0].tiles[0].animation_duration(), Some(300));
+    assert_eq!(map.tilesets[0].tiles[1].collision_shapes().count(), 1);
+    assert_eq!(map.tilesets[0].wang_sets[0].colors[0].tile, None);
+    assert_eq!(map.tilesets[1].tileset_index, 1);
+    assert_eq!(
+        map.tilesets[1].source_attribute.as_deref(),
+        Some("tilesheet.tsx")
+    );
+    assert_eq!(map.tilesets[1].tile_count, Some(84));
+
+    let ground = &map.layers[0];
+    assert_eq!(ground.tiles[1][2], 0x80000006);
+    assert_eq!(ground.properties.get_bool("solid"), Some(true));
+    let base64 = &map.layers[1];
+    assert_eq!(
+        base64.tiles_flat(),
+        &[1, 2, 3, 4, 0, 0, 0x80000005, 6, 7, 8, 9, 10]
+    );
+    assert_eq!((base64.opacity, base64.visible), (0.5, false));
+    assert_eq!((base64.offset_x, base64.offset_y), (4.0, -2.5));
+
+    let objects = &map.object_groups[0];
+    assert_eq!(objects.draw_order, DrawOrder::Index);
+    assert_eq!(objects.extra.get("offsetx").map(String::as_str), Some("8"));
+    assert_eq!(objects.extra.get("offsety").map(String::as_str), Some("-4"));
+    let door = &objects.objects[0];
+    assert_eq!(door.obj_type, "door");
+    assert_eq!(
+        door.properties.get("target"),
+        Some(&PropertyValue::ObjectValue(2))
+    );
+    assert_eq!(door.properties.get_float("weight"), Some(1.25));
+    assert_eq!(door.properties.get_int("count"), Some(-3));
+    assert_eq!(door.properties.get_color("glow"), Some(0x80ff0000));
+    assert_eq!(door.properties.get_string("label"), Some("front"));
+    assert_eq!(
+        objects.objects[1].shape,
+        ObjectShape::Ellipse {
+            width: 10.0,
+            height: 20.0
+        }
+    );
+    assert_eq!(objects.objects[2].shape, ObjectShape::Point);
+    assert_eq!(
+        objects.objects[3].shape,
+        ObjectShape::Polygon {
+            points: vec![(0.0, 0.0), (32.0, 0.0), (16.0, -24.0)]
+        }
+    );
+    match objects.objects[5].shape {
+        ObjectShape::Text { ref text, .. } => {
+            assert_eq!(text.contents, "Hello, world!");
+            assert_eq!(text.halign, HorizontalAlignment::Center);
+            assert_eq!(text.valign, VerticalAlignment::Top);
+            assert!(text.bold && text.wrap && text.kerning);
+        }
+        ref shape => panic!("expected a text object, got {:?}", shape),
+    }
+    assert_eq!(map.local_id(objects.objects[6].gid), Some(0));
+
+    let background = map.image_layers[0].image.as_ref().unwrap();
+    assert_eq!(background.dimensions(), (448, 192));
+    let group = &map.groups[0];
+    assert_eq!(group.layer_index, 4);
+    assert_eq!(group.layers[0].layer_index, 5);
+    assert_eq!(group.layers[0].tiles[1][1], 84);
+    assert!(group.image_layers[0].image.is_none());
+
+    // Writing the map and parsing it again gives the same map. The copy sits
+    // next to the original, so that the external tileset is found.
+    let copy = Path::new("assets/tiled_json_written.tmj");
+    map.write_json(File::create(copy).unwrap()).unwrap();
+    let written = Map::parse_json_file(copy);
+    std::fs::remove_file(copy).unwrap();
+    assert_eq!(written.unwrap(), map);
+
+    // The JSON version of a TMX map parses to the same map.
+    let tmx = read_from_file(Path::new("assets/tiled_infinite.tmx")).unwrap();
+    let tmj = Map::parse_json(File::open("assets/tiled_infinite.tmj").unwrap()).unwrap();
+    assert_eq!(tmj, tmx);
+
+    let e = Map::parse_json(File::open("assets/tiled_json.tmj").unwrap()).unwrap_err();
+    assert!(e.to_string().contains("external tilesets"));
+    let e = Map::parse_json(&b"{\"type\": \"map\", \"version\": \"1.10\"}"[..]).unwrap_err();
+    assert!(matches!(e, Error::MalformedAttributes(_)));
+    assert!(matches!(
+        Map::parse_json(&b"<map/>"[..]),
+        Err(Error::JsonDecodingError(_))
+    ));
+
+    // Objects that use a template are an error of their own, not a broken map.
+    let mut tmj: serde_json::Value =
+        serde_json::from_reader(File::open("assets/tiled_infinite.tmj").unwrap()).unwrap();
+    tmj["layers"]
+        .as_array_mut()
+        .unwrap()
+        .push(serde_json::json!({
+            "type": "objectgroup",
+            "name": "Templates",
+            "objects": [{"id": 1, "template": "crate.tx", "x": 0, "y": 0}]
+        }));
+    let e = Map::parse_json(tmj.to_string().as_bytes()).unwrap_err();
+    assert!(matches!(e, Error::Unsupported(_)), "{}", e);
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn test_parse_async() {
+    let r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    let tmx: &[u8] = include_bytes!("../assets/tiled_base64.tmx");
+    assert_eq!(Map::parse_async(tmx).await.unwrap(), r);
+
+    let path = Path::new("assets/tiled_base64_external.tmx");
+    let e = read_from_file_with_path(path).unwrap();
+    assert_eq!(Map::parse_file_async(path).await.unwrap(), e);
+
+    let tmx: &[u8] = include_bytes!("../assets/tiled_base64_external.tmx");
+    let load = |source: String| async move {
+        assert_eq!(source, "tilesheet.tsx");
+        Ok(include_bytes!("../assets/tilesheet.tsx").to_vec())
+    };
+    let loaded = Map::parse_with_loader_async(tmx, load).await.unwrap();
+    assert_eq!(loaded.tilesets[0].name, "tilesheet");
+    assert_eq!(loaded.layers, e.layers);
+
+    let path = Path::new("assets/tiled_tile_template.tmx");
+    let t = read_from_file_with_path(path).unwrap();
+    assert_eq!(Map::parse_file_async(path).await.unwrap(), t);
+
+    // The templates of an external tileset are loaded relative to it.
+    let path = Path::new("assets/tiled_collision_template.tmx");
+    let c = read_from_file_with_path(path).unwrap();
+    assert_eq!(Map::parse_file_async(path).await.unwrap(), c);
+
+    let tmx: &[u8] = include_bytes!("../assets/tiled_collision_template.tmx");
+    let load = |source: String| async move {
+        std::fs::read(Path::new("assets").join(&source)).map_err(|_| Error::Other(source))
+    };
+    let loaded = Map::parse_with_loader_async(tmx, load).await.unwrap();
+    let shape = loaded.tilesets[1].tiles[0]
+        .collision_shapes()
+        .next()
+        .unwrap();
+    assert_eq!((shape.name.as_str(), shape.x, shape.y), ("box", 2.0, 2.0));
+}
+
+#[cfg(feature = "bevy")]
+#[test]
+fn test_bevy_loader() {
+    use bevy_app::{App, TaskPoolPlugin};
+    use bevy_asset::{AssetPlugin, AssetServer, Assets, Handle, LoadState};
+    use tiled::{TiledMap, TiledPlugin};
+
+    let mut app = App::new();
+    app.add_plugins((
+        TaskPoolPlugin::default(),
+        AssetPlugin::default(),
+        TiledPlugin,
+    ));
+    let handle: Handle<TiledMap> = app
+        .world()
+        .resource::<AssetServer>()
+        .load("tiled_collision_template.tmx");
+    for _ in 0..1000 {
+        app.update();
+        if app
+            .world()
+            .resource::<Assets<TiledMap>>()
+            .contains(handle.id())
+        {
+            break;
+        }
+        let state = app
+            .world()
+            .resource::<AssetServer>()
+            .load_state(handle.id());
+        assert!(!matches!(state, LoadState::Failed(_)), "{:?}", state);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+
+    let maps = app.world().resource::<Assets<TiledMap>>();
+    let map = &maps.get(&handle).expect("the map was not loaded").map;
+    let expected = read_from_file_with_path(Path::new("assets/tiled_collision_template.tmx"));
+    assert_eq!(map.layers, expected.unwrap().layers);
+    for tileset in map.tilesets.iter() {
+        let shape = tileset.tiles[0].collision_shapes().next().unwrap();
+        assert_eq!(shape.name, "box");
+    }
+}
+
+#[cfg(feature = "rand")]
+#[test]
+fn test_weighted_random_tile() {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let mut rng = StdRng::seed_from_u64(1);
+    let tsx = "<tileset name=\"weighted\" tilewidth=\"32\" tileheight=\"32\" tilecount=\"4\" columns=\"2\">
+ <image source=\"weighted.png\" width=\"64\" height=\"64\"/>
+ <tile id=\"0\" probability=\"0\"/>
+ <tile id=\"1\" probability=\"0\"/>
+ <tile id=\"3\" probability=\"0\"/>
+</tileset>";
+    let t = Tileset::parse_str(tsx, 1).unwrap();
+    for _ in 0..100 {
+        assert_eq!(t.weighted_random_tile(&mut rng), Some(2));
+    }
+
+    let none = tsx.replace(
+        "<tile id=\"3\"",
+        "<tile id=\"2\" probability=\"0\"/><tile id=\"3\"",
+    );
+    let t = Tileset::parse_str(&none, 1).unwrap();
+    assert_eq!(t.weighted_random_tile(&mut rng), None);
+
+    let t = Tileset::parse(File::open(Path::new("assets/tilesheet.tsx")).unwrap(), 1).unwrap();
+    let mut picked = HashSet::new();
+    for _ in 0..1000 {
+        picked.insert(t.weighted_random_tile(&mut rng).unwrap());
+    }
+    assert!(picked.iter().all(|&id| id < 84));
+    assert!(picked.len() > 1);
+
+    // The ids of an image collection can have gaps, and only its tiles exist.
+    let collection = "<tileset name=\"c\" tilewidth=\"32\" tileheight=\"32\" tilecount=\"3\">
+ <tile id=\"0\" probability=\"0\"><image width=\"32\" height=\"32\" source=\"rock.png\"/></tile>
+ <tile id=\"5\"><image width=\"32\" height=\"32\" source=\"tree.png\"/></tile>
+ <tile id=\"9\" probability=\"0\"><image width=\"32\" height=\"32\" source=\"bush.png\"/></tile>
+</tileset>";
+    let t = Tileset::parse_str(collection, 1).unwrap();
+    for _ in 0..100 {
+        assert_eq!(t.weighted_random_tile(&mut rng), Some(5));
+    }
+}
+
+#[test]
+fn test_error_source() {
+    use std::error::Error as StdError;
+
+    let e = Map::parse("<map".as_bytes()).unwrap_err();
+    match e {
+        Error::XmlDecodingError(_) => assert!(e.source().is_some()),
+        _ => panic!("expected an XML decoding error, got {:?}", e),
+    }
+    let e = Error::Other("message".to_string());
+    assert!(e.source().is_none());
+}
+
+#[test]
+fn test_editor_settings() {
+    let r = read_from_file(Path::new("assets/tiled_editorsettings.tmx")).unwrap();
+    assert_eq!(r.chunk_size, Some((32, 8)));
+    assert_eq!(r.layers[0].tiles, vec![vec![1, 2], vec![3, 4]]);
+    let r = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    assert_eq!(r.chunk_size, None);
+}
+
+#[test]
+fn test_error_from() {
+    fn decode(data: &str) -> Result<Vec<u8>, Error> {
+        Ok(base64::decode(data)?)
+    }
+    match decode("not base64!") {
+        Err(Error::Base64DecodingError(_)) => {}
+        r => panic!("expected a base64 decoding error, got {:?}", r),
+    }
+    let e: Error = std::io::Error::new(std::io::ErrorKind::InvalidData, "io").into();
+    match e {
+        Error::Io(_) => {}
+        e => panic!("expected an io error, got {:?}", e),
+    }
+}
+
+#[test]
+fn test_color_ordering() {
+    let mut colors: Vec<Color> = ["#ff0000", "#00ff00", "#80ff0000", "#0000ff", "#00ff01"]
+        .iter()
+        .map(|c| c.parse().unwrap())
+        .collect();
+    colors.sort();
+    let colors: Vec<_> = colors
+        .iter()
+        .map(|c| (c.red, c.green, c.blue, c.alpha))
+        .collect();
+    assert_eq!(
+        colors,
+        vec![
+            (0x00, 0x00, 0xff, 0xff),
+            (0x00, 0xff, 0x00, 0xff),
+            (0x00, 0xff, 0x01, 0xff),
+            (0xff, 0x00, 0x00, 0x80),
+            (0xff, 0x00, 0x00, 0xff),
+        ]
+    );
+}
+
+#[test]
+fn test_integer_object_coordinates() {
+    let map = "<map version=\"1.0\" orientation=\"orthogonal\" width=\"4\" height=\"4\" tilewidth=\"16\" tileheight=\"16\">
+ <objectgroup name=\"Objects\">
+  <object id=\"1\" x=\"10\" y=\"20\" width=\"16\" height=\"8\"/>
+  <object id=\"2\" x=\"10.5\" y=\"-3\"/>
+ </objectgroup>
+</map>";
+    let r = Map::parse(map.as_bytes()).unwrap();
+    let objects = &r.object_groups[0].objects;
+    assert_eq!((objects[0].x, objects[0].y), (10.0, 20.0));
+    assert_eq!(
+        objects[0].shape,
+        ObjectShape::Rect {
+            width: 16.0,
+            height: 8.0
+        }
+    );
+    assert_eq!((objects[1].x, objects[1].y), (10.5, -3.0));
+}
+
+#[test]
+fn test_wang_sets() {
+    let r = read_from_file_with_path(Path::new("assets/tiled_wangsets.tmx")).unwrap();
+
+    let paths = &r.tilesets[0].wang_sets[0];
+    assert_eq!(paths.name, "Paths");
+    assert_eq!(paths.wang_type, None);
+    assert_eq!(paths.tile, Some(5));
+    assert_eq!(paths.colors[0].name, "Road");
+    assert_eq!(paths.colors[0].tile, None);
+    // Pre-1.5 wang ids are hexadecimal.
+    assert_eq!(
+        paths.tiles,
+        vec![WangTile {
+            tile_id: 3,
+            wang_id: [0, 1, 0, 1, 0, 1, 0, 1]
+        }]
+    );
+
+    let ground = &r.tilesets[1].wang_sets[0];
+    assert_eq!(ground.name, "Ground");
+    assert_eq!(ground.wang_type.as_deref(), Some("corner"));
+    assert_eq!(ground.tile, None);
+    assert_eq!(
+        ground.properties.get("layer"),
+        Some(&PropertyValue::StringValue("floor".to_string()))
+    );
+    assert_eq!(ground.colors.len(), 2);
+    let grass = &ground.colors[0];
+    assert_eq!(grass.name, "Grass");
+    assert_eq!(grass.color, "#00ff00".parse().unwrap());
+    assert_eq!(grass.tile, Some(2));
+    assert_eq!(grass.probability, 0.5);
+    assert_eq!(
+        grass.properties.get("walkable"),
+        Some(&PropertyValue::BoolValue(true))
+    );
+    assert_eq!(ground.tiles[1].tile_id, 1);
+    assert_eq!(ground.tiles[1].wang_id, [0, 2, 0, 1, 0, 2, 0, 2]);
+
+    // Only the embedded tileset is written out in full.
+    let mut r = r;
+    r.tilesets.truncate(1);
+    let mut tmx = Vec::new();
+    r.write(&mut tmx).unwrap();
+    let written = Map::parse(&tmx[..]).unwrap();
+    assert_eq!(written.tilesets, r.tilesets);
+}
+
+#[test]
+fn test_parse_collecting_errors() {
+    let file = File::open(Path::new("assets/tiled_malformed_layers.tmx")).unwrap();
+    let (r, errors) = Map::parse_collecting_errors(file, None).unwrap();
+    let names: Vec<_> = r.layers.iter().map(|layer| layer.name.as_str()).collect();
+    assert_eq!(names, vec!["Ground"]);
+    assert_eq!(r.groups[0].layers[0].name, "Nested");
+    assert_eq!(r.groups[0].layers[0].layer_index, 2);
+    assert!(r.groups[0].object_groups.is_empty());
+    assert_eq!(errors.len(), 3);
+    match errors[0] {
+        Error::MalformedAttributes(ref s) => assert!(s.contains("layer must have a name")),
+        ref e => panic!("unexpected error {:?}", e),
+    }
+    match errors[1] {
+        Error::Other(ref s) => assert!(s.contains("invalid character")),
+        ref e => panic!("unexpected error {:?}", e),
+    }
+    match errors[2] {
+        Error::MalformedAttributes(ref s) => assert!(s.contains("x")),
+        ref e => panic!("unexpected error {:?}", e),
+    }
+
+    // Without collecting, the first error aborts the parse.
+    let file = File::open(Path::new("assets/tiled_malformed_layers.tmx")).unwrap();
+    assert!(Map::parse(file).is_err());
+}
+
+#[test]
+fn test_animation_gids() {
+    let file = File::open(Path::new("assets/tiled_animation.tsx")).unwrap();
+    let t = Tileset::parse(file, 10).unwrap();
+    assert_eq!(
+        t.animation_gids(0),
+        Some(vec![(10, 100), (11, 100), (12, 250)])
+    );
+    // A frame past the last tile of the tileset is clamped to that tile.
+    assert_eq!(t.animation_gids(3), Some(vec![(13, 100), (13, 100)]));
+    // A tile without an animation, and a tile without a `<tile>` element.
+    assert_eq!(t.animation_gids(1), None);
+    assert_eq!(t.animation_gids(2), None);
+    assert_eq!(t.animation_gids(5), None);
+}
+
+#[test]
+fn test_animation_frame_at() {
+    let file = File::open(Path::new("assets/tiled_animation.tsx")).unwrap();
+    let t = Tileset::parse(file, 1).unwrap();
+    let tile = &t.tiles[0];
+    assert_eq!(tile.animation_duration(), Some(450));
+    let frames = tile.animation.as_ref().unwrap();
+    assert_eq!((frames[2].tile_id(), frames[2].duration()), (2, 250));
+    assert_eq!(tile.animation_frame_at(0), Some(0));
+    assert_eq!(tile.animation_frame_at(99), Some(0));
+    assert_eq!(tile.animation_frame_at(100), Some(1));
+    assert_eq!(tile.animation_frame_at(200), Some(2));
+    assert_eq!(tile.animation_frame_at(449), Some(2));
+    assert_eq!(tile.animation_frame_at(450), Some(0));
+    assert_eq!(tile.animation_frame_at(1000), Some(1));
+
+    let still = &t.tiles[1];
+    assert_eq!(still.animation_duration(), None);
+    assert_eq!(still.animation_frame_at(0), None);
+}
+
+#[test]
+fn test_image_collection_tileset() {
+    let file = File::open(Path::new("assets/tiled_collection.tsx")).unwrap();
+    let t = Tileset::parse(file, 1).unwrap();
+    assert!(t.is_collection());
+    assert!(t.image().is_none());
+    assert_eq!(t.tiles[1].images[0].source, "tree.png");
+    assert_eq!(t.tiles[0].transparent_color(), None);
+    assert_eq!(
+        t.tiles[1].transparent_color(),
+        Some(Color {
+            red: 0xff,
+            green: 0x00,
+            blue: 0xff,
+            alpha: 0xff
+        })
+    );
+
+    let file = File::open(Path::new("assets/tilesheet.tsx")).unwrap();
+    assert!(!Tileset::parse(file, 1).unwrap().is_collection());
+}
+
+#[test]
+fn test_hash_images() {
+    let z = read_from_file(Path::new("assets/tiled_base64_zlib.tmx")).unwrap();
+    let c = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    let file = File::open(Path::new("assets/tiled_collection.tsx")).unwrap();
+    let collection = Tileset::parse(file, 1).unwrap();
+    let mut images = HashSet::new();
+    images.extend(z.tilesets[0].images.iter());
+    images.extend(c.tilesets[0].images.iter());
+    images.extend(collection.tiles.iter().flat_map(|tile| tile.images.iter()));
+    assert_eq!(images.len(), 3);
+}
+
+#[test]
+fn test_legacy_terrain() {
+    let file = File::open(Path::new("assets/tiled_terrain.tsx")).unwrap();
+    let t = Tileset::parse(file, 1).unwrap();
+    assert_eq!(t.terrain_types.len(), 2);
+    assert_eq!(t.terrain_types[0].name, "Grass");
+    assert_eq!(t.terrain_types[1].tile, -1);
+    assert_eq!(
+        t.tiles[0].terrain,
+        Some([Some(0), Some(0), Some(0), Some(1)])
+    );
+    assert_eq!(t.tiles[1].terrain, Some([None, Some(1), None, Some(1)]));
+    assert_eq!(t.tiles[2].terrain, None);
+}
+
+#[test]
+fn test_legacy_terrain_in_map() {
+    let r = read_from_file(Path::new("assets/tiled_legacy_terrain.tmx")).unwrap();
+    let terrain = &r.tilesets[0].terrain_types[1];
+    assert_eq!(terrain.name, "Rock");
+    assert_eq!(terrain.tile, 30);
+    let tile = r.get_tile_by_gid(r.layers[0].tiles[1][0]).unwrap();
+    assert_eq!(tile.terrain, Some([Some(1); 4]));
+}
+
+#[test]
+fn test_pixel_size() {
+    let map = |attrs: &str| {
+        let tmx = format!("<map version=\"1.0\" {}><layer name=\"l\"/></map>", attrs);
+        let r = Map::parse(tmx.as_bytes()).unwrap();
+        (r.pixel_width(), r.pixel_height())
+    };
+    assert_eq!(
+        map("orientation=\"orthogonal\" width=\"10\" height=\"8\" tilewidth=\"32\" tileheight=\"16\""),
+        (320, 128)
+    );
+    assert_eq!(
+        map(
+            "orientation=\"isometric\" width=\"4\" height=\"3\" tilewidth=\"64\" tileheight=\"32\""
+        ),
+        (224, 112)
+    );
+    assert_eq!(
+        map("orientation=\"staggered\" staggeraxis=\"y\" width=\"4\" height=\"5\" tilewidth=\"64\" tileheight=\"32\""),
+        (288, 96)
+    );
+    let r = Map::parse(
+        "<map version=\"1.0\" orientation=\"hexagonal\" staggeraxis=\"x\" hexsidelength=\"16\" width=\"5\" height=\"4\" tilewidth=\"32\" tileheight=\"28\"/>"
+            .as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(r.stagger_axis, Some(StaggerAxis::X));
+    assert_eq!(r.hex_side_length, Some(16));
+    assert_eq!((r.pixel_width(), r.pixel_height()), (128, 126));
+}
+
+#[test]
+fn test_collision_object_properties() {
+    let file = File::open(Path::new("assets/tiled_collision_properties.tsx")).unwrap();
+    let t = Tileset::parse(file, 1).unwrap();
+    let tile = &t.tiles[0];
+    assert_eq!(tile.id, 4);
+    let collider = &tile.objectgroup.as_ref().unwrap().objects[0];
+    assert_eq!((collider.x, collider.y), (0.0, 8.0));
+    assert_eq!(
+        collider.shape,
+        ObjectShape::Polygon {
+            points: vec![(0.0, 0.0), (32.0, 0.0), (32.0, 4.0), (0.0, 4.0)]
+        }
+    );
+    assert_eq!(
+        collider.properties.get("oneway"),
+        Some(&PropertyValue::BoolValue(true))
+    );
+}
+
+#[test]
+fn test_image_dimensions() {
+    let r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    let image = r.tilesets[0].image().unwrap();
+    assert_eq!(image.aspect_ratio(), Some(448.0 / 192.0));
+    assert_eq!(image.dimensions(), (448, 192));
+    assert_eq!(image.pixel_count(), Some(448 * 192));
+
+    let flat = Image {
+        source: "flat.png".to_string(),
+        width: 16,
+        height: 0,
+        transparent_color: None,
+    };
+    assert_eq!(flat.aspect_ratio(), None);
+    assert_eq!(flat.pixel_count(), None);
+
+    let negative = "<tileset name=\"negative\" tilewidth=\"32\" tileheight=\"32\">
+ <image source=\"tilesheet.png\" width=\"-448\" height=\"192\"/>
+</tileset>";
+    assert!(matches!(
+        Tileset::parse(negative.as_bytes(), 1),
+        Err(Error::MalformedAttributes(_))
+    ));
+}
+
+#[test]
+fn test_parse_str() {
+    let map = Map::parse_str(include_str!("../assets/tiled_base64.tmx")).unwrap();
+    assert_eq!(
+        map,
+        read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap()
+    );
+
+    let tileset = Tileset::parse_str(include_str!("../assets/tilesheet.tsx"), 1).unwrap();
+    assert_eq!(
+        tileset,
+        Tileset::parse(File::open(Path::new("assets/tilesheet.tsx")).unwrap(), 1).unwrap()
+    );
+}
+
+#[test]
+fn test_parse_bytes() {
+    let r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    let tmx: &[u8] = include_bytes!("../assets/tiled_base64.tmx");
+    assert_eq!(Map::parse_bytes(tmx).unwrap(), r);
+
+    let path = Path::new("assets/tiled_base64_external.tmx");
+    let tmx: &[u8] = include_bytes!("../assets/tiled_base64_external.tmx");
+    assert!(Map::parse_bytes(tmx).is_err());
+    assert_eq!(
+        Map::parse_bytes_with_path(tmx, path).unwrap(),
+        read_from_file_with_path(path).unwrap()
+    );
+}
+
+#[test]
+fn test_tileset_parse_with_path() {
+    let path = Path::new("assets/tilesets/tilesheet_parent_image.tsx");
+    let t = Tileset::parse_with_path(File::open(path).unwrap(), 1, path).unwrap();
+    assert_eq!(t.source.as_deref(), Some(path));
+    // The map path is only used for tilesets embedded in a map.
+    let image = t.image_source_path(Path::new("")).unwrap();
+    assert_eq!(
+        image.canonicalize().unwrap(),
+        Path::new("assets/tilesheet.png").canonicalize().unwrap()
+    );
+}
+
+#[test]
+fn test_normalized_tileset_path() {
+    let map_path = Path::new("assets/maps/tiled_shared_tileset.tmx");
+    let r = read_from_file_with_path(map_path).unwrap();
+    assert_eq!(
+        r.tilesets[0].source.as_deref(),
+        Some(Path::new("assets/shared/tilesheet.tsx"))
+    );
+    assert_eq!(
+        r.tilesets[0].image_source_path(map_path),
+        Some(PathBuf::from("assets/tilesheet.png"))
+    );
+}
+
+#[test]
+fn test_map_reader() {
+    let path = Path::new("assets/tiled_group.tmx");
+    let map = read_from_file(path).unwrap();
+    let mut reader = MapReader::new(File::open(path).unwrap(), None).unwrap();
+    assert_eq!(reader.header().tilesets, map.tilesets);
+    assert_eq!(reader.header().width, map.width);
+    assert!(reader.header().layers.is_empty());
+
+    let mut streamed = reader.header().clone();
+    for layer in &mut reader {
+        match layer.unwrap() {
+            ParsedLayer::Tile(layer) => streamed.layers.push(layer),
+            ParsedLayer::Image(layer) => streamed.image_layers.push(layer),
+            ParsedLayer::Objects(layer) => streamed.object_groups.push(layer),
+            ParsedLayer::Group(layer) => streamed.groups.push(layer),
+        }
+    }
+    assert!(reader.next().is_none());
+    streamed.properties = reader.into_header().properties;
+    assert_eq!(streamed, map);
+
+    // External tilesets are loaded relative to the given path.
+    let path = Path::new("assets/tiled_base64_external.tmx");
+    let reader = MapReader::new(File::open(path).unwrap(), Some(path)).unwrap();
+    assert_eq!(reader.header().tilesets.len(), 1);
+    assert_eq!(reader.count(), 2);
+}
+
+#[test]
+fn test_map_reader_error() {
+    let file = File::open(Path::new("assets/tiled_malformed_layers.tmx")).unwrap();
+    let mut reader = MapReader::new(file, None).unwrap();
+    assert!(reader.next().unwrap().is_err());
+    assert!(reader.next().is_none());
+}
+
+#[test]
+fn test_interleaved_tilesets() {
+    let r = read_from_file_with_path(Path::new("assets/tiled_interleaved_tilesets.tmx")).unwrap();
+    let first_gids: Vec<_> = r.tilesets.iter().map(|t| t.first_gid).collect();
+    assert_eq!(first_gids, vec![1, 85, 169]);
+    let indices: Vec<_> = r.tilesets.iter().map(|t| t.tileset_index).collect();
+    assert_eq!(indices, vec![0, 1, 2]);
+    for layer in r.layers.iter() {
+        for (_, _, gid) in layer.iter_tiles() {
+            assert!(r.get_tileset_by_gid(gid).is_some(), "gid {}", gid);
+        }
+    }
+    assert_eq!(r.get_tileset_by_gid(86).unwrap().tileset_index, 1);
+    let object = &r.object_groups[0].objects[0];
+    assert_eq!(r.get_tileset_by_gid(object.gid).unwrap().name, "last");
+}
+
+#[test]
+fn test_unsupported_version_warning() {
+    assert_eq!(
+        tiled::max_supported_map_version(),
+        tiled::SUPPORTED_TILED_VERSION
+    );
+    let parse = |version: &str| {
+        let tmx = format!(
+            "<map version=\"{}\" orientation=\"orthogonal\" width=\"1\" height=\"1\" tilewidth=\"8\" tileheight=\"8\"/>",
+            version
+        );
+        let mut warnings = Vec::new();
+        {
+            let options = ParseOptions {
+                on_warning: Some(Box::new(|w: String| warnings.push(w))),
+                ..ParseOptions::default()
+            };
+            let r = Map::parse_with_options(tmx.as_bytes(), None, options).unwrap();
+            assert_eq!(r.version, version);
+        }
+        warnings
+    };
+    let warnings = parse("99.0");
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("99.0"));
+    assert!(parse("1.2").is_empty());
+    assert!(parse("1.9.2").is_empty());
+    assert!(parse(tiled::SUPPORTED_TILED_VERSION).is_empty());
+    assert!(parse("1.10.0").is_empty());
+    assert_eq!(parse("1.10.1").len(), 1);
+}
+
+#[test]
+fn test_collision_shapes() {
+    let file = File::open(Path::new("assets/tiled_collision_properties.tsx")).unwrap();
+    let t = Tileset::parse(file, 1).unwrap();
+    let tile = &t.tiles[0];
+    assert_eq!(tile.objectgroup.as_ref().unwrap().layer_index, None);
+    let shapes: Vec<_> = tile.collision_shapes().collect();
+    assert_eq!(shapes.len(), 1);
+    assert_eq!(shapes[0].id, 1);
+
+    let file = File::open(Path::new("assets/tiled_terrain.tsx")).unwrap();
+    let t = Tileset::parse(file, 1).unwrap();
+    assert_eq!(t.tiles[0].collision_shapes().count(), 0);
+}